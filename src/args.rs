@@ -0,0 +1,91 @@
+use std::error;
+use std::fmt;
+
+/// Invalid argument given to a command.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ArgError {
+    /// `!skip` amount is not a positive number.
+    InvalidSkipAmount,
+    /// `!skip` amount is over the configured limit.
+    SkipLimit(usize),
+    /// `!remove` position is missing or not a number.
+    InvalidPosition,
+    /// `!remove` position points to the track being played.
+    PlayingPosition,
+}
+
+impl fmt::Display for ArgError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidSkipAmount => {
+                write!(f, "Amount of tracks to skip must be a positive integer")
+            }
+            Self::SkipLimit(limit) => write!(f, "Cannot skip more than {limit} tracks at once"),
+            Self::InvalidPosition => write!(
+                f,
+                "Must provide the position of the track, as listed by **!queue**"
+            ),
+            Self::PlayingPosition => write!(
+                f,
+                "Cannot remove the track being played. Use **!skip** instead"
+            ),
+        }
+    }
+}
+
+impl error::Error for ArgError {}
+
+/// Parses the amount of tracks skipped by `!skip`, defaulting to the one being played.
+pub fn skip_amount(arg: Option<&str>, limit: usize) -> Result<usize, ArgError> {
+    let Some(arg) = arg else {
+        return Ok(1);
+    };
+
+    match arg.trim().parse::<usize>() {
+        Ok(0) | Err(_) => Err(ArgError::InvalidSkipAmount),
+        Ok(amount) if amount > limit => Err(ArgError::SkipLimit(limit)),
+        Ok(amount) => Ok(amount),
+    }
+}
+
+/// Parses a queue position as listed by `!queue`, where `0` is the track being played.
+pub fn queue_position(arg: Option<&str>) -> Result<usize, ArgError> {
+    match arg.map(|arg| arg.trim().parse::<usize>()) {
+        Some(Ok(0)) => Err(ArgError::PlayingPosition),
+        Some(Ok(position)) => Ok(position),
+        _ => Err(ArgError::InvalidPosition),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn skips_playing_track_by_default() {
+        assert_eq!(skip_amount(None, 20), Ok(1));
+        assert_eq!(skip_amount(Some("5"), 20), Ok(5));
+    }
+
+    #[test]
+    fn rejects_invalid_skip_amounts() {
+        assert_eq!(skip_amount(Some("0"), 20), Err(ArgError::InvalidSkipAmount));
+        assert_eq!(
+            skip_amount(Some("-1"), 20),
+            Err(ArgError::InvalidSkipAmount)
+        );
+        assert_eq!(
+            skip_amount(Some("all"), 20),
+            Err(ArgError::InvalidSkipAmount)
+        );
+        assert_eq!(skip_amount(Some("21"), 20), Err(ArgError::SkipLimit(20)));
+    }
+
+    #[test]
+    fn parses_queue_positions() {
+        assert_eq!(queue_position(Some("3")), Ok(3));
+        assert_eq!(queue_position(Some("0")), Err(ArgError::PlayingPosition));
+        assert_eq!(queue_position(Some("next")), Err(ArgError::InvalidPosition));
+        assert_eq!(queue_position(None), Err(ArgError::InvalidPosition));
+    }
+}
@@ -0,0 +1,104 @@
+use std::fmt;
+
+pub const DEFAULT_MAX_SIZE_MB: u32 = 50;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Config {
+    /// Maximum size of uploaded files, in bytes.
+    pub max_size: u32,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            max_size: DEFAULT_MAX_SIZE_MB * 1024 * 1024,
+        }
+    }
+}
+
+/// File uploaded along with a command.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Attachment {
+    pub filename: String,
+    pub url: String,
+    /// Size in bytes.
+    pub size: u32,
+    pub content_type: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum AttachmentError {
+    UnsupportedType(String),
+    TooLarge { size: u32, max_size: u32 },
+}
+
+impl fmt::Display for AttachmentError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnsupportedType(content_type) => {
+                write!(f, "unsupported file type `{content_type}`")
+            }
+            Self::TooLarge { size, max_size } => {
+                let to_mb = |bytes: &u32| f64::from(*bytes) / (1024.0 * 1024.0);
+                let (size, max_size) = (to_mb(size), to_mb(max_size));
+                write!(
+                    f,
+                    "file has {size:.1}MB, but max allowed is {max_size:.1}MB"
+                )
+            }
+        }
+    }
+}
+
+/// Checks if an uploaded file of `content_type` and `size` bytes is an audio or video file
+/// small enough to be played.
+pub fn validate(
+    content_type: Option<&str>,
+    size: u32,
+    config: &Config,
+) -> Result<(), AttachmentError> {
+    let content_type = content_type.unwrap_or("unknown");
+    if !content_type.starts_with("audio/") && !content_type.starts_with("video/") {
+        return Err(AttachmentError::UnsupportedType(content_type.to_string()));
+    }
+
+    if size > config.max_size {
+        return Err(AttachmentError::TooLarge {
+            size,
+            max_size: config.max_size,
+        });
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accepts_audio_and_video_files() {
+        let config = Config::default();
+        assert_eq!(validate(Some("audio/mpeg"), 1024, &config), Ok(()));
+        assert_eq!(
+            validate(Some("video/mp4"), config.max_size, &config),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn rejects_other_or_large_files() {
+        let config = Config { max_size: 1024 };
+        assert_eq!(
+            validate(None, 10, &config),
+            Err(AttachmentError::UnsupportedType(String::from("unknown")))
+        );
+        assert_eq!(
+            validate(Some("audio/ogg"), 2048, &config),
+            Err(AttachmentError::TooLarge {
+                size: 2048,
+                max_size: 1024
+            })
+        );
+    }
+}
@@ -0,0 +1,149 @@
+use std::collections::HashSet;
+use std::error;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+use crate::metadata_cache;
+use crate::ytdlp;
+
+pub const DEFAULT_MAX_SIZE_MB: u64 = 1024;
+pub const DEFAULT_PREFETCH: usize = 3;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Config {
+    pub dir: PathBuf,
+    /// Maximum size of the directory, in bytes.
+    pub max_size: u64,
+    /// Amount of upcoming tracks downloaded ahead of time.
+    pub prefetch: usize,
+}
+
+/// Directory of audio files downloaded ahead of time, so upcoming tracks don't
+/// depend on YouTube streaming speed.
+#[derive(Debug)]
+pub struct AudioCache {
+    config: Config,
+    ytdlp: Arc<ytdlp::Config>,
+    downloading: Mutex<HashSet<String>>,
+}
+
+impl AudioCache {
+    pub async fn new(
+        config: Config,
+        ytdlp: Arc<ytdlp::Config>,
+    ) -> Result<Self, Box<dyn error::Error + Send + Sync>> {
+        tokio::fs::create_dir_all(&config.dir).await?;
+
+        Ok(Self {
+            config,
+            ytdlp,
+            downloading: Mutex::new(HashSet::new()),
+        })
+    }
+
+    pub fn prefetch_count(&self) -> usize {
+        self.config.prefetch
+    }
+
+    /// Finds the downloaded file of `url`, if completely downloaded.
+    pub async fn lookup(&self, url: &str) -> Option<PathBuf> {
+        let stem = file_stem(url);
+        let mut entries = tokio::fs::read_dir(&self.config.dir).await.ok()?;
+
+        while let Ok(Some(entry)) = entries.next_entry().await {
+            let path = entry.path();
+            let matches_stem = path.file_stem().and_then(|s| s.to_str()) == Some(stem.as_str());
+            if matches_stem && !is_partial(&path) {
+                // refresh modification time so recently played tracks are evicted last
+                let touched = path.clone();
+                let _ = tokio::task::spawn_blocking(move || {
+                    let file = std::fs::File::options().append(true).open(touched)?;
+                    file.set_modified(SystemTime::now())
+                })
+                .await;
+
+                return Some(path);
+            }
+        }
+
+        None
+    }
+
+    /// Downloads `url` in background, unless already cached or being downloaded.
+    pub async fn prefetch(self: Arc<Self>, url: String) {
+        if self.lookup(&url).await.is_some() {
+            return;
+        }
+
+        let stem = file_stem(&url);
+        {
+            let mut downloading = self.downloading.lock().expect("Audio cache lock poisoned");
+            if !downloading.insert(stem.clone()) {
+                return;
+            }
+        }
+
+        tokio::spawn(async move {
+            let template = self.config.dir.join(format!("{stem}.%(ext)s"));
+            let template = template.to_string_lossy();
+
+            match ytdlp::download(&self.ytdlp, &url, &template).await {
+                Ok(_) => tracing::info!("Downloaded {url} into audio cache"),
+                Err(err) => tracing::error!("Failed downloading {url} into audio cache: {err}"),
+            }
+
+            self.downloading
+                .lock()
+                .expect("Audio cache lock poisoned")
+                .remove(&stem);
+
+            if let Err(err) = self.evict().await {
+                tracing::error!("Failed evicting audio cache: {err}");
+            }
+        });
+    }
+
+    /// Removes least recently used files until the cache fits its size limit.
+    async fn evict(&self) -> Result<(), Box<dyn error::Error + Send + Sync>> {
+        let mut files = Vec::new();
+        let mut entries = tokio::fs::read_dir(&self.config.dir).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let path = entry.path();
+            let metadata = entry.metadata().await?;
+            if metadata.is_file() && !is_partial(&path) {
+                let modified = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+                files.push((path, metadata.len(), modified));
+            }
+        }
+
+        let mut total_size = files.iter().map(|(_, size, _)| size).sum::<u64>();
+        files.sort_by_key(|(_, _, modified)| *modified);
+
+        for (path, size, _) in files {
+            if total_size <= self.config.max_size {
+                break;
+            }
+
+            tokio::fs::remove_file(&path).await?;
+            total_size -= size;
+        }
+
+        Ok(())
+    }
+}
+
+fn is_partial(path: &Path) -> bool {
+    let extension = path.extension().and_then(|ext| ext.to_str());
+    matches!(extension, Some("part" | "ytdl" | "temp"))
+}
+
+/// Stable file name for `url`, using FNV-1a hash of its normalized form.
+fn file_stem(url: &str) -> String {
+    let key = metadata_cache::normalize_key(url);
+    let hash = key.bytes().fold(0xcbf29ce484222325u64, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x100000001b3)
+    });
+
+    format!("{hash:016x}")
+}
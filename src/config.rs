@@ -0,0 +1,736 @@
+use std::collections::HashMap;
+use std::env;
+use std::error;
+use std::fmt;
+use std::io::ErrorKind;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
+
+use serde::Deserialize;
+
+use crate::embed::EmbedStyle;
+use crate::{attachment, audio_cache, library, metadata_cache, ytdlp};
+
+const DEFAULT_CONFIG_PATH: &str = "config.toml";
+const DEFAULT_PREFIX: &str = "!";
+const DEFAULT_SKIP_LIMIT: usize = 20;
+const DEFAULT_QUEUE_DISPLAY_LIMIT: usize = 50;
+const DEFAULT_AUTO_LEAVE_DELAY: Duration = Duration::from_secs(5 * 60);
+const DEFAULT_GRACE_PERIOD: Duration = Duration::from_secs(60);
+const DEFAULT_DATA_DIR: &str = "data";
+
+/// Maximum amount of tracks listed by `!queue`, keeping its embed under Discord size limit.
+const MAX_QUEUE_DISPLAY_LIMIT: usize = 100;
+
+/// Cooldown of commands without one of their own.
+const DEFAULT_COOLDOWN: &str = "default";
+
+/// Built-in cooldowns, stricter for commands spawning `yt-dlp` processes.
+const DEFAULT_COOLDOWNS: [(&str, Cooldown); 4] = [
+    (
+        DEFAULT_COOLDOWN,
+        Cooldown::new(CooldownScope::User, 2, None),
+    ),
+    (
+        "play",
+        Cooldown::new(CooldownScope::User, 3, Some((10, 60))),
+    ),
+    (
+        "playlist-load",
+        Cooldown::new(CooldownScope::Guild, 15, Some((3, 300))),
+    ),
+    (
+        "import",
+        Cooldown::new(CooldownScope::Guild, 30, Some((2, 300))),
+    ),
+];
+
+/// Bot settings, read from `CONFIG_PATH` TOML file (defaults to `config.toml`) and
+/// overridden by environment variables.
+#[derive(Clone, Debug)]
+pub struct Config {
+    pub token: String,
+    pub prefix: String,
+    pub log_level: tracing::Level,
+    /// Maximum amount of tracks skipped at once by `!skip`.
+    pub skip_limit: usize,
+    /// Maximum amount of tracks listed by `!queue`.
+    pub queue_display_limit: usize,
+    /// Time to wait before leaving a voice channel where nothing plays, unless the guild
+    /// sets its own `auto_leave_delay`.
+    pub auto_leave_delay: Duration,
+    /// Time to wait for listeners to come back to an empty voice channel before leaving it,
+    /// unless the guild sets its own `grace_period`.
+    pub grace_period: Duration,
+    /// Whether URLs of loopback, private or link-local hosts can be played, as when
+    /// streaming from the local network.
+    pub allow_private_hosts: bool,
+    pub embed: EmbedStyle,
+    /// Cooldowns keyed by command name, along with the `default` one.
+    pub cooldowns: HashMap<String, Cooldown>,
+    /// Directory where persistent data is stored.
+    pub data_dir: PathBuf,
+    pub ytdlp: ytdlp::Config,
+    pub metadata_cache: metadata_cache::Config,
+    /// Audio cache settings, if enabled by setting its directory.
+    pub audio_cache: Option<audio_cache::Config>,
+    pub attachment: attachment::Config,
+    /// Local library settings, if enabled by setting its directory.
+    pub library: Option<library::Config>,
+}
+
+/// Who shares the uses allowed by a [`Cooldown`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CooldownScope {
+    User,
+    Guild,
+    Channel,
+    Global,
+}
+
+impl CooldownScope {
+    fn parse(value: &str) -> Option<Self> {
+        match value.to_lowercase().as_str() {
+            "user" => Some(Self::User),
+            "guild" => Some(Self::Guild),
+            "channel" => Some(Self::Channel),
+            "global" => Some(Self::Global),
+            _ => None,
+        }
+    }
+}
+
+/// Rate limit of a command.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Cooldown {
+    pub scope: CooldownScope,
+    /// Minimum time between two uses.
+    pub delay: Duration,
+    /// Maximum amount of uses within a time span, if any.
+    pub limit: Option<(u32, Duration)>,
+}
+
+impl Cooldown {
+    const fn new(scope: CooldownScope, delay: u64, limit: Option<(u32, u64)>) -> Self {
+        let limit = match limit {
+            Some((uses, secs)) => Some((uses, Duration::from_secs(secs))),
+            None => None,
+        };
+
+        Self {
+            scope,
+            delay: Duration::from_secs(delay),
+            limit,
+        }
+    }
+}
+
+/// Settings as written in the TOML file, before validation.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct RawConfig {
+    token: Option<String>,
+    prefix: Option<String>,
+    log_level: Option<String>,
+    skip_limit: Option<usize>,
+    queue_display_limit: Option<usize>,
+    auto_leave_delay: Option<u64>,
+    grace_period: Option<u64>,
+    allow_private_hosts: Option<bool>,
+    embed: RawEmbedConfig,
+    cooldowns: HashMap<String, RawCooldown>,
+    data_dir: Option<String>,
+    ytdlp: RawYtDlpConfig,
+    metadata_cache: RawMetadataCacheConfig,
+    audio_cache: RawAudioCacheConfig,
+    attachment: RawAttachmentConfig,
+    library: RawLibraryConfig,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct RawEmbedConfig {
+    author_name: Option<String>,
+    avatar_url: Option<String>,
+    color: Option<String>,
+    error_color: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct RawYtDlpConfig {
+    path: Option<String>,
+    format: Option<String>,
+    cookies: Option<String>,
+    proxy: Option<String>,
+    args: Option<Vec<String>>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct RawMetadataCacheConfig {
+    capacity: Option<usize>,
+    ttl: Option<u64>,
+    path: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct RawAudioCacheConfig {
+    dir: Option<String>,
+    max_size: Option<u64>,
+    prefetch: Option<usize>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct RawAttachmentConfig {
+    max_size: Option<u32>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct RawLibraryConfig {
+    dir: Option<String>,
+    scan_interval: Option<u64>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct RawCooldown {
+    per: Option<String>,
+    delay: Option<u64>,
+    limit: Option<u32>,
+    time_span: Option<u64>,
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    /// Configuration file could not be read or is not valid TOML.
+    File {
+        path: PathBuf,
+        reason: String,
+    },
+    Missing(&'static str),
+    Invalid {
+        key: &'static str,
+        reason: String,
+    },
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::File { path, reason } => {
+                write!(f, "could not read config file {}: {reason}", path.display())
+            }
+            Self::Missing(key) => write!(f, "missing required setting {key}"),
+            Self::Invalid { key, reason } => write!(f, "invalid setting {key}: {reason}"),
+        }
+    }
+}
+
+impl error::Error for ConfigError {}
+
+impl Config {
+    /// Loads the config file, if any, applies environment overrides and validates the result.
+    pub fn load() -> Result<Self, ConfigError> {
+        let (path, required) = match var("CONFIG_PATH") {
+            Some(path) => (PathBuf::from(path), true),
+            None => (PathBuf::from(DEFAULT_CONFIG_PATH), false),
+        };
+
+        let mut raw = match std::fs::read_to_string(&path) {
+            Ok(content) => {
+                toml::from_str::<RawConfig>(&content).map_err(|err| ConfigError::File {
+                    path: path.clone(),
+                    reason: err.to_string(),
+                })?
+            }
+            Err(err) if err.kind() == ErrorKind::NotFound && !required => RawConfig::default(),
+            Err(err) => {
+                return Err(ConfigError::File {
+                    path,
+                    reason: err.to_string(),
+                })
+            }
+        };
+
+        raw.token = var("DISCORD_TOKEN").or(raw.token);
+        raw.prefix = var("PREFIX").or(raw.prefix);
+        raw.log_level = var("LOG_LEVEL").or(raw.log_level);
+        raw.skip_limit = parse_number("SKIP_LIMIT")?.or(raw.skip_limit);
+        raw.queue_display_limit = parse_number("QUEUE_DISPLAY_LIMIT")?.or(raw.queue_display_limit);
+        raw.auto_leave_delay = parse_number("AUTO_LEAVE_DELAY")?.or(raw.auto_leave_delay);
+        raw.grace_period = parse_number("GRACE_PERIOD")?.or(raw.grace_period);
+        raw.allow_private_hosts = parse_bool("ALLOW_PRIVATE_HOSTS")?.or(raw.allow_private_hosts);
+        raw.embed.author_name = var("EMBED_AUTHOR_NAME").or(raw.embed.author_name);
+        raw.embed.avatar_url = var("EMBED_AVATAR_URL").or(raw.embed.avatar_url);
+        raw.embed.color = var("EMBED_COLOR").or(raw.embed.color);
+        raw.embed.error_color = var("EMBED_ERROR_COLOR").or(raw.embed.error_color);
+        raw.data_dir = var("DATA_DIR").or(raw.data_dir);
+        raw.ytdlp.path = var("YTDLP_PATH").or(raw.ytdlp.path);
+        raw.ytdlp.format = var("YTDLP_FORMAT").or(raw.ytdlp.format);
+        raw.ytdlp.cookies = var("YTDLP_COOKIES").or(raw.ytdlp.cookies);
+        raw.ytdlp.proxy = var("YTDLP_PROXY").or(raw.ytdlp.proxy);
+        raw.ytdlp.args = parse_args("YTDLP_ARGS")?.or(raw.ytdlp.args);
+        raw.metadata_cache.capacity =
+            parse_number("METADATA_CACHE_CAPACITY")?.or(raw.metadata_cache.capacity);
+        raw.metadata_cache.ttl = parse_number("METADATA_CACHE_TTL")?.or(raw.metadata_cache.ttl);
+        raw.metadata_cache.path = var("METADATA_CACHE_PATH").or(raw.metadata_cache.path);
+        raw.audio_cache.dir = var("AUDIO_CACHE_DIR").or(raw.audio_cache.dir);
+        raw.audio_cache.max_size =
+            parse_number("AUDIO_CACHE_MAX_SIZE")?.or(raw.audio_cache.max_size);
+        raw.audio_cache.prefetch =
+            parse_number("AUDIO_CACHE_PREFETCH")?.or(raw.audio_cache.prefetch);
+        raw.attachment.max_size = parse_number("ATTACHMENT_MAX_SIZE")?.or(raw.attachment.max_size);
+        raw.library.dir = var("LIBRARY_DIR").or(raw.library.dir);
+        raw.library.scan_interval =
+            parse_number("LIBRARY_SCAN_INTERVAL")?.or(raw.library.scan_interval);
+
+        Self::validate(raw)
+    }
+
+    fn validate(raw: RawConfig) -> Result<Self, ConfigError> {
+        let token = raw
+            .token
+            .filter(|token| !token.trim().is_empty())
+            .ok_or(ConfigError::Missing("token (DISCORD_TOKEN)"))?;
+
+        let prefix = raw.prefix.unwrap_or_else(|| String::from(DEFAULT_PREFIX));
+        if prefix.is_empty() || prefix.chars().any(char::is_whitespace) {
+            return Err(ConfigError::Invalid {
+                key: "prefix (PREFIX)",
+                reason: String::from("must not be empty nor contain spaces"),
+            });
+        }
+
+        let log_level = match raw.log_level {
+            Some(level) => level.parse().map_err(|_| ConfigError::Invalid {
+                key: "log_level (LOG_LEVEL)",
+                reason: format!("expected trace, debug, info, warn or error, got {level}"),
+            })?,
+            None => tracing::Level::INFO,
+        };
+
+        let skip_limit = raw.skip_limit.unwrap_or(DEFAULT_SKIP_LIMIT);
+        if skip_limit == 0 {
+            return Err(ConfigError::Invalid {
+                key: "skip_limit (SKIP_LIMIT)",
+                reason: String::from("must be at least 1"),
+            });
+        }
+
+        let queue_display_limit = raw
+            .queue_display_limit
+            .unwrap_or(DEFAULT_QUEUE_DISPLAY_LIMIT);
+        if !(1..=MAX_QUEUE_DISPLAY_LIMIT).contains(&queue_display_limit) {
+            return Err(ConfigError::Invalid {
+                key: "queue_display_limit (QUEUE_DISPLAY_LIMIT)",
+                reason: format!("must be between 1 and {MAX_QUEUE_DISPLAY_LIMIT}"),
+            });
+        }
+
+        let auto_leave_delay = raw
+            .auto_leave_delay
+            .map(Duration::from_secs)
+            .unwrap_or(DEFAULT_AUTO_LEAVE_DELAY);
+        let grace_period = raw
+            .grace_period
+            .map(Duration::from_secs)
+            .unwrap_or(DEFAULT_GRACE_PERIOD);
+
+        let default_style = EmbedStyle::default();
+        let avatar_url = raw.embed.avatar_url.unwrap_or(default_style.avatar_url);
+        if !avatar_url.starts_with("http://") && !avatar_url.starts_with("https://") {
+            return Err(ConfigError::Invalid {
+                key: "embed.avatar_url (EMBED_AVATAR_URL)",
+                reason: format!("expected an http URL, got {avatar_url}"),
+            });
+        }
+
+        let embed = EmbedStyle {
+            author_name: raw.embed.author_name.unwrap_or(default_style.author_name),
+            avatar_url,
+            color: parse_color("embed.color (EMBED_COLOR)", raw.embed.color)?
+                .unwrap_or(default_style.color),
+            error_color: parse_color(
+                "embed.error_color (EMBED_ERROR_COLOR)",
+                raw.embed.error_color,
+            )?
+            .unwrap_or(default_style.error_color),
+        };
+
+        let mut cooldowns = DEFAULT_COOLDOWNS
+            .into_iter()
+            .map(|(command, cooldown)| (command.to_string(), cooldown))
+            .collect::<HashMap<_, _>>();
+
+        for (command, raw) in raw.cooldowns {
+            let command = command.to_lowercase();
+            let base = cooldowns
+                .get(&command)
+                .or_else(|| cooldowns.get(DEFAULT_COOLDOWN))
+                .copied()
+                .expect("Default cooldown guaranteed to exist");
+
+            let cooldown = parse_cooldown(&command, base, raw)?;
+            cooldowns.insert(command, cooldown);
+        }
+
+        let data_dir = PathBuf::from(
+            raw.data_dir
+                .unwrap_or_else(|| String::from(DEFAULT_DATA_DIR)),
+        );
+
+        Ok(Self {
+            token,
+            prefix,
+            log_level,
+            skip_limit,
+            queue_display_limit,
+            auto_leave_delay,
+            grace_period,
+            allow_private_hosts: raw.allow_private_hosts.unwrap_or(false),
+            embed,
+            cooldowns,
+            data_dir,
+            ytdlp: validate_ytdlp(raw.ytdlp)?,
+            metadata_cache: validate_metadata_cache(raw.metadata_cache)?,
+            audio_cache: validate_audio_cache(raw.audio_cache)?,
+            attachment: validate_attachment(raw.attachment)?,
+            library: validate_library(raw.library)?,
+        })
+    }
+
+    /// Cooldown of `command`, or the default one if it has none of its own.
+    pub fn cooldown(&self, command: &str) -> Cooldown {
+        self.cooldowns
+            .get(command)
+            .or_else(|| self.cooldowns.get(DEFAULT_COOLDOWN))
+            .copied()
+            .expect("Default cooldown guaranteed to exist")
+    }
+
+    /// Ensures every configured cooldown belongs to one of `commands`.
+    pub fn check_cooldowns(&self, commands: &[&str]) -> Result<(), ConfigError> {
+        let unknown = self
+            .cooldowns
+            .keys()
+            .find(|name| *name != DEFAULT_COOLDOWN && !commands.contains(&name.as_str()));
+
+        match unknown {
+            Some(name) => Err(ConfigError::Invalid {
+                key: "cooldowns",
+                reason: format!("unknown command {name}"),
+            }),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Value of environment variable `key`, unless empty.
+fn var(key: &str) -> Option<String> {
+    env::var(key).ok().filter(|value| !value.trim().is_empty())
+}
+
+/// Parses environment variable `key` as a positive number, if defined.
+fn parse_number<T: FromStr>(key: &'static str) -> Result<Option<T>, ConfigError> {
+    var(key)
+        .map(|value| {
+            value.trim().parse::<T>().map_err(|_| ConfigError::Invalid {
+                key,
+                reason: format!("expected a positive number, got {value}"),
+            })
+        })
+        .transpose()
+}
+
+fn parse_bool(key: &'static str) -> Result<Option<bool>, ConfigError> {
+    var(key)
+        .map(|value| match value.trim().to_lowercase().as_str() {
+            "true" | "1" => Ok(true),
+            "false" | "0" => Ok(false),
+            _ => Err(ConfigError::Invalid {
+                key,
+                reason: format!("expected true or false, got {value}"),
+            }),
+        })
+        .transpose()
+}
+
+/// Splits environment variable `key` into arguments as a shell would, honoring quotes.
+fn parse_args(key: &'static str) -> Result<Option<Vec<String>>, ConfigError> {
+    var(key)
+        .map(|value| {
+            shell_words::split(&value).map_err(|err| ConfigError::Invalid {
+                key,
+                reason: format!("{err} in {value}"),
+            })
+        })
+        .transpose()
+}
+
+/// Rejects number settings set to 0.
+fn at_least_one<T: Default + PartialEq>(key: &'static str, value: T) -> Result<T, ConfigError> {
+    if value == T::default() {
+        return Err(ConfigError::Invalid {
+            key,
+            reason: String::from("must be at least 1"),
+        });
+    }
+
+    Ok(value)
+}
+
+fn validate_ytdlp(raw: RawYtDlpConfig) -> Result<ytdlp::Config, ConfigError> {
+    let default = ytdlp::Config::default();
+
+    let format = raw.format.unwrap_or(default.format);
+    if format.trim().is_empty() {
+        return Err(ConfigError::Invalid {
+            key: "ytdlp.format (YTDLP_FORMAT)",
+            reason: String::from("must not be empty"),
+        });
+    }
+
+    if let Some(proxy) = &raw.proxy {
+        if reqwest::Proxy::all(proxy).is_err() {
+            return Err(ConfigError::Invalid {
+                key: "ytdlp.proxy (YTDLP_PROXY)",
+                reason: format!("expected a proxy URL, got {proxy}"),
+            });
+        }
+    }
+
+    Ok(ytdlp::Config {
+        program: raw.path.unwrap_or(default.program),
+        format,
+        cookies: raw.cookies.map(PathBuf::from),
+        proxy: raw.proxy,
+        extra_args: raw.args.unwrap_or(default.extra_args),
+    })
+}
+
+fn validate_metadata_cache(
+    raw: RawMetadataCacheConfig,
+) -> Result<metadata_cache::Config, ConfigError> {
+    let default = metadata_cache::Config::default();
+
+    Ok(metadata_cache::Config {
+        capacity: at_least_one(
+            "metadata_cache.capacity (METADATA_CACHE_CAPACITY)",
+            raw.capacity.unwrap_or(default.capacity),
+        )?,
+        ttl: match raw.ttl {
+            Some(secs) => Duration::from_secs(at_least_one(
+                "metadata_cache.ttl (METADATA_CACHE_TTL)",
+                secs,
+            )?),
+            None => default.ttl,
+        },
+        path: raw.path.map(PathBuf::from),
+    })
+}
+
+fn validate_audio_cache(
+    raw: RawAudioCacheConfig,
+) -> Result<Option<audio_cache::Config>, ConfigError> {
+    let Some(dir) = raw.dir else {
+        return Ok(None);
+    };
+
+    let max_size = at_least_one(
+        "audio_cache.max_size (AUDIO_CACHE_MAX_SIZE)",
+        raw.max_size.unwrap_or(audio_cache::DEFAULT_MAX_SIZE_MB),
+    )?;
+
+    Ok(Some(audio_cache::Config {
+        dir: PathBuf::from(dir),
+        max_size: max_size.saturating_mul(1024 * 1024),
+        prefetch: raw.prefetch.unwrap_or(audio_cache::DEFAULT_PREFETCH),
+    }))
+}
+
+fn validate_attachment(raw: RawAttachmentConfig) -> Result<attachment::Config, ConfigError> {
+    const KEY: &str = "attachment.max_size (ATTACHMENT_MAX_SIZE)";
+
+    let max_size = at_least_one(KEY, raw.max_size.unwrap_or(attachment::DEFAULT_MAX_SIZE_MB))?;
+
+    // sizes are compared against Discord attachment sizes, which fit in an u32
+    let max_size = max_size
+        .checked_mul(1024 * 1024)
+        .ok_or_else(|| ConfigError::Invalid {
+            key: KEY,
+            reason: format!("must be at most {}", u32::MAX / (1024 * 1024)),
+        })?;
+
+    Ok(attachment::Config { max_size })
+}
+
+fn validate_library(raw: RawLibraryConfig) -> Result<Option<library::Config>, ConfigError> {
+    let Some(dir) = raw.dir else {
+        return Ok(None);
+    };
+
+    let scan_interval = match raw.scan_interval {
+        Some(secs) => Duration::from_secs(at_least_one(
+            "library.scan_interval (LIBRARY_SCAN_INTERVAL)",
+            secs,
+        )?),
+        None => library::DEFAULT_SCAN_INTERVAL,
+    };
+
+    Ok(Some(library::Config {
+        dir: PathBuf::from(dir),
+        scan_interval,
+    }))
+}
+
+/// Applies the settings of a `[cooldowns.<command>]` table over `base`.
+fn parse_cooldown(
+    command: &str,
+    base: Cooldown,
+    raw: RawCooldown,
+) -> Result<Cooldown, ConfigError> {
+    let invalid = |reason: String| ConfigError::Invalid {
+        key: "cooldowns",
+        reason: format!("{command}: {reason}"),
+    };
+
+    let scope = match raw.per {
+        Some(per) => CooldownScope::parse(&per).ok_or_else(|| {
+            invalid(format!(
+                "expected per to be user, guild, channel or global, got {per}"
+            ))
+        })?,
+        None => base.scope,
+    };
+
+    let limit = match (raw.limit, raw.time_span) {
+        (Some(0), _) | (_, Some(0)) => {
+            return Err(invalid(String::from(
+                "limit and time_span must be at least 1",
+            )))
+        }
+        (Some(uses), Some(secs)) => Some((uses, Duration::from_secs(secs))),
+        (Some(uses), None) => match base.limit {
+            Some((_, time_span)) => Some((uses, time_span)),
+            None => return Err(invalid(String::from("limit requires a time_span"))),
+        },
+        (None, Some(secs)) => match base.limit {
+            Some((uses, _)) => Some((uses, Duration::from_secs(secs))),
+            None => return Err(invalid(String::from("time_span requires a limit"))),
+        },
+        (None, None) => base.limit,
+    };
+
+    Ok(Cooldown {
+        scope,
+        delay: raw.delay.map(Duration::from_secs).unwrap_or(base.delay),
+        limit,
+    })
+}
+
+/// Parses colors written as `#RRGGBB`.
+fn parse_color(key: &'static str, value: Option<String>) -> Result<Option<u32>, ConfigError> {
+    let Some(value) = value else {
+        return Ok(None);
+    };
+
+    let hex = value.trim().trim_start_matches('#');
+    match u32::from_str_radix(hex, 16) {
+        Ok(color) if hex.len() == 6 => Ok(Some(color)),
+        _ => Err(ConfigError::Invalid {
+            key,
+            reason: format!("expected a color as #RRGGBB, got {value}"),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn raw_config(toml: &str) -> RawConfig {
+        let mut raw = toml::from_str::<RawConfig>(toml).unwrap();
+        raw.token = Some(String::from("token"));
+        raw
+    }
+
+    #[test]
+    fn accepts_example_config() {
+        let config = Config::validate(raw_config(include_str!("../config.example.toml"))).unwrap();
+
+        assert_eq!(config.ytdlp, ytdlp::Config::default());
+        assert_eq!(config.metadata_cache, metadata_cache::Config::default());
+        assert_eq!(config.attachment, attachment::Config::default());
+        assert_eq!(config.audio_cache, None);
+        assert_eq!(config.library, None);
+    }
+
+    #[test]
+    fn reads_module_settings() {
+        let config = Config::validate(raw_config(
+            r#"
+            data_dir = "/var/lib/rina"
+
+            [ytdlp]
+            args = ["--no-playlist"]
+
+            [audio_cache]
+            dir = "cache"
+            max_size = 2
+
+            [library]
+            dir = "music"
+            scan_interval = 60
+            "#,
+        ))
+        .unwrap();
+
+        assert_eq!(config.data_dir, PathBuf::from("/var/lib/rina"));
+        assert_eq!(config.ytdlp.extra_args, vec![String::from("--no-playlist")]);
+
+        let audio_cache = config.audio_cache.unwrap();
+        assert_eq!(audio_cache.max_size, 2 * 1024 * 1024);
+        assert_eq!(audio_cache.prefetch, audio_cache::DEFAULT_PREFETCH);
+
+        let library = config.library.unwrap();
+        assert_eq!(library.scan_interval, Duration::from_secs(60));
+    }
+
+    #[test]
+    fn rejects_invalid_module_settings() {
+        for toml in [
+            "[metadata_cache]\ncapacity = 0",
+            "[metadata_cache]\nttl = 0",
+            "[audio_cache]\ndir = \"cache\"\nmax_size = 0",
+            "[attachment]\nmax_size = 0",
+            "[attachment]\nmax_size = 5000",
+            "[library]\ndir = \"music\"\nscan_interval = 0",
+            "[ytdlp]\nformat = \"\"",
+            "[ytdlp]\nproxy = \"not a proxy\"",
+        ] {
+            assert!(Config::validate(raw_config(toml)).is_err(), "{toml}");
+        }
+    }
+
+    #[test]
+    fn splits_quoted_args() {
+        std::env::set_var("RINA_TEST_ARGS", r#"--user-agent "Mozilla/5.0 (X11)" -N 4"#);
+        assert_eq!(
+            parse_args("RINA_TEST_ARGS").unwrap(),
+            Some(vec![
+                String::from("--user-agent"),
+                String::from("Mozilla/5.0 (X11)"),
+                String::from("-N"),
+                String::from("4"),
+            ])
+        );
+
+        std::env::set_var("RINA_TEST_UNCLOSED_ARGS", r#"--user-agent "Mozilla"#);
+        assert!(parse_args("RINA_TEST_UNCLOSED_ARGS").is_err());
+    }
+}
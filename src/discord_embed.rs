@@ -0,0 +1,42 @@
+use serenity::all::{Color, CreateEmbed, CreateEmbedAuthor, Timestamp};
+
+use rina::embed::{EmbedBuilder, EmbedStyle};
+
+/// Turns replies of the core into serenity embeds.
+pub trait BuildEmbed {
+    /// Finishes the embed with the author and colors of `style`.
+    fn build(self, style: &EmbedStyle) -> CreateEmbed;
+}
+
+impl BuildEmbed for EmbedBuilder {
+    fn build(self, style: &EmbedStyle) -> CreateEmbed {
+        let embed = self.finish();
+        let author = CreateEmbedAuthor::new(&style.author_name).icon_url(&style.avatar_url);
+        let color = if embed.error {
+            style.error_color
+        } else {
+            style.color
+        };
+
+        let mut built = CreateEmbed::new()
+            .author(author)
+            .color(Color::new(color))
+            .timestamp(Timestamp::now())
+            .fields(
+                embed
+                    .fields
+                    .into_iter()
+                    .map(|field| (field.name, field.value, field.inline)),
+            );
+
+        if let Some(title) = embed.title {
+            built = built.title(title);
+        }
+
+        if let Some(description) = embed.description {
+            built = built.description(description);
+        }
+
+        built
+    }
+}
@@ -1,11 +1,46 @@
-use serenity::all::{Color, CreateEmbed, CreateEmbedAuthor, Timestamp};
-
 const AUTHOR_NAME: &str = "Nina";
 const AVATAR_IMG_URL: &str =
     "https://raw.githubusercontent.com/Hironha/rina/main/static/images/nina.jpg";
 
-#[derive(Clone, Debug)]
-pub struct EmbedBuilder(CreateEmbed);
+/// Orange of the Discord palette.
+const COLOR: u32 = 0xE67E22;
+/// Red of the Discord palette.
+const ERROR_COLOR: u32 = 0xE74C3C;
+
+/// Author and colors shared by every embed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EmbedStyle {
+    pub author_name: String,
+    pub avatar_url: String,
+    /// Color as `0xRRGGBB`.
+    pub color: u32,
+    /// Color of error replies, as `0xRRGGBB`.
+    pub error_color: u32,
+}
+
+impl Default for EmbedStyle {
+    fn default() -> Self {
+        Self {
+            author_name: String::from(AUTHOR_NAME),
+            avatar_url: String::from(AVATAR_IMG_URL),
+            color: COLOR,
+            error_color: ERROR_COLOR,
+        }
+    }
+}
+
+/// Text of a reply, turned into a message of the chat platform along with an [`EmbedStyle`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Embed {
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub fields: Vec<EmbedField>,
+    /// Whether the reply reports an error, shown with the error color.
+    pub error: bool,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct EmbedBuilder(Embed);
 
 impl EmbedBuilder {
     pub fn new() -> Self {
@@ -13,46 +48,37 @@ impl EmbedBuilder {
     }
 
     pub fn error() -> Self {
-        let embed = Self::default().0.color(Color::RED);
-        Self(embed)
+        Self(Embed {
+            error: true,
+            ..Embed::default()
+        })
     }
 
-    pub fn title(self, title: impl Into<String>) -> Self {
-        let embed = self.0;
-        Self(embed.title(title))
+    pub fn title(mut self, title: impl Into<String>) -> Self {
+        self.0.title = Some(title.into());
+        self
     }
 
-    pub fn description(self, description: impl Into<String>) -> Self {
-        let embed = self.0;
-        Self(embed.description(description))
+    pub fn description(mut self, description: impl Into<String>) -> Self {
+        self.0.description = Some(description.into());
+        self
     }
 
-    pub fn fields(self, fields: impl IntoIterator<Item = EmbedField>) -> Self {
-        let fields = fields.into_iter().map(|f| (f.name, f.value, f.inline));
-        Self(self.0.fields(fields))
+    pub fn fields(mut self, fields: impl IntoIterator<Item = EmbedField>) -> Self {
+        self.0.fields.extend(fields);
+        self
     }
 
-    pub fn build(self) -> CreateEmbed {
+    pub fn finish(self) -> Embed {
         self.0
     }
 }
 
-impl Default for EmbedBuilder {
-    fn default() -> Self {
-        let embed = CreateEmbed::new()
-            .color(Color::ORANGE)
-            .author(CreateEmbedAuthor::new(AUTHOR_NAME).icon_url(AVATAR_IMG_URL))
-            .timestamp(Timestamp::now());
-
-        Self(embed)
-    }
-}
-
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct EmbedField {
-    name: String,
-    value: String,
-    inline: bool,
+    pub name: String,
+    pub value: String,
+    pub inline: bool,
 }
 
 impl EmbedField {
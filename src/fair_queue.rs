@@ -0,0 +1,50 @@
+use std::collections::{HashMap, VecDeque};
+use std::hash::Hash;
+
+/// Reorders `items` taking one item of each key in turn, starting from the key that appears
+/// first, while preserving the order of items sharing the same key.
+pub fn round_robin<T, K: Eq + Hash>(items: Vec<T>, key: impl Fn(&T) -> K) -> Vec<T> {
+    let len = items.len();
+    let mut indexes = HashMap::<K, usize>::new();
+    let mut groups = Vec::<VecDeque<T>>::new();
+
+    for item in items {
+        let idx = *indexes.entry(key(&item)).or_insert_with(|| {
+            groups.push(VecDeque::new());
+            groups.len() - 1
+        });
+
+        groups[idx].push_back(item);
+    }
+
+    let mut ordered = Vec::with_capacity(len);
+    while ordered.len() < len {
+        for group in &mut groups {
+            ordered.extend(group.pop_front());
+        }
+    }
+
+    ordered
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn interleaves_requesters() {
+        let items = vec![("a", 1), ("a", 2), ("a", 3), ("b", 1), ("c", 1), ("b", 2)];
+        let ordered = round_robin(items, |(requester, _)| *requester);
+
+        assert_eq!(
+            ordered,
+            [("a", 1), ("b", 1), ("c", 1), ("a", 2), ("b", 2), ("a", 3)]
+        );
+    }
+
+    #[test]
+    fn keeps_single_requester_order() {
+        assert_eq!(round_robin(vec![3, 1, 2], |_| ()), [3, 1, 2]);
+        assert!(round_robin(Vec::<u8>::new(), |_| ()).is_empty());
+    }
+}
@@ -0,0 +1,452 @@
+use std::collections::HashMap;
+use std::error;
+use std::fmt;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::storage::{Collection, Storage};
+
+/// Maximum length of a guild command prefix.
+const MAX_PREFIX_LEN: usize = 5;
+
+/// Maximum default volume, in percent.
+const MAX_VOLUME: u16 = 200;
+
+/// Settings a guild can change with `!settings`. Unset values fall back to the bot defaults.
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct GuildSettings {
+    pub prefix: Option<String>,
+    /// Volume of enqueued tracks, in percent.
+    pub default_volume: Option<u16>,
+    pub max_queue_length: Option<usize>,
+    /// Maximum amount of tracks each member can have in the queue.
+    pub max_user_tracks: Option<usize>,
+    /// Maximum duration of enqueued tracks, in seconds.
+    pub max_track_duration: Option<u64>,
+    /// Whether direct streams, radios and live videos can be enqueued.
+    pub allow_streams: Option<bool>,
+    /// Channel where announcements are sent, instead of the channel the bot was called from.
+    pub announce_channel: Option<u64>,
+    /// Seconds to wait before leaving a voice channel where nothing plays.
+    pub auto_leave_delay: Option<u64>,
+    /// Seconds to wait, with playback paused, for listeners to come back to an empty
+    /// voice channel before leaving it.
+    pub grace_period: Option<u64>,
+    /// Whether the queue is kept for `!resume-session` when leaving an empty voice channel.
+    pub keep_queue: Option<bool>,
+    pub dj_role: Option<u64>,
+    /// Percentage of listeners that must vote with `!skip` to skip a track, for members
+    /// who are not DJs. Vote skipping is disabled while unset.
+    pub vote_skip: Option<u8>,
+    /// Whether enqueued tracks are interleaved by requester, instead of played in order.
+    pub fair_queue: Option<bool>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Setting {
+    Prefix,
+    DefaultVolume,
+    MaxQueueLength,
+    MaxUserTracks,
+    MaxTrackDuration,
+    AllowStreams,
+    AnnounceChannel,
+    AutoLeaveDelay,
+    GracePeriod,
+    KeepQueue,
+    DjRole,
+    VoteSkip,
+    FairQueue,
+}
+
+impl Setting {
+    pub const ALL: [Self; 13] = [
+        Self::Prefix,
+        Self::DefaultVolume,
+        Self::MaxQueueLength,
+        Self::MaxUserTracks,
+        Self::MaxTrackDuration,
+        Self::AllowStreams,
+        Self::AnnounceChannel,
+        Self::AutoLeaveDelay,
+        Self::GracePeriod,
+        Self::KeepQueue,
+        Self::DjRole,
+        Self::VoteSkip,
+        Self::FairQueue,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Prefix => "prefix",
+            Self::DefaultVolume => "default_volume",
+            Self::MaxQueueLength => "max_queue_length",
+            Self::MaxUserTracks => "max_user_tracks",
+            Self::MaxTrackDuration => "max_track_duration",
+            Self::AllowStreams => "allow_streams",
+            Self::AnnounceChannel => "announce_channel",
+            Self::AutoLeaveDelay => "auto_leave_delay",
+            Self::GracePeriod => "grace_period",
+            Self::KeepQueue => "keep_queue",
+            Self::DjRole => "dj_role",
+            Self::VoteSkip => "vote_skip",
+            Self::FairQueue => "fair_queue",
+        }
+    }
+
+    /// Finds a setting by name, accepting dashes in place of underscores.
+    pub fn from_name(name: &str) -> Option<Self> {
+        let name = name.to_lowercase().replace('-', "_");
+        Self::ALL.into_iter().find(|setting| setting.name() == name)
+    }
+
+    /// Example of accepted values, shown when a value cannot be parsed.
+    pub fn hint(self) -> &'static str {
+        match self {
+            Self::Prefix => "up to 5 characters without spaces, as in `?`",
+            Self::DefaultVolume => "a percentage from 0 to 200, as in `80`",
+            Self::MaxQueueLength => "a positive number of tracks, as in `100`",
+            Self::MaxUserTracks => "a positive number of tracks, as in `10`",
+            Self::MaxTrackDuration | Self::AutoLeaveDelay | Self::GracePeriod => {
+                "a duration, as in `90`, `5m`, `1h30m` or `1:30:00`"
+            }
+            Self::AnnounceChannel => "a text channel mention, as in `#music`",
+            Self::DjRole => "a role mention, as in `@DJ`",
+            Self::VoteSkip => "a percentage of listeners from 1 to 100, as in `50`",
+            Self::AllowStreams | Self::KeepQueue | Self::FairQueue => "`on` or `off`",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InvalidValue {
+    pub setting: Setting,
+    pub value: String,
+}
+
+impl fmt::Display for InvalidValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Invalid value {} for {}, expected {}",
+            self.value,
+            self.setting.name(),
+            self.setting.hint()
+        )
+    }
+}
+
+impl error::Error for InvalidValue {}
+
+impl GuildSettings {
+    /// Human readable value of `setting`, or `None` if unset.
+    pub fn get(&self, setting: Setting) -> Option<String> {
+        match setting {
+            Setting::Prefix => self.prefix.clone(),
+            Setting::DefaultVolume => self.default_volume.map(|volume| format!("{volume}%")),
+            Setting::MaxQueueLength => self.max_queue_length.map(|len| format!("{len} tracks")),
+            Setting::MaxUserTracks => self.max_user_tracks.map(|len| format!("{len} tracks")),
+            Setting::MaxTrackDuration => self.max_track_duration.map(format_duration),
+            Setting::AllowStreams => self.allow_streams.map(format_switch),
+            Setting::AnnounceChannel => self.announce_channel.map(|id| format!("<#{id}>")),
+            Setting::AutoLeaveDelay => self.auto_leave_delay.map(format_duration),
+            Setting::GracePeriod => self.grace_period.map(format_duration),
+            Setting::KeepQueue => self.keep_queue.map(format_switch),
+            Setting::DjRole => self.dj_role.map(|id| format!("<@&{id}>")),
+            Setting::VoteSkip => self
+                .vote_skip
+                .map(|percent| format!("{percent}% of listeners")),
+            Setting::FairQueue => self.fair_queue.map(format_switch),
+        }
+    }
+
+    pub fn set(&mut self, setting: Setting, value: &str) -> Result<(), InvalidValue> {
+        let value = value.trim();
+        let invalid = || InvalidValue {
+            setting,
+            value: value.to_string(),
+        };
+
+        match setting {
+            Setting::Prefix => {
+                let valid = !value.is_empty()
+                    && value.chars().count() <= MAX_PREFIX_LEN
+                    && !value.chars().any(char::is_whitespace);
+
+                if !valid {
+                    return Err(invalid());
+                }
+
+                self.prefix = Some(value.to_string());
+            }
+            Setting::DefaultVolume => {
+                let volume = value.trim_end_matches('%').parse::<u16>().ok();
+                self.default_volume =
+                    Some(volume.filter(|v| *v <= MAX_VOLUME).ok_or_else(invalid)?);
+            }
+            Setting::MaxQueueLength => {
+                let len = value.parse::<usize>().ok().filter(|len| *len > 0);
+                self.max_queue_length = Some(len.ok_or_else(invalid)?);
+            }
+            Setting::MaxUserTracks => {
+                let len = value.parse::<usize>().ok().filter(|len| *len > 0);
+                self.max_user_tracks = Some(len.ok_or_else(invalid)?);
+            }
+            Setting::MaxTrackDuration => {
+                let secs = parse_duration(value)
+                    .map(|d| d.as_secs())
+                    .filter(|s| *s > 0);
+                self.max_track_duration = Some(secs.ok_or_else(invalid)?);
+            }
+            Setting::AllowStreams => {
+                self.allow_streams = Some(parse_switch(value).ok_or_else(invalid)?);
+            }
+            Setting::AnnounceChannel => {
+                let id = parse_mention(value, "<#");
+                self.announce_channel = Some(id.ok_or_else(invalid)?);
+            }
+            Setting::AutoLeaveDelay => {
+                let secs = parse_duration(value).map(|d| d.as_secs());
+                self.auto_leave_delay = Some(secs.ok_or_else(invalid)?);
+            }
+            Setting::GracePeriod => {
+                let secs = parse_duration(value).map(|d| d.as_secs());
+                self.grace_period = Some(secs.ok_or_else(invalid)?);
+            }
+            Setting::KeepQueue => {
+                self.keep_queue = Some(parse_switch(value).ok_or_else(invalid)?);
+            }
+            Setting::DjRole => {
+                let id = parse_mention(value, "<@&");
+                self.dj_role = Some(id.ok_or_else(invalid)?);
+            }
+            Setting::VoteSkip => {
+                let percent = value.trim_end_matches('%').parse::<u8>().ok();
+                let percent = percent.filter(|p| (1..=100).contains(p));
+                self.vote_skip = Some(percent.ok_or_else(invalid)?);
+            }
+            Setting::FairQueue => {
+                self.fair_queue = Some(parse_switch(value).ok_or_else(invalid)?);
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn reset(&mut self, setting: Setting) {
+        match setting {
+            Setting::Prefix => self.prefix = None,
+            Setting::DefaultVolume => self.default_volume = None,
+            Setting::MaxQueueLength => self.max_queue_length = None,
+            Setting::MaxUserTracks => self.max_user_tracks = None,
+            Setting::MaxTrackDuration => self.max_track_duration = None,
+            Setting::AllowStreams => self.allow_streams = None,
+            Setting::AnnounceChannel => self.announce_channel = None,
+            Setting::AutoLeaveDelay => self.auto_leave_delay = None,
+            Setting::GracePeriod => self.grace_period = None,
+            Setting::KeepQueue => self.keep_queue = None,
+            Setting::DjRole => self.dj_role = None,
+            Setting::VoteSkip => self.vote_skip = None,
+            Setting::FairQueue => self.fair_queue = None,
+        }
+    }
+}
+
+/// Parses durations written as seconds (`90`), with units (`1h30m`, `5m`, `45s`) or
+/// as a clock (`1:30`, `1:30:00`).
+pub fn parse_duration(value: &str) -> Option<Duration> {
+    let value = value.trim().to_lowercase();
+    if value.is_empty() {
+        return None;
+    }
+
+    if let Ok(secs) = value.parse::<u64>() {
+        return Some(Duration::from_secs(secs));
+    }
+
+    if value.contains(':') {
+        let parts = value.split(':').collect::<Vec<_>>();
+        if parts.len() > 3 {
+            return None;
+        }
+
+        return parts
+            .iter()
+            .try_fold(0u64, |total, part| {
+                let part = part.parse::<u64>().ok()?;
+                Some(total * 60 + part)
+            })
+            .map(Duration::from_secs);
+    }
+
+    let mut total = 0u64;
+    let mut number = String::new();
+    for c in value.chars() {
+        if c.is_ascii_digit() {
+            number.push(c);
+            continue;
+        }
+
+        let multiplier = match c {
+            'h' => 3600,
+            'm' => 60,
+            's' => 1,
+            _ => return None,
+        };
+
+        total += number.parse::<u64>().ok()? * multiplier;
+        number.clear();
+    }
+
+    number.is_empty().then(|| Duration::from_secs(total))
+}
+
+/// Formats seconds as `1h 30m 5s`, omitting zero parts.
+pub fn format_duration(secs: u64) -> String {
+    let (hours, minutes, seconds) = (secs / 3600, secs % 3600 / 60, secs % 60);
+    let parts = [(hours, "h"), (minutes, "m"), (seconds, "s")]
+        .into_iter()
+        .filter(|(amount, _)| *amount > 0)
+        .map(|(amount, unit)| format!("{amount}{unit}"))
+        .collect::<Vec<_>>();
+
+    if parts.is_empty() {
+        String::from("0s")
+    } else {
+        parts.join(" ")
+    }
+}
+
+/// Parses switches written as `on`/`off`, `true`/`false` or `yes`/`no`.
+fn parse_switch(value: &str) -> Option<bool> {
+    match value.to_lowercase().as_str() {
+        "on" | "true" | "yes" => Some(true),
+        "off" | "false" | "no" => Some(false),
+        _ => None,
+    }
+}
+
+fn format_switch(enabled: bool) -> String {
+    String::from(if enabled { "on" } else { "off" })
+}
+
+/// Parses a Discord id, either plain or as a mention starting with `prefix`.
+fn parse_mention(value: &str, prefix: &str) -> Option<u64> {
+    let id = match value.strip_prefix(prefix) {
+        Some(mention) => mention.strip_suffix('>')?,
+        None => value,
+    };
+
+    id.parse::<u64>().ok().filter(|id| *id > 0)
+}
+
+/// Settings of every guild, keyed by guild id.
+#[derive(Debug)]
+pub struct SettingsStore(Collection<HashMap<u64, GuildSettings>>);
+
+impl SettingsStore {
+    pub async fn load(storage: Storage) -> Result<Self, Box<dyn error::Error + Send + Sync>> {
+        Collection::load(storage, "settings").await.map(Self)
+    }
+
+    pub async fn get(&self, guild_id: u64) -> GuildSettings {
+        self.0
+            .read(|settings| settings.get(&guild_id).cloned().unwrap_or_default())
+            .await
+    }
+
+    /// Updates the guild settings, removing them entirely once all are unset.
+    pub async fn update<R>(
+        &self,
+        guild_id: u64,
+        f: impl FnOnce(&mut GuildSettings) -> R,
+    ) -> Result<R, Box<dyn error::Error + Send + Sync>> {
+        self.0
+            .update(|settings| {
+                let guild = settings.entry(guild_id).or_default();
+                let result = f(guild);
+                if *guild == GuildSettings::default() {
+                    settings.remove(&guild_id);
+                }
+
+                result
+            })
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_durations() {
+        assert_eq!(parse_duration("90"), Some(Duration::from_secs(90)));
+        assert_eq!(parse_duration("1h30m"), Some(Duration::from_secs(5400)));
+        assert_eq!(parse_duration("5M"), Some(Duration::from_secs(300)));
+        assert_eq!(parse_duration("1:30"), Some(Duration::from_secs(90)));
+        assert_eq!(parse_duration("1:30:00"), Some(Duration::from_secs(5400)));
+        assert_eq!(parse_duration("1:2:3:4"), None);
+        assert_eq!(parse_duration("5"), Some(Duration::from_secs(5)));
+        assert_eq!(parse_duration("5d"), None);
+        assert_eq!(parse_duration("1h30"), None);
+        assert_eq!(parse_duration(""), None);
+    }
+
+    #[test]
+    fn formats_durations() {
+        assert_eq!(format_duration(0), "0s");
+        assert_eq!(format_duration(3605), "1h 5s");
+        assert_eq!(format_duration(90), "1m 30s");
+    }
+
+    #[test]
+    fn sets_and_resets_settings() {
+        let mut settings = GuildSettings::default();
+
+        settings.set(Setting::DjRole, "<@&123>").unwrap();
+        settings.set(Setting::VoteSkip, "50%").unwrap();
+        settings.set(Setting::FairQueue, "yes").unwrap();
+        settings.set(Setting::MaxTrackDuration, "10m").unwrap();
+        settings.set(Setting::GracePeriod, "2m").unwrap();
+        settings.set(Setting::KeepQueue, "off").unwrap();
+
+        assert_eq!(settings.dj_role, Some(123));
+        assert_eq!(settings.grace_period, Some(120));
+        assert_eq!(settings.get(Setting::KeepQueue).as_deref(), Some("off"));
+        assert_eq!(
+            settings.get(Setting::VoteSkip).as_deref(),
+            Some("50% of listeners")
+        );
+        assert_eq!(settings.get(Setting::FairQueue).as_deref(), Some("on"));
+        assert_eq!(
+            settings.get(Setting::MaxTrackDuration).as_deref(),
+            Some("10m")
+        );
+
+        settings.reset(Setting::DjRole);
+        assert_eq!(settings.get(Setting::DjRole), None);
+    }
+
+    #[test]
+    fn rejects_invalid_values() {
+        let mut settings = GuildSettings::default();
+
+        assert!(settings.set(Setting::Prefix, "two words").is_err());
+        assert!(settings.set(Setting::VoteSkip, "0").is_err());
+        assert!(settings.set(Setting::VoteSkip, "101%").is_err());
+        assert!(settings.set(Setting::MaxQueueLength, "0").is_err());
+        assert!(settings.set(Setting::AllowStreams, "maybe").is_err());
+        assert_eq!(settings, GuildSettings::default());
+    }
+
+    #[test]
+    fn ignores_removed_settings() {
+        let settings: GuildSettings =
+            serde_json::from_str(r#"{"prefix": "?", "language": "pt-BR"}"#).unwrap();
+
+        assert_eq!(settings.prefix.as_deref(), Some("?"));
+    }
+}
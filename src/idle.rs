@@ -0,0 +1,149 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use tokio::task::AbortHandle;
+
+/// Per guild timers running an action once the bot stays idle for a while.
+#[derive(Debug, Default)]
+pub struct IdleTimers(Mutex<Inner>);
+
+#[derive(Debug, Default)]
+struct Inner {
+    timers: HashMap<u64, (u64, AbortHandle)>,
+    next_id: u64,
+}
+
+impl IdleTimers {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Runs `on_idle` after `delay`, unless cancelled before. Restarts the guild timer
+    /// if one is already running.
+    pub fn start<F>(self: &Arc<Self>, guild_id: u64, delay: Duration, on_idle: F)
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        let timers = Arc::clone(self);
+        let mut inner = self.0.lock().expect("Idle timers lock poisoned");
+        let id = inner.next_id;
+        inner.next_id += 1;
+
+        // the task cannot finish before being registered, since it needs the lock held here
+        let task = tokio::spawn(async move {
+            tokio::time::sleep(delay).await;
+            if timers.finish(guild_id, id) {
+                on_idle.await;
+            }
+        });
+
+        if let Some((_, previous)) = inner.timers.insert(guild_id, (id, task.abort_handle())) {
+            previous.abort();
+        }
+    }
+
+    /// Cancels the guild timer, returning whether one was running.
+    pub fn cancel(&self, guild_id: u64) -> bool {
+        let mut inner = self.0.lock().expect("Idle timers lock poisoned");
+        match inner.timers.remove(&guild_id) {
+            Some((_, timer)) => {
+                timer.abort();
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn is_running(&self, guild_id: u64) -> bool {
+        let inner = self.0.lock().expect("Idle timers lock poisoned");
+        inner.timers.contains_key(&guild_id)
+    }
+
+    /// Unregisters timer `id` once elapsed, unless it was replaced in the meantime.
+    fn finish(&self, guild_id: u64, id: u64) -> bool {
+        let mut inner = self.0.lock().expect("Idle timers lock poisoned");
+        if inner.timers.get(&guild_id).map(|(current, _)| *current) != Some(id) {
+            return false;
+        }
+
+        inner.timers.remove(&guild_id);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+
+    const GUILD: u64 = 1;
+    const DELAY: Duration = Duration::from_secs(60);
+
+    fn counter() -> (Arc<AtomicUsize>, impl Fn() -> std::future::Ready<()>) {
+        let count = Arc::new(AtomicUsize::new(0));
+        let counted = Arc::clone(&count);
+        let action = move || {
+            counted.fetch_add(1, Ordering::SeqCst);
+            std::future::ready(())
+        };
+
+        (count, action)
+    }
+
+    async fn wait(duration: Duration) {
+        tokio::time::sleep(duration).await;
+        // lets elapsed timers run their action
+        tokio::task::yield_now().await;
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn runs_after_delay() {
+        let timers = Arc::new(IdleTimers::new());
+        let (count, action) = counter();
+
+        timers.start(GUILD, DELAY, async move { action().await });
+        assert!(timers.is_running(GUILD));
+
+        wait(DELAY / 2).await;
+        assert_eq!(count.load(Ordering::SeqCst), 0);
+
+        wait(DELAY).await;
+        assert_eq!(count.load(Ordering::SeqCst), 1);
+        assert!(!timers.is_running(GUILD));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn cancels_pending_timer() {
+        let timers = Arc::new(IdleTimers::new());
+        let (count, action) = counter();
+
+        timers.start(GUILD, DELAY, async move { action().await });
+        assert!(timers.cancel(GUILD));
+        assert!(!timers.cancel(GUILD));
+
+        wait(DELAY * 2).await;
+        assert_eq!(count.load(Ordering::SeqCst), 0);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn restarts_running_timer() {
+        let timers = Arc::new(IdleTimers::new());
+        let (count, action) = counter();
+        let action = Arc::new(action);
+
+        let first = Arc::clone(&action);
+        timers.start(GUILD, DELAY, async move { first().await });
+        wait(DELAY / 2).await;
+
+        let second = Arc::clone(&action);
+        timers.start(GUILD, DELAY, async move { second().await });
+        wait(DELAY * 3 / 4).await;
+        assert_eq!(count.load(Ordering::SeqCst), 0);
+
+        wait(DELAY / 2).await;
+        assert_eq!(count.load(Ordering::SeqCst), 1);
+    }
+}
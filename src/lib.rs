@@ -0,0 +1,43 @@
+//! Core of the **Rina** music bot: queue ordering and limits, command argument parsing,
+//! permission rules, reply rendering, track resolution and storage. It does not depend on
+//! serenity: guilds and users are plain ids and replies are [`embed::Embed`] text, turned
+//! into Discord messages by the command handlers and gateway events of the `rina` binary.
+//! None of it needs a Discord connection or a voice call to be tested.
+
+pub mod args;
+pub mod attachment;
+pub mod audio_cache;
+pub mod config;
+pub mod embed;
+pub mod fair_queue;
+pub mod guild_settings;
+pub mod idle;
+pub mod library;
+pub mod metadata_cache;
+pub mod permissions;
+pub mod playlist;
+pub mod playlist_file;
+pub mod quota;
+pub mod radio;
+pub mod render;
+pub mod resolve;
+pub mod saved_playlist;
+pub mod session;
+pub mod storage;
+pub mod stream;
+pub mod vote_skip;
+pub mod ytdlp;
+
+/// Where a track audio comes from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TrackSource {
+    /// Page resolved through `yt-dlp`.
+    YtDlp,
+    /// File uploaded along with the command.
+    Attachment,
+    /// File from the local music library.
+    Local,
+    /// Direct audio stream, such as an internet radio. Has no duration.
+    Stream,
+}
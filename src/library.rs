@@ -0,0 +1,528 @@
+use std::collections::HashMap;
+use std::error;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, SystemTime};
+
+use notify::{EventKind, RecursiveMode, Watcher};
+use symphonia::core::formats::FormatOptions;
+use symphonia::core::io::MediaSourceStream;
+use symphonia::core::meta::{MetadataOptions, StandardTagKey, Tag, Value};
+use symphonia::core::probe::Hint;
+
+pub const DEFAULT_SCAN_INTERVAL: Duration = Duration::from_secs(5 * 60);
+/// Time waited after a change in the library directory before scanning it, so that a batch
+/// of copied files is indexed at once.
+const CHANGE_DELAY: Duration = Duration::from_secs(2);
+const AUDIO_EXTENSIONS: [&str; 8] = ["flac", "mp3", "ogg", "oga", "wav", "m4a", "aac", "mka"];
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Config {
+    pub dir: PathBuf,
+    /// Interval between full scans, catching changes the filesystem does not notify of,
+    /// such as those of network shares.
+    pub scan_interval: Duration,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct LocalTrack {
+    pub path: PathBuf,
+    pub title: String,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    pub track_number: Option<u32>,
+    pub duration: Option<Duration>,
+    modified: SystemTime,
+}
+
+impl LocalTrack {
+    /// Title prefixed by the artist name, when known.
+    pub fn display_title(&self) -> String {
+        match &self.artist {
+            Some(artist) => format!("{artist} - {}", self.title),
+            None => self.title.clone(),
+        }
+    }
+
+    fn searchable_text(&self) -> String {
+        let fields = [
+            Some(self.title.as_str()),
+            self.artist.as_deref(),
+            self.album.as_deref(),
+            self.path.file_stem().and_then(|stem| stem.to_str()),
+        ];
+
+        fields
+            .into_iter()
+            .flatten()
+            .collect::<Vec<&str>>()
+            .join(" ")
+            .to_lowercase()
+    }
+}
+
+/// Index of audio files found in a local directory.
+#[derive(Debug)]
+pub struct Library {
+    config: Config,
+    tracks: RwLock<Arc<Vec<LocalTrack>>>,
+}
+
+impl Library {
+    pub fn new(config: Config) -> Self {
+        Self {
+            config,
+            tracks: RwLock::new(Arc::new(Vec::new())),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.snapshot().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.snapshot().is_empty()
+    }
+
+    /// Scans library directory, only reading tags from new or modified files.
+    pub async fn scan(&self) -> Result<(), Box<dyn error::Error + Send + Sync>> {
+        let dir = self.config.dir.clone();
+        let previous = self.snapshot();
+
+        let tracks = tokio::task::spawn_blocking(move || {
+            let known = previous
+                .iter()
+                .map(|track| (track.path.as_path(), track))
+                .collect::<HashMap<&Path, &LocalTrack>>();
+
+            let mut files = Vec::new();
+            collect_audio_files(&dir, &mut files)?;
+
+            let tracks = files
+                .into_iter()
+                .filter_map(|(path, modified)| match known.get(path.as_path()) {
+                    Some(track) if track.modified == modified => Some((*track).clone()),
+                    _ => read_track(path, modified),
+                })
+                .collect::<Vec<LocalTrack>>();
+
+            Ok::<_, std::io::Error>(tracks)
+        })
+        .await??;
+
+        *self.tracks.write().expect("Library lock poisoned") = Arc::new(tracks);
+        Ok(())
+    }
+
+    /// Keeps the index in sync with the directory, scanning it again whenever the filesystem
+    /// notifies of a change and every `scan_interval` otherwise.
+    pub fn watch(self: Arc<Self>) {
+        let (changes_tx, mut changes) = tokio::sync::mpsc::unbounded_channel();
+        let watcher =
+            notify::recommended_watcher(move |event: notify::Result<notify::Event>| match event {
+                Ok(event) if !matches!(event.kind, EventKind::Access(_)) => {
+                    let _ = changes_tx.send(());
+                }
+                Ok(_) => {}
+                Err(err) => tracing::warn!("Failed watching local library: {err}"),
+            })
+            .and_then(|mut watcher| {
+                watcher.watch(&self.config.dir, RecursiveMode::Recursive)?;
+                Ok(watcher)
+            });
+
+        let watcher = match watcher {
+            Ok(watcher) => Some(watcher),
+            Err(err) => {
+                tracing::warn!("Failed watching local library, relying on periodic scans: {err}");
+                None
+            }
+        };
+
+        tokio::spawn(async move {
+            // changes stop being notified once the watcher is dropped
+            let _watcher = watcher;
+            let mut interval = tokio::time::interval(self.config.scan_interval);
+            loop {
+                tokio::select! {
+                    _ = interval.tick() => {}
+                    Some(()) = changes.recv() => {
+                        tokio::time::sleep(CHANGE_DELAY).await;
+                        while changes.try_recv().is_ok() {}
+                        interval.reset();
+                    }
+                }
+
+                match self.scan().await {
+                    Ok(_) => tracing::info!("Local library scanned, {} tracks found", self.len()),
+                    Err(err) => tracing::error!("Failed scanning local library: {err}"),
+                }
+            }
+        });
+    }
+
+    /// Finds tracks matching all words of `query` in their title, artist, album or file name,
+    /// with title matches ranked first.
+    pub fn search(&self, query: &str) -> Vec<LocalTrack> {
+        let query = query.to_lowercase();
+        let words = query.split_whitespace().collect::<Vec<&str>>();
+        if words.is_empty() {
+            return Vec::new();
+        }
+
+        let mut matches = self
+            .snapshot()
+            .iter()
+            .filter(|track| {
+                let text = track.searchable_text();
+                words.iter().all(|word| text.contains(word))
+            })
+            .cloned()
+            .collect::<Vec<LocalTrack>>();
+
+        matches.sort_by_key(|track| {
+            let title = track.title.to_lowercase();
+            (
+                title != query,
+                !title.contains(&query),
+                track.display_title(),
+            )
+        });
+
+        matches
+    }
+
+    /// Finds the album best matching `name`, returning its tracks in order.
+    pub fn album(&self, name: &str) -> Vec<LocalTrack> {
+        let name = name.trim().to_lowercase();
+        if name.is_empty() {
+            return Vec::new();
+        }
+
+        let tracks = self.snapshot();
+        let album_of = |track: &LocalTrack| track.album.as_deref().map(str::to_lowercase);
+
+        let exact = tracks
+            .iter()
+            .find_map(|track| album_of(track).filter(|a| *a == name));
+        let Some(album) = exact.or_else(|| {
+            tracks
+                .iter()
+                .find_map(|track| album_of(track).filter(|a| a.contains(&name)))
+        }) else {
+            return Vec::new();
+        };
+
+        let mut album_tracks = tracks
+            .iter()
+            .filter(|track| album_of(track).as_ref() == Some(&album))
+            .cloned()
+            .collect::<Vec<LocalTrack>>();
+
+        album_tracks.sort_by(|a, b| {
+            let key = |t: &LocalTrack| (t.track_number.unwrap_or(u32::MAX), t.title.clone());
+            key(a).cmp(&key(b))
+        });
+
+        album_tracks
+    }
+
+    fn snapshot(&self) -> Arc<Vec<LocalTrack>> {
+        self.tracks.read().expect("Library lock poisoned").clone()
+    }
+}
+
+/// Lists audio files inside `dir` and its subdirectories. Only failing to read `dir` itself
+/// is an error, while unreadable entries inside it are logged and skipped.
+fn collect_audio_files(dir: &Path, files: &mut Vec<(PathBuf, SystemTime)>) -> std::io::Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let (path, metadata) = match entry.and_then(|entry| Ok((entry.path(), entry.metadata()?))) {
+            Ok(entry) => entry,
+            Err(err) => {
+                tracing::warn!("Skipping unreadable entry of {}: {err}", dir.display());
+                continue;
+            }
+        };
+
+        if metadata.is_dir() {
+            if let Err(err) = collect_audio_files(&path, files) {
+                tracing::warn!("Skipping unreadable directory {}: {err}", path.display());
+            }
+            continue;
+        }
+
+        let extension = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(str::to_lowercase);
+
+        if extension.is_some_and(|ext| AUDIO_EXTENSIONS.contains(&ext.as_str())) {
+            let modified = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+            files.push((path, modified));
+        }
+    }
+
+    Ok(())
+}
+
+/// Reads tags and duration of an audio file, returning `None` if it cannot be probed.
+fn read_track(path: PathBuf, modified: SystemTime) -> Option<LocalTrack> {
+    let file = std::fs::File::open(&path).ok()?;
+    let stream = MediaSourceStream::new(Box::new(file), Default::default());
+
+    let mut hint = Hint::new();
+    if let Some(extension) = path.extension().and_then(|ext| ext.to_str()) {
+        hint.with_extension(extension);
+    }
+
+    let probed = symphonia::default::get_probe().format(
+        &hint,
+        stream,
+        &FormatOptions::default(),
+        &MetadataOptions::default(),
+    );
+
+    let mut probed = match probed {
+        Ok(probed) => probed,
+        Err(err) => {
+            tracing::warn!("Failed probing {}: {err}", path.display());
+            return None;
+        }
+    };
+
+    let mut tags = Vec::<Tag>::new();
+    if let Some(revision) = probed.metadata.get().as_ref().and_then(|m| m.current()) {
+        tags.extend(revision.tags().iter().cloned());
+    }
+    if let Some(revision) = probed.format.metadata().current() {
+        tags.extend(revision.tags().iter().cloned());
+    }
+
+    // RIFF INFO values keep their NUL terminator
+    let text = |value: &Value| {
+        let text = value.to_string();
+        text.trim_matches(|c: char| c.is_whitespace() || c == '\0')
+            .to_string()
+    };
+
+    let tag_value = |key: StandardTagKey| {
+        tags.iter()
+            .find(|tag| tag.std_key == Some(key))
+            .map(|tag| text(&tag.value))
+            .filter(|value| !value.is_empty())
+    };
+
+    let track_number = tags
+        .iter()
+        .find(|tag| tag.std_key == Some(StandardTagKey::TrackNumber))
+        .and_then(|tag| match &tag.value {
+            Value::UnsignedInt(number) => u32::try_from(*number).ok(),
+            // track numbers are commonly formatted as "3/12"
+            value => text(value).split('/').next()?.trim().parse().ok(),
+        });
+
+    let duration = probed.format.default_track().and_then(|track| {
+        let params = &track.codec_params;
+        match (params.n_frames, params.time_base, params.sample_rate) {
+            (Some(frames), Some(time_base), _) => {
+                let time = time_base.calc_time(frames);
+                Some(Duration::from_secs(time.seconds) + Duration::from_secs_f64(time.frac))
+            }
+            (Some(frames), None, Some(rate)) if rate > 0 => {
+                Some(Duration::from_secs_f64(frames as f64 / f64::from(rate)))
+            }
+            _ => None,
+        }
+    });
+
+    let file_title = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+
+    Some(LocalTrack {
+        title: tag_value(StandardTagKey::TrackTitle).unwrap_or(file_title),
+        artist: tag_value(StandardTagKey::Artist)
+            .or_else(|| tag_value(StandardTagKey::AlbumArtist)),
+        album: tag_value(StandardTagKey::Album),
+        track_number,
+        duration,
+        modified,
+        path,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: u32 = 8000;
+
+    /// Mono 16 bits WAV file of `secs` of silence, tagged through a RIFF INFO list.
+    fn wav(tags: &[(&[u8; 4], &str)], secs: u32) -> Vec<u8> {
+        let mut info = b"INFO".to_vec();
+        for (id, value) in tags {
+            let mut value = value.as_bytes().to_vec();
+            value.push(0);
+            info.extend_from_slice(*id);
+            info.extend_from_slice(&(value.len() as u32).to_le_bytes());
+            if value.len() % 2 == 1 {
+                value.push(0);
+            }
+            info.extend_from_slice(&value);
+        }
+
+        let mut fmt = Vec::new();
+        fmt.extend_from_slice(&1u16.to_le_bytes());
+        fmt.extend_from_slice(&1u16.to_le_bytes());
+        fmt.extend_from_slice(&SAMPLE_RATE.to_le_bytes());
+        fmt.extend_from_slice(&(SAMPLE_RATE * 2).to_le_bytes());
+        fmt.extend_from_slice(&2u16.to_le_bytes());
+        fmt.extend_from_slice(&16u16.to_le_bytes());
+
+        let data = vec![0; (SAMPLE_RATE * 2 * secs) as usize];
+
+        let mut chunks = b"WAVE".to_vec();
+        for (id, body) in [(b"fmt ", fmt), (b"LIST", info), (b"data", data)] {
+            chunks.extend_from_slice(id);
+            chunks.extend_from_slice(&(body.len() as u32).to_le_bytes());
+            chunks.extend_from_slice(&body);
+        }
+
+        let mut file = b"RIFF".to_vec();
+        file.extend_from_slice(&(chunks.len() as u32).to_le_bytes());
+        file.extend_from_slice(&chunks);
+        file
+    }
+
+    fn album_track(title: &str, number: &str) -> Vec<u8> {
+        wav(
+            &[
+                (b"INAM", title),
+                (b"IART", "The Band"),
+                (b"IPRD", "Night Drive"),
+                (b"IPRT", number),
+            ],
+            1,
+        )
+    }
+
+    /// Library of a temporary directory holding `files`, scanned once.
+    async fn library(files: &[(&str, Vec<u8>)]) -> (tempfile::TempDir, Library) {
+        let dir = tempfile::tempdir().unwrap();
+        for (name, content) in files {
+            let path = dir.path().join(name);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, content).unwrap();
+        }
+
+        let library = Library::new(Config {
+            dir: dir.path().to_path_buf(),
+            scan_interval: DEFAULT_SCAN_INTERVAL,
+        });
+        library.scan().await.unwrap();
+
+        (dir, library)
+    }
+
+    #[tokio::test]
+    async fn indexes_tagged_audio_files() {
+        let (_dir, library) = library(&[
+            ("night/02.wav", album_track("Neon Lights", "2/9")),
+            ("untagged.wav", wav(&[], 2)),
+            ("cover.jpg", vec![0; 16]),
+        ])
+        .await;
+
+        assert_eq!(library.len(), 2);
+
+        let [track] = &library.search("neon")[..] else {
+            panic!("Expected a single track to match");
+        };
+        assert_eq!(track.title, "Neon Lights");
+        assert_eq!(track.artist.as_deref(), Some("The Band"));
+        assert_eq!(track.album.as_deref(), Some("Night Drive"));
+        assert_eq!(track.track_number, Some(2));
+        assert_eq!(track.duration, Some(Duration::from_secs(1)));
+
+        let [untagged] = &library.search("untagged")[..] else {
+            panic!("Expected untagged file to match its name");
+        };
+        assert_eq!(untagged.title, "untagged");
+        assert_eq!(untagged.artist, None);
+        assert_eq!(untagged.duration, Some(Duration::from_secs(2)));
+    }
+
+    #[tokio::test]
+    async fn ranks_title_matches_first() {
+        let (_dir, library) = library(&[
+            ("a.wav", wav(&[(b"INAM", "Intro"), (b"IPRD", "Rain")], 1)),
+            ("b.wav", wav(&[(b"INAM", "Rain")], 1)),
+            ("c.wav", wav(&[(b"INAM", "Rain Again")], 1)),
+            ("d.wav", wav(&[(b"INAM", "Sunny")], 1)),
+        ])
+        .await;
+
+        let titles = library
+            .search("RAIN")
+            .into_iter()
+            .map(|track| track.title)
+            .collect::<Vec<_>>();
+        assert_eq!(titles, ["Rain", "Rain Again", "Intro"]);
+        assert!(library.search("  ").is_empty());
+    }
+
+    #[tokio::test]
+    async fn lists_album_tracks_in_order() {
+        let (_dir, library) = library(&[
+            ("3.wav", album_track("Third", "3")),
+            ("1.wav", album_track("First", "1/3")),
+            ("2.wav", album_track("Second", "2")),
+            ("other.wav", wav(&[(b"INAM", "Other"), (b"IPRD", "Day")], 1)),
+        ])
+        .await;
+
+        let titles = |name| {
+            library
+                .album(name)
+                .into_iter()
+                .map(|track| track.title)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(titles("night drive"), ["First", "Second", "Third"]);
+        assert_eq!(titles("drive"), ["First", "Second", "Third"]);
+        assert!(titles("morning").is_empty());
+    }
+
+    #[tokio::test]
+    async fn rescans_added_and_removed_files() {
+        let (dir, library) = library(&[("old.wav", wav(&[(b"INAM", "Old")], 1))]).await;
+
+        std::fs::remove_file(dir.path().join("old.wav")).unwrap();
+        std::fs::write(dir.path().join("new.wav"), wav(&[(b"INAM", "New")], 1)).unwrap();
+        library.scan().await.unwrap();
+
+        assert!(library.search("old").is_empty());
+        assert_eq!(library.search("new")[0].title, "New");
+    }
+
+    #[tokio::test]
+    async fn watches_directory_changes() {
+        let (dir, library) = library(&[]).await;
+        let library = Arc::new(library);
+        Arc::clone(&library).watch();
+
+        // lets the watcher start before the directory changes
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        std::fs::write(dir.path().join("added.wav"), wav(&[(b"INAM", "Added")], 1)).unwrap();
+
+        let added = async {
+            while library.is_empty() {
+                tokio::time::sleep(Duration::from_millis(100)).await;
+            }
+        };
+        tokio::time::timeout(CHANGE_DELAY + Duration::from_secs(5), added)
+            .await
+            .expect("Expected added file to be indexed");
+    }
+}
@@ -1,24 +1,66 @@
-mod embed;
-mod playlist;
+mod discord_embed;
+mod voice_context;
 
-use std::env;
-use std::sync::Arc;
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::{Arc, Weak};
+use std::time::Duration;
 
-use reqwest::Client as HttpClient;
-use serenity::all::{ChannelType, CreateMessage, VoiceState};
+use reqwest::{Client as HttpClient, Proxy};
+use serenity::all::{ChannelType, CreateAttachment, CreateMessage, EditVoiceState, VoiceState};
 use serenity::client::{Client, Context, EventHandler};
-use serenity::framework::standard::macros::{command, group};
-use serenity::framework::standard::{Args, CommandResult, Configuration};
+use serenity::framework::standard::macros::{check, command, group, hook};
+use serenity::framework::standard::{
+    Args, BucketBuilder, CommandOptions, CommandResult, Configuration, DispatchError, Reason,
+};
 use serenity::framework::StandardFramework;
+use serenity::http::Http;
 use serenity::model::application::Command;
 use serenity::model::channel::Message;
 use serenity::model::gateway::Ready;
-use serenity::prelude::{GatewayIntents, Mentionable, TypeMapKey};
-use songbird::input::{Input, YoutubeDl};
-use songbird::tracks::{Queued, Track, TrackHandle};
-use songbird::SerenityInit;
+use serenity::model::id::{ChannelId, GuildId, RoleId, UserId};
+use serenity::prelude::{GatewayIntents, Mentionable, Mutex, RwLock, TypeMapKey};
+use songbird::error::JoinError;
+use songbird::input::{File, HttpRequest, Input};
+use songbird::tracks::{LoopState, PlayMode, Queued, Track, TrackHandle, TrackQueue};
+use songbird::{Call, Event, EventContext, SerenityInit, Songbird, TrackEvent};
+use tokio::sync::MutexGuard;
+
+use discord_embed::BuildEmbed;
+use rina::attachment::Attachment;
+use rina::audio_cache::AudioCache;
+use rina::config::{Config, Cooldown, CooldownScope};
+use rina::embed::{EmbedBuilder, EmbedField, EmbedStyle};
+use rina::guild_settings::{format_duration, Setting, SettingsStore};
+use rina::idle::IdleTimers;
+use rina::library::Library;
+use rina::metadata_cache::MetadataCache;
+use rina::permissions::{Rules, Verdict};
+use rina::quota::{Limits, Quota};
+use rina::radio::{RadioStore, Station};
+use rina::resolve::{self, PlayContext, PlayQueue, ResolveError, Resolved};
+use rina::saved_playlist::PlaylistStore;
+use rina::session::{Session, SessionStore, SessionTrack};
+use rina::storage::Storage;
+use rina::stream::{self, DelayedRequest, TitleWatcher, TitleWatcherHandler};
+use rina::vote_skip::{self, SkipVotes};
+use rina::ytdlp::{self, YtDlp};
+use rina::{
+    args, attachment, fair_queue, playlist, playlist_file, render, saved_playlist, TrackSource,
+};
+use voice_context::{VoiceCommandContext, VoiceCommandError};
+
+struct ConfigKey;
+
+impl TypeMapKey for ConfigKey {
+    type Value = Arc<Config>;
+}
+
+struct EmbedStyleKey;
 
-use embed::{EmbedBuilder, EmbedField};
+impl TypeMapKey for EmbedStyleKey {
+    type Value = Arc<EmbedStyle>;
+}
 
 struct HttpKey;
 
@@ -26,543 +68,1364 @@ impl TypeMapKey for HttpKey {
     type Value = HttpClient;
 }
 
-struct TrackTitleKey;
+struct YtDlpKey;
 
-impl TypeMapKey for TrackTitleKey {
-    type Value = Arc<str>;
+impl TypeMapKey for YtDlpKey {
+    type Value = Arc<ytdlp::Config>;
 }
 
-struct Handler;
-
-#[serenity::async_trait]
-impl EventHandler for Handler {
-    async fn ready(&self, ctx: Context, ready: Ready) {
-        Command::set_global_commands(&ctx.http, Vec::new())
-            .await
-            .expect("Could not set global slash commands");
+struct MetadataCacheKey;
 
-        tracing::info!("{} is connected!", ready.user.name);
-    }
+impl TypeMapKey for MetadataCacheKey {
+    type Value = Arc<MetadataCache>;
+}
 
-    async fn voice_state_update(&self, ctx: Context, old: Option<VoiceState>, new: VoiceState) {
-        let channel_id = match (old.and_then(|state| state.channel_id), new.channel_id) {
-            // if old state has channel_id and new state doesn't, it means the user left voice channel
-            (Some(channel_id), None) => channel_id,
-            _ => return tracing::info!("Voice state updated, but not a leave event"),
-        };
+struct AttachmentKey;
 
-        let Some(guild_id) = new.guild_id else {
-            return tracing::error!("Unexpected guild_id not defined in new state");
-        };
+impl TypeMapKey for AttachmentKey {
+    type Value = attachment::Config;
+}
 
-        let channels = match guild_id.channels(&ctx.http).await {
-            Ok(channels) => channels,
-            Err(err) => return tracing::error!("Failed getting guild channels: {err:?}"),
-        };
+struct AudioCacheKey;
 
-        let voice_channel = match channels.get(&channel_id) {
-            Some(channel) if channel.kind == ChannelType::Voice => channel,
-            Some(_) => return tracing::info!("No voice channel defined for {channel_id}"),
-            None => return tracing::info!("No channel defined for {channel_id}"),
-        };
+impl TypeMapKey for AudioCacheKey {
+    type Value = Arc<AudioCache>;
+}
 
-        let members = match voice_channel.members(&ctx.cache) {
-            Ok(members) => members,
-            Err(err) => return tracing::error!("Failed getting members from channel: {err:?}"),
-        };
+struct LibraryKey;
 
-        if members.len() > 1 {
-            let remaining = members.len();
-            return tracing::info!("Remaining {remaining} members connected to voice channel");
-        }
+impl TypeMapKey for LibraryKey {
+    type Value = Arc<Library>;
+}
 
-        let manager = songbird::get(&ctx)
-            .await
-            .expect("Expected songbird in context");
+struct RadioKey;
 
-        if let Err(err) = manager.remove(voice_channel.guild_id).await {
-            return tracing::error!("Failed leaving empty voice channel automatically: {err:?}");
-        }
-    }
+impl TypeMapKey for RadioKey {
+    type Value = Arc<RadioStore>;
 }
 
-#[group]
-#[commands(help, join, leave, mute, play, skip, stop, unmute, queue, now)]
-struct General;
+struct PlaylistKey;
 
-#[tokio::main]
-async fn main() {
-    tracing_subscriber::fmt()
-        .with_max_level(tracing::Level::INFO)
-        .with_thread_ids(false)
-        .with_thread_names(false)
-        .compact()
-        .init();
+impl TypeMapKey for PlaylistKey {
+    type Value = Arc<PlaylistStore>;
+}
 
-    let token = env::var("DISCORD_TOKEN").expect("Expected DISCORD_TOKEN environment variable");
+struct SettingsKey;
 
-    let framework = StandardFramework::new().group(&GENERAL_GROUP);
-    framework.configure(Configuration::new().prefix("!"));
+impl TypeMapKey for SettingsKey {
+    type Value = Arc<SettingsStore>;
+}
 
-    let intents = GatewayIntents::non_privileged() | GatewayIntents::MESSAGE_CONTENT;
-    let mut client = Client::builder(&token, intents)
-        .event_handler(Handler)
-        .framework(framework)
-        .register_songbird()
-        .type_map_insert::<HttpKey>(HttpClient::new())
-        .await
-        .expect("Failed creating serenity client");
+struct SessionKey;
 
-    client
-        .start()
-        .await
-        .expect("Failed starting serenity client");
+impl TypeMapKey for SessionKey {
+    type Value = Arc<SessionStore>;
 }
 
-#[command]
-#[only_in(guilds)]
-async fn join(ctx: &Context, msg: &Message) -> CommandResult {
-    let (guild_id, author_channel_id) = {
-        let guild = msg.guild(&ctx.cache).expect("Expected guild to be defined");
-        let channel_id = guild
-            .voice_states
-            .get(&msg.author.id)
-            .and_then(|vs| vs.channel_id);
-
-        (guild.id, channel_id)
-    };
+struct SkipVotesKey;
 
-    let Some(connect_to) = author_channel_id else {
-        let error = EmbedBuilder::error()
-            .title("!join")
-            .description("User not in a voice channel")
-            .build();
+impl TypeMapKey for SkipVotesKey {
+    type Value = Arc<SkipVotes>;
+}
 
-        let message = CreateMessage::new().add_embed(error);
-        check_msg(msg.channel_id.send_message(&ctx.http, message).await);
-        return Ok(());
-    };
+struct IdleTimersKey;
 
-    let manager = songbird::get(ctx)
-        .await
-        .expect("Expected songbird in context");
+impl TypeMapKey for IdleTimersKey {
+    type Value = Arc<IdleTimers>;
+}
 
-    if manager.get(guild_id).is_some() {
-        let error = EmbedBuilder::error()
-            .title("!join")
-            .description("I'm already in another voice channel")
-            .build();
+/// Timers leaving voice channels everybody left, unless someone comes back in time.
+struct GraceTimersKey;
 
-        let message = CreateMessage::new().add_embed(error);
-        check_msg(msg.channel_id.send_message(ctx, message).await);
-        return Ok(());
-    }
+impl TypeMapKey for GraceTimersKey {
+    type Value = Arc<IdleTimers>;
+}
 
-    let Ok(voice_lock) = manager.join(guild_id, connect_to).await else {
-        let description = format!("Could not join the voice channel {}", connect_to.mention());
-        let error = EmbedBuilder::error()
-            .title("!join")
-            .description(description)
-            .build();
+/// Text channel each guild last called the bot to a voice channel from.
+struct TextChannelKey;
 
-        let message = CreateMessage::new().add_embed(error);
-        check_msg(msg.channel_id.send_message(&ctx.http, message).await);
-        return Ok(());
-    };
+impl TypeMapKey for TextChannelKey {
+    type Value = Arc<RwLock<HashMap<GuildId, ChannelId>>>;
+}
 
-    let embed = EmbedBuilder::new()
-        .title("!join")
-        .description(format!("Joined {}", connect_to.mention()))
-        .build();
+struct TrackTitleKey;
 
-    let message = CreateMessage::new().add_embed(embed);
-    check_msg(msg.channel_id.send_message(&ctx.http, message).await);
+impl TypeMapKey for TrackTitleKey {
+    type Value = Arc<str>;
+}
 
-    if let Err(err) = voice_lock.lock().await.deafen(true).await {
-        tracing::error!("Failed self deafening: {err}");
-    }
+struct TrackSourceKey;
 
-    Ok(())
+impl TypeMapKey for TrackSourceKey {
+    type Value = TrackSource;
 }
 
-#[command]
-#[only_in(guilds)]
-async fn leave(ctx: &Context, msg: &Message) -> CommandResult {
-    let (guild_id, author_channel_id) = {
-        let guild = msg.guild(&ctx.cache).expect("Expected guild to be defined");
-        let channel_id = guild
-            .voice_states
-            .get(&msg.author.id)
-            .and_then(|vs| vs.channel_id);
+struct TrackUrlKey;
 
-        (guild.id, channel_id)
-    };
+impl TypeMapKey for TrackUrlKey {
+    type Value = Arc<str>;
+}
 
-    let manager = songbird::get(ctx)
-        .await
-        .expect("Expected songbird in context");
+struct TrackRequesterKey;
 
-    let Some(voice_lock) = manager.get(guild_id) else {
-        let error = EmbedBuilder::error()
-            .title("!leave")
-            .description("User not in a voice channel")
-            .build();
+impl TypeMapKey for TrackRequesterKey {
+    type Value = UserId;
+}
 
-        let message = CreateMessage::new().add_embed(error);
-        check_msg(msg.channel_id.send_message(&ctx.http, message).await);
-        return Ok(());
-    };
+/// Marks the track paused by the grace period, to be resumed once someone joins back.
+struct GracePausedKey;
 
-    let voice_channel_id = voice_lock.lock().await.current_channel();
-    if author_channel_id.map(songbird::id::ChannelId::from) != voice_channel_id {
-        let error = EmbedBuilder::error()
-            .title("!leave")
-            .description("User not in the same voice channel")
-            .build();
+impl TypeMapKey for GracePausedKey {
+    type Value = ();
+}
 
-        let message = CreateMessage::new().add_embed(error);
-        check_msg(msg.channel_id.send_message(&ctx.http, message).await);
-        return Ok(());
-    }
+struct StreamTitleKey;
 
-    if let Err(err) = manager.remove(guild_id).await {
-        tracing::error!("Failed leaving voice channel: {err:?}");
-        let error = EmbedBuilder::error()
-            .title("!leave")
-            .description("Failed leaving voice channel")
-            .build();
+impl TypeMapKey for StreamTitleKey {
+    type Value = Arc<TitleWatcher>;
+}
 
-        let message = CreateMessage::new().add_embed(error);
-        check_msg(msg.channel_id.send_message(&ctx.http, message).await);
-        return Ok(());
+/// Downloads upcoming tracks into the audio cache whenever a track starts playing.
+struct PrefetchHandler {
+    queue: TrackQueue,
+    audio_cache: Arc<AudioCache>,
+}
+
+#[serenity::async_trait]
+impl songbird::EventHandler for PrefetchHandler {
+    async fn act(&self, _ctx: &EventContext<'_>) -> Option<Event> {
+        prefetch_upcoming(&self.queue, &self.audio_cache).await;
+        None
     }
+}
 
-    let voice_channel_mention = author_channel_id
-        .map(|id| id.mention())
-        .expect("Expected author channel id to be defined");
+/// Starts the guild idle timer when its queue ends or the playing track is paused, and
+/// cancels it once a track plays again.
+struct IdleHandler {
+    ctx: Context,
+    guild_id: GuildId,
+    queue: TrackQueue,
+}
 
-    let embed = EmbedBuilder::new()
-        .title("!leave")
-        .description(format!("Left voice channel {voice_channel_mention}"))
-        .build();
+#[serenity::async_trait]
+impl songbird::EventHandler for IdleHandler {
+    async fn act(&self, _ctx: &EventContext<'_>) -> Option<Event> {
+        // playback paused for the grace period must not start the idle timer
+        if get_grace_timers(&self.ctx)
+            .await
+            .is_running(self.guild_id.get())
+        {
+            return None;
+        }
 
-    let message = CreateMessage::new().add_embed(embed);
-    check_msg(msg.channel_id.send_message(&ctx.http, message).await);
+        // track specific handlers, such as the queue moving to the next track, already ran
+        if is_idle(&self.queue).await {
+            start_idle_timer(&self.ctx, self.guild_id).await;
+        } else {
+            get_idle_timers(&self.ctx).await.cancel(self.guild_id.get());
+        }
 
-    Ok(())
+        None
+    }
 }
 
-#[command]
-#[only_in(guilds)]
-async fn mute(ctx: &Context, msg: &Message) -> CommandResult {
-    let (guild_id, author_channel_id) = {
-        let guild = msg.guild(&ctx.cache).expect("Expected guild to be defined");
-        let channel_id = guild
-            .voice_states
-            .get(&msg.author.id)
-            .and_then(|vs| vs.channel_id);
-
-        (guild.id, channel_id)
-    };
-
-    let manager = songbird::get(ctx)
-        .await
-        .expect("Expected songbird in context");
+/// Consecutive failed reconnections after which a radio station is given up.
+const MAX_RADIO_RECONNECTS: u32 = 5;
 
-    let Some(voice_lock) = manager.get(guild_id) else {
-        let error = EmbedBuilder::error()
-            .title("!mute")
-            .description("User not in a voice channel")
-            .build();
+/// Minimum play time for a radio connection to be considered successful.
+const RADIO_STABLE_TIME: Duration = Duration::from_secs(30);
 
-        let message = CreateMessage::new().add_embed(error);
-        check_msg(msg.channel_id.send_message(&ctx.http, message).await);
-        return Ok(());
-    };
+/// Interval between snapshots of every guild playback, restored on the next start.
+const SESSION_SAVE_INTERVAL: Duration = Duration::from_secs(60);
 
-    let current_channel = voice_lock.lock().await.current_channel();
-    if author_channel_id.map(songbird::id::ChannelId::from) != current_channel {
-        let error = EmbedBuilder::error()
-            .title("!mute")
-            .description("User not in the same voice channel")
-            .build();
+/// Time given to each shutdown step before giving up on it.
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(10);
 
-        let message = CreateMessage::new().add_embed(error);
-        check_msg(msg.channel_id.send_message(&ctx.http, message).await);
-        return Ok(());
-    }
+/// Applies the guild default volume to tracks starting to play for the first time.
+struct DefaultVolumeHandler {
+    settings: Arc<SettingsStore>,
+    guild_id: GuildId,
+}
 
-    let embed = if voice_lock.lock().await.is_mute() {
-        EmbedBuilder::new()
-            .title("!mute")
-            .description("I'm already muted. Use `!unmute` to unmute me")
-            .build()
-    } else if let Err(err) = voice_lock.lock().await.mute(true).await {
-        tracing::error!("Failed self muting: {err}");
+#[serenity::async_trait]
+impl songbird::EventHandler for DefaultVolumeHandler {
+    async fn act(&self, ctx: &EventContext<'_>) -> Option<Event> {
+        let EventContext::Track(tracks) = ctx else {
+            return None;
+        };
 
-        EmbedBuilder::error()
-            .title("!mute")
-            .description("Could not mute myself")
-            .build()
-    } else {
-        EmbedBuilder::new()
-            .title("!mute")
-            .description("I'm now muted. Use `!unmute` to unmute me")
-            .build()
-    };
+        let volume = self
+            .settings
+            .get(self.guild_id.get())
+            .await
+            .default_volume?;
+        for (state, handle) in tracks.iter() {
+            if !state.play_time.is_zero() {
+                continue;
+            }
 
-    let message = CreateMessage::new().add_embed(embed);
-    check_msg(msg.channel_id.send_message(&ctx.http, message).await);
+            if let Err(err) = handle.set_volume(f32::from(volume) / 100.0) {
+                tracing::error!("Failed setting default volume: {err}");
+            }
+        }
 
-    Ok(())
+        None
+    }
 }
 
-#[command]
-#[only_in(guilds)]
-async fn play(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
-    let (guild_id, author_channel_id) = {
-        let guild = msg.guild(&ctx.cache).expect("Expected guild to be defined");
-        let channel_id = guild
-            .voice_states
-            .get(&msg.author.id)
-            .and_then(|vs| vs.channel_id);
+/// Reconnects to a radio station when its stream drops, keeping it at the front of the queue.
+struct RadioReconnectHandler {
+    call: Weak<Mutex<Call>>,
+    http_client: HttpClient,
+    station: Station,
+    /// Consecutive reconnection attempts made before this connection.
+    attempt: u32,
+    requester: UserId,
+}
 
-        (guild.id, channel_id)
-    };
+#[serenity::async_trait]
+impl songbird::EventHandler for RadioReconnectHandler {
+    async fn act(&self, ctx: &EventContext<'_>) -> Option<Event> {
+        let EventContext::Track([(state, ended_track)]) = ctx else {
+            return None;
+        };
 
-    let Some(connect_to) = author_channel_id else {
-        let error = EmbedBuilder::error()
-            .title("!play")
-            .description("User not in a voice channel")
-            .build();
+        // tracks stopped by users, through `!skip` or `!stop`, must not reconnect
+        if !matches!(state.playing, PlayMode::End | PlayMode::Errored(_)) {
+            return None;
+        }
 
-        let message = CreateMessage::new().add_embed(error);
-        check_msg(msg.channel_id.send_message(&ctx.http, message).await);
-        return Ok(());
-    };
+        let attempt = if state.play_time >= RADIO_STABLE_TIME {
+            1
+        } else {
+            self.attempt + 1
+        };
 
-    let Ok(music) = args.single::<String>() else {
-        let error = EmbedBuilder::error()
-            .title("!play")
-            .description("Missing music or URL argument")
-            .build();
+        let name = &self.station.name;
+        if attempt > MAX_RADIO_RECONNECTS {
+            tracing::error!("Giving up reconnecting to radio {name} after {attempt} attempts");
+            return None;
+        }
 
-        let message = CreateMessage::new().add_embed(error);
-        check_msg(msg.channel_id.send_message(&ctx.http, message).await);
-        return Ok(());
-    };
+        tracing::info!("Radio {name} stream dropped, reconnecting (attempt {attempt})");
+
+        let call_lock = self.call.upgrade()?;
+        let mut call = call_lock.lock().await;
+        let radio_track = enqueue_radio(
+            &mut call,
+            &call_lock,
+            self.http_client.clone(),
+            &self.station,
+            attempt,
+            self.requester,
+        )
+        .await;
+
+        // moves reconnected radio back to the front, pausing whichever track
+        // the queue started playing in its place
+        call.queue().modify_queue(|queue| {
+            queue.retain(|track| track.uuid() != ended_track.uuid());
+            let position = queue.iter().position(|t| t.uuid() == radio_track.uuid())?;
+            let radio = queue.remove(position)?;
+
+            if let Some(current) = queue.front() {
+                let _ = current.pause();
+            }
 
-    let manager = songbird::get(ctx)
-        .await
-        .expect("Expected songbird in context");
+            queue.push_front(radio);
+            queue.front().map(|track| track.play())
+        });
 
-    let voice_lock = if let Some(voice_lock) = manager.get(guild_id) {
-        voice_lock
-    } else {
-        match manager.join(guild_id, connect_to).await {
-            Ok(voice_lock) => voice_lock,
-            Err(err) => {
-                tracing::error!("Failed joining voice channel to play music: {err}");
+        None
+    }
+}
 
-                let description = format!("Could not join voice channel {}", connect_to.mention());
-                let error = EmbedBuilder::error()
-                    .title("!play")
-                    .description(description)
-                    .build();
+struct Handler;
 
-                let message = CreateMessage::new().add_embed(error);
-                check_msg(msg.channel_id.send_message(&ctx.http, message).await);
-                return Ok(());
-            }
-        }
-    };
+#[serenity::async_trait]
+impl EventHandler for Handler {
+    async fn ready(&self, ctx: Context, ready: Ready) {
+        Command::set_global_commands(&ctx.http, Vec::new())
+            .await
+            .expect("Could not set global slash commands");
 
-    let current_channel = voice_lock.lock().await.current_channel();
-    if author_channel_id.map(songbird::id::ChannelId::from) != current_channel {
-        let error = EmbedBuilder::error()
-            .title("!play")
-            .description("User not in the same voice channel")
-            .build();
+        tracing::info!("{} is connected!", ready.user.name);
 
-        let message = CreateMessage::new().add_embed(error);
-        check_msg(msg.channel_id.send_message(&ctx.http, message).await);
-        return Ok(());
-    }
+        let session_store = get_session_store(&ctx).await;
+        let Some(sessions) = session_store.take_for_restore().await else {
+            return;
+        };
 
-    // FIXME: only works for youtube playlists, and it doesn't cover all cases
-    if music.starts_with("http") && music.contains("&list=") {
-        let playlist_metadata = match playlist::query(&music).await {
-            Ok(metadata) => metadata,
-            Err(err) => {
-                tracing::error!("Failed quering playlist metadata: {err}");
+        for (guild_id, session) in sessions {
+            restore_session(&ctx, GuildId::new(guild_id), session).await;
+        }
 
-                let error = EmbedBuilder::error()
-                    .title("!play")
-                    .description("Could not load track from playlist")
-                    .build();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(SESSION_SAVE_INTERVAL);
+            interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
 
-                let message = CreateMessage::new().add_embed(error);
-                check_msg(msg.channel_id.send_message(&ctx.http, message).await);
-                return Ok(());
+            loop {
+                interval.tick().await;
+                save_sessions(&ctx).await;
             }
+        });
+    }
+
+    async fn voice_state_update(&self, ctx: Context, old: Option<VoiceState>, new: VoiceState) {
+        let Some(guild_id) = new.guild_id else {
+            return tracing::error!("Unexpected guild_id not defined in new state");
         };
 
-        let playlist_len = playlist_metadata.len();
-        let http_client = get_http_client(ctx).await;
+        let old_channel_id = old.and_then(|state| state.channel_id);
+        if new.user_id == ctx.cache.current_user().id {
+            return bot_voice_state_update(&ctx, guild_id, old_channel_id, &new).await;
+        }
 
-        let mut voice = voice_lock.lock().await;
-        for metadata in playlist_metadata.into_iter() {
-            let src = YoutubeDl::new(http_client.clone(), metadata.url);
-            let track_handle = voice.enqueue_with_preload(Track::from(src), None);
-            let mut typemap = track_handle.typemap().write().await;
-            typemap.insert::<TrackTitleKey>(metadata.title.into())
+        // mute, deafen and stream updates don't change who is listening
+        if old_channel_id == new.channel_id {
+            return;
         }
 
-        std::mem::drop(voice);
+        let manager = songbird::get(&ctx)
+            .await
+            .expect("Expected songbird in context");
 
-        let embed = EmbedBuilder::new()
-            .title("!play")
-            .description(format!("{playlist_len} tracks added to the queue"))
-            .build();
+        let Some(voice_lock) = manager.get(guild_id) else {
+            return;
+        };
 
-        let message = CreateMessage::new().add_embed(embed);
-        check_msg(msg.channel_id.send_message(&ctx.http, message).await);
-        return Ok(());
+        let Some(bot_channel_id) = voice_lock.lock().await.current_channel() else {
+            return;
+        };
+
+        let bot_channel_id = ChannelId::new(bot_channel_id.0.get());
+        if new.channel_id == Some(bot_channel_id) {
+            let is_bot = new.member.as_ref().is_some_and(|member| member.user.bot);
+            if !is_bot {
+                end_grace_period(&ctx, guild_id, bot_channel_id).await;
+            }
+        } else if old_channel_id == Some(bot_channel_id) {
+            let listeners = channel_listeners(&ctx, guild_id, bot_channel_id);
+            if listeners.is_empty() {
+                start_grace_period(&ctx, guild_id, &voice_lock).await;
+            } else {
+                let remaining = listeners.len();
+                tracing::info!("Remaining {remaining} members connected to voice channel");
+            }
+        }
+    }
+}
+
+/// Keeps up with the bot own voice state, changed by moderators moving it to another
+/// channel or disconnecting it, and by joining stage channels as a listener.
+async fn bot_voice_state_update(
+    ctx: &Context,
+    guild_id: GuildId,
+    old_channel_id: Option<ChannelId>,
+    new: &VoiceState,
+) {
+    let Some(channel_id) = new.channel_id else {
+        return leave_disconnected(ctx, guild_id, old_channel_id).await;
+    };
+
+    if new.suppress && new.request_to_speak_timestamp.is_none() {
+        speak_on_stage(ctx, guild_id, channel_id).await;
+    }
+
+    if old_channel_id.is_none() || old_channel_id == Some(channel_id) {
+        return;
     }
 
-    let mut src: Input = if music.starts_with("http") {
-        YoutubeDl::new(get_http_client(ctx).await, music).into()
+    // songbird already follows the move, while votes were cast by the previous listeners
+    tracing::info!("Moved to voice channel {channel_id} of {guild_id}");
+    get_skip_votes(ctx).await.clear(guild_id.get());
+
+    let manager = songbird::get(ctx)
+        .await
+        .expect("Expected songbird in context");
+
+    let Some(voice_lock) = manager.get(guild_id) else {
+        return;
+    };
+
+    if channel_listeners(ctx, guild_id, channel_id).is_empty() {
+        start_grace_period(ctx, guild_id, &voice_lock).await;
     } else {
-        YoutubeDl::new_search(get_http_client(ctx).await, music).into()
+        end_grace_period(ctx, guild_id, channel_id).await;
+    }
+}
+
+/// Becomes a speaker of the stage channel the bot joined as a listener, or requests to
+/// speak when lacking the permission to.
+async fn speak_on_stage(ctx: &Context, guild_id: GuildId, channel_id: ChannelId) {
+    let channel = ctx
+        .cache
+        .guild(guild_id)
+        .and_then(|guild| guild.channels.get(&channel_id).cloned());
+
+    let Some(channel) = channel.filter(|channel| channel.kind == ChannelType::Stage) else {
+        return;
     };
 
-    let metadata = src.aux_metadata().await?;
-    let track_handle = voice_lock
-        .lock()
+    let speak = EditVoiceState::new().suppress(false);
+    if let Err(err) = channel.edit_own_voice_state(&ctx.http, speak).await {
+        tracing::info!("Failed speaking on stage {channel_id}, requesting to: {err}");
+
+        let request = EditVoiceState::new().request_to_speak(true);
+        if let Err(err) = channel.edit_own_voice_state(&ctx.http, request).await {
+            tracing::error!("Failed requesting to speak on stage {channel_id}: {err}");
+        }
+    }
+}
+
+#[group]
+#[commands(
+    help,
+    join,
+    leave,
+    mute,
+    play,
+    skip,
+    stop,
+    unmute,
+    queue,
+    remove,
+    now,
+    cache,
+    local,
+    album,
+    radio,
+    import,
+    export,
+    playlist,
+    settings,
+    resume_session
+)]
+struct General;
+
+#[tokio::main]
+async fn main() {
+    let commands = GENERAL_GROUP
+        .options
+        .commands
+        .iter()
+        .map(|command| command.options.names[0])
+        .chain(sub_command_buckets())
+        .collect::<Vec<_>>();
+
+    let config = match Config::load().and_then(|config| {
+        config.check_cooldowns(&commands)?;
+        Ok(config)
+    }) {
+        Ok(config) => Arc::new(config),
+        Err(err) => {
+            eprintln!("Invalid configuration: {err}");
+            std::process::exit(1);
+        }
+    };
+
+    tracing_subscriber::fmt()
+        .with_max_level(config.log_level)
+        .with_thread_ids(false)
+        .with_thread_names(false)
+        .compact()
+        .init();
+
+    let ytdlp_config = Arc::new(config.ytdlp.clone());
+
+    let mut http_client = HttpClient::builder();
+    if let Some(proxy) = &ytdlp_config.proxy {
+        let proxy = Proxy::all(proxy).expect("Expected proxy URL to be validated by config");
+        http_client = http_client.proxy(proxy);
+    }
+
+    if !config.allow_private_hosts {
+        let proxy_host = ytdlp_config
+            .proxy
+            .as_deref()
+            .and_then(|proxy| reqwest::Url::parse(proxy).ok())
+            .and_then(|proxy| proxy.host_str().map(String::from));
+        http_client = stream::public_hosts_only(http_client, proxy_host);
+    }
+    let http_client = http_client.build().expect("Failed creating HTTP client");
+
+    let storage = Storage::new(config.data_dir.clone())
         .await
-        .enqueue_with_preload(Track::from(src), None);
+        .expect("Failed creating data directory");
 
-    let mut typemap = track_handle.typemap().write().await;
-    let title: Arc<str> = metadata.title.unwrap_or_else(|| "Unknown".into()).into();
-    let description = format!("Track {title} added to queue");
-    typemap.insert::<TrackTitleKey>(title);
+    let radio_store = RadioStore::load(storage.clone())
+        .await
+        .expect("Failed loading radio stations");
 
-    let embed = EmbedBuilder::new()
-        .title("!play")
+    let playlist_store = PlaylistStore::load(storage.clone())
+        .await
+        .expect("Failed loading saved playlists");
+
+    let settings_store = SettingsStore::load(storage.clone())
+        .await
+        .expect("Failed loading guild settings");
+
+    let session_store = SessionStore::load(storage.clone())
+        .await
+        .expect("Failed loading saved sessions");
+
+    let metadata_cache = MetadataCache::load(config.metadata_cache.clone())
+        .await
+        .map(Arc::new)
+        .expect("Failed loading metadata cache");
+    metadata_cache.clone().persist();
+
+    let owners = match Http::new(&config.token)
+        .get_current_application_info()
+        .await
+    {
+        Ok(info) => match info.team {
+            Some(team) => team.members.into_iter().map(|m| m.user.id).collect(),
+            None => info.owner.into_iter().map(|owner| owner.id).collect(),
+        },
+        Err(err) => {
+            tracing::error!("Failed getting application owners: {err:?}");
+            HashSet::new()
+        }
+    };
+
+    let audio_cache = match config.audio_cache.clone() {
+        Some(config) => AudioCache::new(config, ytdlp_config.clone())
+            .await
+            .map(Arc::new)
+            .map(Some)
+            .expect("Failed creating audio cache directory"),
+        None => None,
+    };
+
+    let mut framework = StandardFramework::new()
+        .group(&GENERAL_GROUP)
+        .on_dispatch_error(dispatch_error);
+
+    // every command has a bucket of its own, named after it
+    for command in commands {
+        framework = framework
+            .bucket(command, bucket(config.cooldown(command)))
+            .await;
+    }
+
+    // prefix is resolved per guild by `dynamic_prefix`, falling back to the configured one
+    framework.configure(
+        Configuration::new()
+            .prefix("")
+            .dynamic_prefix(dynamic_prefix)
+            .owners(owners),
+    );
+
+    let songbird = Songbird::serenity();
+    let session_store = Arc::new(session_store);
+    let settings_store = Arc::new(settings_store);
+    let text_channels = Arc::<RwLock<HashMap<GuildId, ChannelId>>>::default();
+
+    let intents = GatewayIntents::non_privileged() | GatewayIntents::MESSAGE_CONTENT;
+    let mut client = Client::builder(&config.token, intents)
+        .event_handler(Handler)
+        .framework(framework)
+        .register_songbird_with(songbird.clone())
+        .type_map_insert::<ConfigKey>(config.clone())
+        .type_map_insert::<EmbedStyleKey>(Arc::new(config.embed.clone()))
+        .type_map_insert::<HttpKey>(http_client)
+        .type_map_insert::<YtDlpKey>(ytdlp_config)
+        .type_map_insert::<MetadataCacheKey>(metadata_cache.clone())
+        .type_map_insert::<AttachmentKey>(config.attachment)
+        .type_map_insert::<RadioKey>(Arc::new(radio_store))
+        .type_map_insert::<PlaylistKey>(Arc::new(playlist_store))
+        .type_map_insert::<SettingsKey>(settings_store.clone())
+        .type_map_insert::<SessionKey>(session_store.clone())
+        .type_map_insert::<SkipVotesKey>(Arc::default())
+        .type_map_insert::<IdleTimersKey>(Arc::default())
+        .type_map_insert::<GraceTimersKey>(Arc::default())
+        .type_map_insert::<TextChannelKey>(text_channels.clone())
+        .await
+        .expect("Failed creating serenity client");
+
+    if let Some(audio_cache) = audio_cache {
+        client
+            .data
+            .write()
+            .await
+            .insert::<AudioCacheKey>(audio_cache);
+    }
+
+    if let Some(config) = config.library.clone() {
+        let library = Arc::new(Library::new(config));
+        library.clone().watch();
+        client.data.write().await.insert::<LibraryKey>(library);
+    }
+
+    let shard_manager = client.shard_manager.clone();
+    let http = client.http.clone();
+    tokio::spawn(async move {
+        shutdown_signal().await;
+        tracing::info!("Shutting down");
+
+        let cleanup = shutdown(
+            &http,
+            &config.embed,
+            &songbird,
+            &session_store,
+            &settings_store,
+            &text_channels,
+        );
+        if tokio::time::timeout(SHUTDOWN_TIMEOUT, cleanup)
+            .await
+            .is_err()
+        {
+            tracing::error!("Shutdown cleanup took longer than {SHUTDOWN_TIMEOUT:?}");
+        }
+
+        if tokio::time::timeout(SHUTDOWN_TIMEOUT, metadata_cache.flush())
+            .await
+            .is_err()
+        {
+            tracing::error!("Failed persisting metadata cache in time");
+        }
+
+        if tokio::time::timeout(SHUTDOWN_TIMEOUT, shard_manager.shutdown_all())
+            .await
+            .is_err()
+        {
+            tracing::error!("Failed shutting down shards in time, exiting");
+            std::process::exit(1);
+        }
+    });
+
+    client
+        .start()
+        .await
+        .expect("Failed starting serenity client");
+}
+
+/// Waits for SIGINT, or SIGTERM on unix platforms, as sent by `docker stop`.
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        let mut sigterm = signal(SignalKind::terminate()).expect("Failed listening to SIGTERM");
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => {},
+            _ = sigterm.recv() => {},
+        }
+    }
+
+    #[cfg(not(unix))]
+    if let Err(err) = tokio::signal::ctrl_c().await {
+        tracing::error!("Failed listening to SIGINT: {err}");
+    }
+}
+
+/// Saves every guild session, then says goodbye with embeds of `style` and leaves all
+/// voice channels.
+async fn shutdown(
+    http: &Http,
+    style: &EmbedStyle,
+    manager: &Songbird,
+    session_store: &SessionStore,
+    settings_store: &SettingsStore,
+    text_channels: &RwLock<HashMap<GuildId, ChannelId>>,
+) {
+    let text_channels = text_channels.read().await.clone();
+    let sessions = snapshot_sessions(manager, &text_channels).await;
+    let has_session = sessions.keys().copied().collect::<HashSet<_>>();
+
+    if let Err(err) = session_store.flush(sessions).await {
+        tracing::error!("Failed saving sessions: {err}");
+    }
+
+    let guild_ids = manager
+        .iter()
+        .map(|(guild_id, _)| GuildId::new(guild_id.0.get()))
+        .collect::<Vec<_>>();
+
+    for guild_id in guild_ids {
+        let announce_channel = settings_store.get(guild_id.get()).await.announce_channel;
+        let channel_id = announce_channel
+            .map(ChannelId::new)
+            .or_else(|| text_channels.get(&guild_id).copied());
+
+        if let Some(channel_id) = channel_id {
+            let name = &style.author_name;
+            let description = if has_session.contains(&guild_id.get()) {
+                format!("**{name}** is restarting. The queue will be resumed once back")
+            } else {
+                format!("**{name}** is shutting down. See you soon!")
+            };
+
+            let embed = EmbedBuilder::new()
+                .title("Goodbye")
+                .description(description)
+                .build(style);
+
+            let message = CreateMessage::new().add_embed(embed);
+            check_msg(channel_id.send_message(http, message).await);
+        }
+
+        if let Err(err) = manager.remove(guild_id).await {
+            tracing::error!("Failed leaving voice channel of {guild_id}: {err:?}");
+        }
+    }
+}
+
+/// Buckets of sub-commands, named after the command and sub-command joined by a dash.
+fn sub_command_buckets() -> impl Iterator<Item = &'static str> {
+    GENERAL_GROUP
+        .options
+        .commands
+        .iter()
+        .flat_map(|command| command.options.sub_commands)
+        .filter_map(|sub_command| sub_command.options.bucket)
+}
+
+/// Reply title and bucket of `command_name`, which serenity gives as the last word of
+/// sub-commands.
+fn dispatched_command(command_name: &str) -> (String, &str) {
+    for command in GENERAL_GROUP.options.commands {
+        let sub_command = command
+            .options
+            .sub_commands
+            .iter()
+            .find(|sub_command| sub_command.options.names[0] == command_name);
+
+        if let Some(sub_command) = sub_command {
+            let title = format!("!{}", command.options.names[0]);
+            return (title, sub_command.options.bucket.unwrap_or(command_name));
+        }
+    }
+
+    (format!("!{command_name}"), command_name)
+}
+
+fn bucket(cooldown: Cooldown) -> BucketBuilder {
+    let builder = match cooldown.scope {
+        CooldownScope::User => BucketBuilder::new_user(),
+        CooldownScope::Guild => BucketBuilder::new_guild(),
+        CooldownScope::Channel => BucketBuilder::new_channel(),
+        CooldownScope::Global => BucketBuilder::new_global(),
+    };
+
+    let builder = builder.delay(cooldown.delay.as_secs());
+    match cooldown.limit {
+        Some((uses, time_span)) => builder.limit(uses).time_span(time_span.as_secs()),
+        None => builder,
+    }
+}
+
+#[hook]
+async fn dynamic_prefix(ctx: &Context, msg: &Message) -> Option<String> {
+    Some(command_prefix(ctx, msg.guild_id).await)
+}
+
+/// Uses the guild prefix set with `!settings`, or the configured one otherwise.
+async fn command_prefix(ctx: &Context, guild_id: Option<GuildId>) -> String {
+    if let Some(guild_id) = guild_id {
+        let settings = get_settings_store(ctx).await.get(guild_id.get()).await;
+        if let Some(prefix) = settings.prefix {
+            return prefix;
+        }
+    }
+
+    get_config(ctx).await.prefix.clone()
+}
+
+#[hook]
+async fn dispatch_error(ctx: &Context, msg: &Message, error: DispatchError, command_name: &str) {
+    let style = get_embed_style(ctx).await;
+    let (title, bucket) = dispatched_command(command_name);
+    let description = match error {
+        DispatchError::LackingPermissions(permissions) => {
+            format!("You need the {permissions} permission to use this command")
+        }
+        DispatchError::CheckFailed(_, Reason::User(reason)) => reason,
+        // only the first rejected use is answered, so spamming does not spam replies too
+        DispatchError::Ratelimited(info) if info.is_first_try => {
+            let who = match get_config(ctx).await.cooldown(bucket).scope {
+                CooldownScope::User => "You are",
+                CooldownScope::Guild => "This server is",
+                CooldownScope::Channel => "This channel is",
+                CooldownScope::Global => "Everyone is",
+            };
+
+            let secs = info.rate_limit.as_millis().div_ceil(1000);
+            format!("{who} using this command too often, try again in {secs}s")
+        }
+        _ => return tracing::info!("Command {command_name} not dispatched: {error:?}"),
+    };
+
+    let error = EmbedBuilder::error()
+        .title(title)
         .description(description)
-        .build();
+        .build(&style);
 
-    let message = CreateMessage::new().embed(embed);
+    let message = CreateMessage::new().add_embed(error);
     check_msg(msg.channel_id.send_message(&ctx.http, message).await);
+}
 
-    Ok(())
+// Once the guild sets a `dj_role`, restricts playback control to members with that role
+// and to server managers. Anyone may still skip or remove tracks they requested, and
+// vote to skip the playing track once the guild enables `vote_skip`.
+#[check]
+#[name = "Dj"]
+async fn dj_check(
+    ctx: &Context,
+    msg: &Message,
+    args: &mut Args,
+    options: &CommandOptions,
+) -> Result<(), Reason> {
+    let Some(guild_id) = msg.guild_id else {
+        return Ok(());
+    };
+
+    let settings = get_settings_store(ctx).await.get(guild_id.get()).await;
+    let rules = Rules::from_settings(&settings);
+    let command = options.names.first().copied().unwrap_or_default();
+    let arg = args.current().map(String::from);
+
+    let is_dj = match rules.judge(false, command, arg.as_deref()) {
+        Verdict::Allowed => return Ok(()),
+        _ => is_dj(ctx, msg, rules.dj_role.map(RoleId::new)).await,
+    };
+
+    match rules.judge(is_dj, command, arg.as_deref()) {
+        Verdict::Allowed => Ok(()),
+        Verdict::Denied => Err(Reason::User(rules.denial(false))),
+        // skipping only the playing track registers a vote, handled by `!skip` itself
+        Verdict::OwnTracks { vote: true, .. } => Ok(()),
+        Verdict::OwnTracks { positions, .. } => {
+            match requested_tracks(ctx, guild_id, msg.author.id, positions).await {
+                true => Ok(()),
+                false => Err(Reason::User(rules.denial(true))),
+            }
+        }
+    }
 }
 
-#[command]
-#[only_in(guilds)]
-async fn skip(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
-    let (guild_id, author_channel_id) = {
-        let guild = msg.guild(&ctx.cache).expect("Expected guild to be defined");
-        let channel_id = guild
-            .voice_states
-            .get(&msg.author.id)
-            .and_then(|vs| vs.channel_id);
+/// Whether the author has the DJ role, if any, or is allowed to manage the server.
+async fn is_dj(ctx: &Context, msg: &Message, dj_role: Option<RoleId>) -> bool {
+    let member = match msg.member(ctx).await {
+        Ok(member) => member,
+        Err(err) => {
+            tracing::error!("Failed fetching member {}: {err:?}", msg.author.id);
+            return false;
+        }
+    };
+
+    if dj_role.is_some_and(|dj_role| member.roles.contains(&dj_role)) {
+        return true;
+    }
+
+    msg.guild(&ctx.cache).is_some_and(|guild| {
+        let permissions = guild.member_permissions(&member);
+        permissions.administrator() || permissions.manage_guild()
+    })
+}
+
+/// Whether the author can change the guild radio stations, as DJs and server managers can.
+async fn can_manage_stations(ctx: &Context, msg: &Message, guild_id: GuildId) -> bool {
+    let dj_role = get_settings_store(ctx)
+        .await
+        .get(guild_id.get())
+        .await
+        .dj_role;
+    is_dj(ctx, msg, dj_role.map(RoleId::new)).await
+}
 
-        (guild.id, channel_id)
+/// Users connected to `channel_id`, ignoring bots.
+fn channel_listeners(ctx: &Context, guild_id: GuildId, channel_id: ChannelId) -> HashSet<u64> {
+    let bot_id = ctx.cache.current_user().id;
+    let Some(guild) = ctx.cache.guild(guild_id) else {
+        return HashSet::new();
     };
 
+    guild
+        .voice_states
+        .values()
+        .filter(|vs| vs.channel_id == Some(channel_id) && vs.user_id != bot_id)
+        .filter(|vs| {
+            let member = vs
+                .member
+                .as_ref()
+                .or_else(|| guild.members.get(&vs.user_id));
+            !member.is_some_and(|member| member.user.bot)
+        })
+        .map(|vs| vs.user_id.get())
+        .collect()
+}
+
+/// Whether every track at `positions` of the guild queue was requested by `user_id`.
+async fn requested_tracks(
+    ctx: &Context,
+    guild_id: GuildId,
+    user_id: UserId,
+    positions: std::ops::Range<usize>,
+) -> bool {
     let manager = songbird::get(ctx)
         .await
         .expect("Expected songbird in context");
 
     let Some(voice_lock) = manager.get(guild_id) else {
-        let error = EmbedBuilder::error()
-            .title("!skip")
-            .description("User not in a voice channel")
-            .build();
+        return false;
+    };
 
-        let message = CreateMessage::new().add_embed(error);
-        check_msg(msg.channel_id.send_message(&ctx.http, message).await);
-        return Ok(());
+    let tracks = voice_lock.lock().await.queue().current_queue();
+    let end = positions.end.min(tracks.len());
+    let Some(targets) = tracks.get(positions.start..end).filter(|t| !t.is_empty()) else {
+        return false;
     };
 
-    let current_channel = voice_lock.lock().await.current_channel();
-    if author_channel_id.map(songbird::id::ChannelId::from) != current_channel {
-        let error = EmbedBuilder::error()
-            .title("!skip")
-            .description("User not in the same voice channel")
-            .build();
+    for track in targets {
+        if get_track_requester(track).await != Some(user_id) {
+            return false;
+        }
+    }
 
-        let message = CreateMessage::new().add_embed(error);
-        check_msg(msg.channel_id.send_message(&ctx.http, message).await);
-        return Ok(());
+    true
+}
+
+#[command]
+#[only_in(guilds)]
+#[bucket = "join"]
+async fn join(ctx: &Context, msg: &Message) -> CommandResult {
+    let style = get_embed_style(ctx).await;
+    let voice = match VoiceCommandContext::join(ctx, msg).await {
+        Ok(voice) if voice.joined => voice,
+        result @ (Ok(_) | Err(VoiceCommandError::DifferentChannel)) => {
+            let description = match result {
+                Ok(_) => "I'm already in your voice channel",
+                Err(_) => "I'm already in another voice channel",
+            };
+
+            let error = EmbedBuilder::error()
+                .title("!join")
+                .description(description)
+                .build(&style);
+
+            let message = CreateMessage::new().add_embed(error);
+            check_msg(msg.channel_id.send_message(ctx, message).await);
+            return Ok(());
+        }
+        Err(err) => return err.reply(ctx, msg, "!join").await,
+    };
+
+    let embed = EmbedBuilder::new()
+        .title("!join")
+        .description(format!("Joined {}", voice.channel_id.mention()))
+        .build(&style);
+
+    let message = CreateMessage::new().add_embed(embed);
+    check_msg(voice.text_channel_id.send_message(&ctx.http, message).await);
+
+    if let Err(err) = voice.call.lock().await.deafen(true).await {
+        tracing::error!("Failed self deafening: {err}");
     }
 
-    if voice_lock.lock().await.queue().is_empty() {
+    Ok(())
+}
+
+#[command]
+#[only_in(guilds)]
+#[checks(Dj)]
+#[bucket = "leave"]
+async fn leave(ctx: &Context, msg: &Message) -> CommandResult {
+    let style = get_embed_style(ctx).await;
+    let VoiceCommandContext {
+        guild_id,
+        channel_id,
+        ..
+    } = match VoiceCommandContext::current(ctx, msg).await {
+        Ok(voice) => voice,
+        Err(err) => return err.reply(ctx, msg, "!leave").await,
+    };
+
+    let manager = songbird::get(ctx)
+        .await
+        .expect("Expected songbird in context");
+
+    get_idle_timers(ctx).await.cancel(guild_id.get());
+    get_grace_timers(ctx).await.cancel(guild_id.get());
+    if let Err(err) = manager.remove(guild_id).await {
+        tracing::error!("Failed leaving voice channel: {err:?}");
         let error = EmbedBuilder::error()
-            .title("!skip")
-            .description("Queue is already empty. No tracks to skip")
-            .build();
+            .title("!leave")
+            .description("Failed leaving voice channel")
+            .build(&style);
 
         let message = CreateMessage::new().add_embed(error);
         check_msg(msg.channel_id.send_message(&ctx.http, message).await);
         return Ok(());
     }
 
-    let amount = match args
-        .single::<String>()
-        .unwrap_or_else(|_| String::from("1"))
-        .parse::<usize>()
-    {
-        Ok(amount) if amount > 20 => {
+    let embed = EmbedBuilder::new()
+        .title("!leave")
+        .description(format!("Left voice channel {}", channel_id.mention()))
+        .build(&style);
+
+    let message = CreateMessage::new().add_embed(embed);
+    check_msg(msg.channel_id.send_message(&ctx.http, message).await);
+
+    Ok(())
+}
+
+#[command("resume-session")]
+#[only_in(guilds)]
+#[bucket = "resume-session"]
+async fn resume_session(ctx: &Context, msg: &Message) -> CommandResult {
+    let style = get_embed_style(ctx).await;
+    let guild_id = msg.guild_id.expect("Expected guild_id to be defined");
+    let session_store = get_session_store(ctx).await;
+
+    let session = match session_store.unpark(guild_id.get()).await {
+        Ok(Some(session)) => session,
+        result => {
+            if let Err(err) = result {
+                tracing::error!("Failed taking session of {guild_id}: {err}");
+            }
+
             let error = EmbedBuilder::error()
-                .title("!skip")
-                .description("Cannot skip more than 20 tracks at once")
-                .build();
+                .title("!resume-session")
+                .description("There is no queue left behind to resume")
+                .build(&style);
 
             let message = CreateMessage::new().add_embed(error);
             check_msg(msg.channel_id.send_message(&ctx.http, message).await);
             return Ok(());
         }
-        Ok(amount) => amount,
-        Err(_) => {
-            let error = EmbedBuilder::error()
-                .title("!skip")
-                .description("Amount of tracks to skip must be a positive integer")
-                .build();
+    };
 
-            let message = CreateMessage::new().add_embed(error);
-            check_msg(msg.channel_id.send_message(&ctx.http, message).await);
-            return Ok(());
+    let voice = match VoiceCommandContext::join(ctx, msg).await {
+        Ok(voice) => voice,
+        Err(err) => {
+            // kept for another try once the author joins a voice channel
+            if let Err(err) = session_store.park(guild_id.get(), session).await {
+                tracing::error!("Failed keeping session of {guild_id}: {err}");
+            }
+
+            return err.reply(ctx, msg, "!resume-session").await;
         }
     };
 
-    if let Err(err) = voice_lock.lock().await.queue().skip() {
-        tracing::error!("Failed skipping current track: {err}");
+    if voice.joined {
+        if let Err(err) = voice.call.lock().await.deafen(true).await {
+            tracing::error!("Failed self deafening: {err}");
+        }
+    }
 
-        let error = EmbedBuilder::error()
-            .title("!skip")
-            .description("Could not skip current track")
-            .build();
+    let call = voice.call.lock().await;
+    let mut quota = user_quota(ctx, guild_id, call.queue(), msg.author.id).await;
+    std::mem::drop(call);
+
+    let (admitted, rejected) = admit_session(&mut quota, session.clone());
+    let restorable = admitted
+        .tracks
+        .iter()
+        .any(|track| track.source != TrackSource::Attachment);
+    if !rejected.is_empty() && !restorable {
+        // kept for another try once the queue has room for it
+        if let Err(err) = session_store.park(guild_id.get(), session).await {
+            tracing::error!("Failed keeping session of {guild_id}: {err}");
+        }
 
-        let message = CreateMessage::new().add_embed(error);
+        let message = CreateMessage::new()
+            .add_embed(resolve::rejection("!resume-session", &rejected).build(&style));
         check_msg(msg.channel_id.send_message(&ctx.http, message).await);
         return Ok(());
     }
 
-    if amount == 1 {
-        let description = match voice_lock.lock().await.queue().current() {
-            Some(track) => {
+    let handles = enqueue_session(ctx, guild_id, &voice.call, &admitted).await;
+
+    let mut description = format!(
+        "{} tracks added back to the queue in {}{}",
+        handles.len(),
+        voice.channel_id.mention(),
+        expired_attachments_note(&admitted)
+    );
+    render::list(&mut description, "Rejected", "tracks", &rejected);
+
+    let embed = EmbedBuilder::new()
+        .title("!resume-session")
+        .description(description)
+        .build(&style);
+
+    let message = CreateMessage::new().add_embed(embed);
+    check_msg(msg.channel_id.send_message(&ctx.http, message).await);
+
+    Ok(())
+}
+
+#[command]
+#[only_in(guilds)]
+#[checks(Dj)]
+#[bucket = "mute"]
+async fn mute(ctx: &Context, msg: &Message) -> CommandResult {
+    let style = get_embed_style(ctx).await;
+    let VoiceCommandContext {
+        call: voice_lock, ..
+    } = match VoiceCommandContext::current(ctx, msg).await {
+        Ok(voice) => voice,
+        Err(err) => return err.reply(ctx, msg, "!mute").await,
+    };
+
+    let embed = if voice_lock.lock().await.is_mute() {
+        EmbedBuilder::new()
+            .title("!mute")
+            .description("I'm already muted. Use `!unmute` to unmute me")
+            .build(&style)
+    } else if let Err(err) = voice_lock.lock().await.mute(true).await {
+        tracing::error!("Failed self muting: {err}");
+
+        EmbedBuilder::error()
+            .title("!mute")
+            .description("Could not mute myself")
+            .build(&style)
+    } else {
+        EmbedBuilder::new()
+            .title("!mute")
+            .description("I'm now muted. Use `!unmute` to unmute me")
+            .build(&style)
+    };
+
+    let message = CreateMessage::new().add_embed(embed);
+    check_msg(msg.channel_id.send_message(&ctx.http, message).await);
+
+    Ok(())
+}
+
+#[command]
+#[only_in(guilds)]
+#[bucket = "play"]
+async fn play(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let query = args.single::<String>().ok();
+    let attachments = msg
+        .attachments
+        .iter()
+        .map(|attachment| Attachment {
+            filename: attachment.filename.clone(),
+            url: attachment.url.clone(),
+            size: attachment.size,
+            content_type: attachment.content_type.clone(),
+        })
+        .collect::<Vec<_>>();
+
+    let attachment_config = get_attachment_config(ctx).await;
+    let mut command = PlayCommand {
+        ctx: ctx.clone(),
+        msg: msg.clone(),
+        style: get_embed_style(ctx).await,
+        voice: None,
+    };
+
+    resolve::play_command(
+        &mut command,
+        query.as_deref(),
+        &attachments,
+        &attachment_config,
+    )
+    .await;
+
+    Ok(())
+}
+
+/// `!play` sent with `msg`, replying in its channel. Owns the context and message, since
+/// the command future is not `Send` when its queue borrows them.
+struct PlayCommand {
+    ctx: Context,
+    msg: Message,
+    style: Arc<EmbedStyle>,
+    /// Call joined for the author, once [`PlayContext::join`] succeeds.
+    voice: Option<VoiceCommandContext>,
+}
+
+impl PlayContext for PlayCommand {
+    type JoinError = VoiceCommandError;
+
+    type Queue<'b>
+        = PlayCall<'b>
+    where
+        Self: 'b;
+
+    async fn join(&mut self) -> Result<(), VoiceCommandError> {
+        self.voice = Some(VoiceCommandContext::join(&self.ctx, &self.msg).await?);
+        Ok(())
+    }
+
+    async fn lock(&self) -> PlayCall<'_> {
+        let voice = self
+            .voice
+            .as_ref()
+            .expect("Expected voice channel to be joined before locking its queue");
+
+        PlayCall {
+            ctx: &self.ctx,
+            guild_id: voice.guild_id,
+            author: self.msg.author.id,
+            voice: voice.call.lock().await,
+        }
+    }
+
+    async fn resolve(&self, query: &str) -> Result<Resolved, ResolveError> {
+        resolve(&self.ctx, query).await
+    }
+
+    async fn reply(&self, reply: EmbedBuilder) {
+        let message = CreateMessage::new().add_embed(reply.build(&self.style));
+        check_msg(
+            self.msg
+                .channel_id
+                .send_message(&self.ctx.http, message)
+                .await,
+        );
+    }
+}
+
+/// Call queue locked by `!play`, enqueueing tracks as requested by `author`.
+struct PlayCall<'a> {
+    ctx: &'a Context,
+    guild_id: GuildId,
+    author: UserId,
+    voice: MutexGuard<'a, Call>,
+}
+
+impl PlayQueue for PlayCall<'_> {
+    async fn quota(&mut self) -> Quota {
+        user_quota(self.ctx, self.guild_id, self.voice.queue(), self.author).await
+    }
+
+    async fn enqueue(&mut self, resolved: Resolved) {
+        enqueue_resolved(self.ctx, &mut self.voice, resolved, self.author).await;
+        apply_fair_queue(self.ctx, self.guild_id, self.voice.queue()).await;
+        prefetch(self.ctx, self.voice.queue()).await;
+    }
+
+    async fn enqueue_attachment(&mut self, attachment: &Attachment) {
+        let http_client = get_http_client(self.ctx).await;
+        enqueue_attachment(
+            &mut self.voice,
+            http_client,
+            &attachment.url,
+            &attachment.filename,
+            Some(u64::from(attachment.size)),
+            self.author,
+        )
+        .await;
+        apply_fair_queue(self.ctx, self.guild_id, self.voice.queue()).await;
+    }
+}
+
+#[command]
+#[only_in(guilds)]
+#[checks(Dj)]
+#[bucket = "skip"]
+async fn skip(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let style = get_embed_style(ctx).await;
+    let VoiceCommandContext {
+        guild_id,
+        channel_id,
+        call: voice_lock,
+        ..
+    } = match VoiceCommandContext::current(ctx, msg).await {
+        Ok(voice) => voice,
+        Err(err) => return err.reply(ctx, msg, "!skip").await,
+    };
+
+    if voice_lock.lock().await.queue().is_empty() {
+        let error = EmbedBuilder::error()
+            .title("!skip")
+            .description("Queue is already empty. No tracks to skip")
+            .build(&style);
+
+        let message = CreateMessage::new().add_embed(error);
+        check_msg(msg.channel_id.send_message(&ctx.http, message).await);
+        return Ok(());
+    }
+
+    let skip_limit = get_config(ctx).await.skip_limit;
+    let amount = match args::skip_amount(args.current(), skip_limit) {
+        Ok(amount) => amount,
+        Err(err) => {
+            let error = EmbedBuilder::error()
+                .title("!skip")
+                .description(err.to_string())
+                .build(&style);
+
+            let message = CreateMessage::new().add_embed(error);
+            check_msg(msg.channel_id.send_message(&ctx.http, message).await);
+            return Ok(());
+        }
+    };
+
+    // non DJs skipping a track requested by others only vote for it, when enabled
+    let settings = get_settings_store(ctx).await.get(guild_id.get()).await;
+    let mut tally = None;
+    if let Some(percent) = settings.vote_skip.filter(|_| amount == 1) {
+        let dj_role = settings.dj_role.map(RoleId::new);
+        let voting = !is_dj(ctx, msg, dj_role).await
+            && !requested_tracks(ctx, guild_id, msg.author.id, 0..1).await;
+
+        let current = voice_lock.lock().await.queue().current();
+        if let Some(current) = current.filter(|_| voting) {
+            let listeners = channel_listeners(ctx, guild_id, channel_id);
+
+            let votes = get_skip_votes(ctx).await.vote(
+                guild_id.get(),
+                &current,
+                msg.author.id.get(),
+                &listeners,
+            );
+            let required = vote_skip::required_votes(listeners.len(), percent);
+
+            if votes < required {
+                let title = get_track_title(&current).await;
+                let embed = EmbedBuilder::new()
+                    .title("!skip")
+                    .description(render::vote_progress(&title, votes, required))
+                    .build(&style);
+
+                let message = CreateMessage::new().add_embed(embed);
+                check_msg(msg.channel_id.send_message(&ctx.http, message).await);
+                return Ok(());
+            }
+
+            tally = Some((votes, required));
+        }
+    }
+
+    if let Err(err) = voice_lock.lock().await.queue().skip() {
+        tracing::error!("Failed skipping current track: {err}");
+
+        let error = EmbedBuilder::error()
+            .title("!skip")
+            .description("Could not skip current track")
+            .build(&style);
+
+        let message = CreateMessage::new().add_embed(error);
+        check_msg(msg.channel_id.send_message(&ctx.http, message).await);
+        return Ok(());
+    }
+
+    get_skip_votes(ctx).await.clear(guild_id.get());
+
+    if amount == 1 {
+        let mut description = match voice_lock.lock().await.queue().current() {
+            Some(track) => {
                 let title = get_track_title(&track).await;
                 format!("Current track {title} skipped")
             }
             None => String::from("Current track skipped"),
         };
 
+        if let Some((votes, required)) = tally {
+            description.push_str(&format!(" with {votes}/{required} votes"));
+        }
+
         let embed = EmbedBuilder::new()
             .title("!skip")
             .description(description)
-            .build();
+            .build(&style);
 
         let message = CreateMessage::new().add_embed(embed);
         check_msg(msg.channel_id.send_message(&ctx.http, message).await);
@@ -585,296 +1448,2282 @@ async fn skip(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
     }
 
     let embed = EmbedBuilder::new()
-        .title("!skip")
+        .title("!skip")
+        .description(description)
+        .build(&style);
+
+    let message = CreateMessage::new().add_embed(embed);
+    check_msg(msg.channel_id.send_message(&ctx.http, message).await);
+
+    Ok(())
+}
+
+#[command]
+#[only_in(guilds)]
+#[checks(Dj)]
+#[bucket = "stop"]
+async fn stop(ctx: &Context, msg: &Message, _args: Args) -> CommandResult {
+    let VoiceCommandContext {
+        call: voice_lock, ..
+    } = match VoiceCommandContext::current(ctx, msg).await {
+        Ok(voice) => voice,
+        Err(err) => return err.reply(ctx, msg, "!stop").await,
+    };
+
+    voice_lock.lock().await.queue().stop();
+
+    Ok(())
+}
+
+#[command]
+#[only_in(guilds)]
+#[checks(Dj)]
+#[bucket = "unmute"]
+async fn unmute(ctx: &Context, msg: &Message) -> CommandResult {
+    let style = get_embed_style(ctx).await;
+    let VoiceCommandContext {
+        call: voice_lock, ..
+    } = match VoiceCommandContext::current(ctx, msg).await {
+        Ok(voice) => voice,
+        Err(err) => return err.reply(ctx, msg, "!unmute").await,
+    };
+
+    let embed = if let Err(err) = voice_lock.lock().await.mute(false).await {
+        tracing::error!("Failed self unmuting: {err}");
+
+        EmbedBuilder::error()
+            .title("!unmute")
+            .description("Could not unmute myself")
+            .build(&style)
+    } else {
+        EmbedBuilder::new()
+            .title("!unmute")
+            .description("I'm now unmuted. Use `!mute` to mute me")
+            .build(&style)
+    };
+
+    let message = CreateMessage::new().add_embed(embed);
+    check_msg(msg.channel_id.send_message(&ctx.http, message).await);
+
+    Ok(())
+}
+
+#[command]
+#[only_in(guilds)]
+#[bucket = "queue"]
+async fn queue(ctx: &Context, msg: &Message) -> CommandResult {
+    let style = get_embed_style(ctx).await;
+    let VoiceCommandContext {
+        call: voice_lock, ..
+    } = match VoiceCommandContext::current(ctx, msg).await {
+        Ok(voice) => voice,
+        Err(err) => return err.reply(ctx, msg, "!queue").await,
+    };
+
+    let mut tracks = voice_lock.lock().await.queue().current_queue();
+    if tracks.is_empty() {
+        let embed = EmbedBuilder::new()
+            .title("!queue")
+            .description("Queue is curently empty")
+            .build(&style);
+
+        let message = CreateMessage::new().add_embed(embed);
+        check_msg(msg.channel_id.send_message(&ctx.http, message).await);
+        return Ok(());
+    }
+
+    let current_track = tracks.remove(0);
+    let current = queue_entry(&current_track).await;
+
+    let limit = get_config(ctx).await.queue_display_limit;
+    let mut upcoming = Vec::with_capacity(tracks.len().min(limit));
+    for handle in tracks.iter().take(limit) {
+        upcoming.push(queue_entry(handle).await);
+    }
+
+    let description = render::queue(&current, &upcoming, tracks.len());
+
+    let embed = EmbedBuilder::new()
+        .title("!queue")
+        .description(description)
+        .build(&style);
+
+    let message = CreateMessage::new().embed(embed);
+    check_msg(msg.channel_id.send_message(&ctx.http, message).await);
+
+    Ok(())
+}
+
+#[command]
+#[only_in(guilds)]
+#[checks(Dj)]
+#[bucket = "remove"]
+async fn remove(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let style = get_embed_style(ctx).await;
+    let VoiceCommandContext {
+        call: voice_lock, ..
+    } = match VoiceCommandContext::current(ctx, msg).await {
+        Ok(voice) => voice,
+        Err(err) => return err.reply(ctx, msg, "!remove").await,
+    };
+
+    let index = match args::queue_position(args.current()) {
+        Ok(index) => index,
+        Err(err) => {
+            let error = EmbedBuilder::error()
+                .title("!remove")
+                .description(err.to_string())
+                .build(&style);
+
+            let message = CreateMessage::new().add_embed(error);
+            check_msg(msg.channel_id.send_message(&ctx.http, message).await);
+            return Ok(());
+        }
+    };
+
+    let removed = voice_lock
+        .lock()
+        .await
+        .queue()
+        .modify_queue(|q| q.remove(index));
+
+    let Some(removed) = removed else {
+        let error = EmbedBuilder::error()
+            .title("!remove")
+            .description(format!(
+                "There is no track at position {index} of the queue"
+            ))
+            .build(&style);
+
+        let message = CreateMessage::new().add_embed(error);
+        check_msg(msg.channel_id.send_message(&ctx.http, message).await);
+        return Ok(());
+    };
+
+    let handle = removed.handle();
+    if let Err(err) = handle.stop() {
+        tracing::error!("Failed stopping removed track: {err}");
+    }
+
+    let title = get_track_title(&handle).await;
+    let embed = EmbedBuilder::new()
+        .title("!remove")
+        .description(format!("Removed **{title}** from the queue"))
+        .build(&style);
+
+    let message = CreateMessage::new().add_embed(embed);
+    check_msg(msg.channel_id.send_message(&ctx.http, message).await);
+
+    Ok(())
+}
+
+#[command]
+#[only_in(guilds)]
+#[bucket = "now"]
+async fn now(ctx: &Context, msg: &Message) -> CommandResult {
+    let style = get_embed_style(ctx).await;
+    let VoiceCommandContext {
+        call: voice_lock, ..
+    } = match VoiceCommandContext::current(ctx, msg).await {
+        Ok(voice) => voice,
+        Err(err) => return err.reply(ctx, msg, "!now").await,
+    };
+
+    let Some(current_track) = voice_lock.lock().await.queue().current() else {
+        let embed = EmbedBuilder::new()
+            .title("!now")
+            .description("Not currently playing a track")
+            .build(&style);
+
+        let message = CreateMessage::new().add_embed(embed);
+        check_msg(msg.channel_id.send_message(&ctx.http, message).await);
+        return Ok(());
+    };
+
+    let title = get_track_title(&current_track).await;
+    let mut description = format!("Now playing {title}");
+
+    let typemap = current_track.typemap().read().await;
+    if typemap.get::<TrackSourceKey>() == Some(&TrackSource::Stream) {
+        description.push_str(" (live)");
+    }
+
+    if let Some(song) = typemap.get::<StreamTitleKey>().and_then(|w| w.title()) {
+        description.push_str(&format!("\nOn air: **{song}**"));
+    }
+
+    std::mem::drop(typemap);
+
+    let embed = EmbedBuilder::new()
+        .title("!now")
+        .description(description)
+        .build(&style);
+
+    let message = CreateMessage::new().add_embed(embed);
+    check_msg(msg.channel_id.send_message(&ctx.http, message).await);
+
+    Ok(())
+}
+
+#[command]
+#[only_in(guilds)]
+#[bucket = "local"]
+async fn local(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let style = get_embed_style(ctx).await;
+    let Some(library) = get_library(ctx).await else {
+        let error = EmbedBuilder::error()
+            .title("!local")
+            .description("Local library is not configured")
+            .build(&style);
+
+        let message = CreateMessage::new().add_embed(error);
+        check_msg(msg.channel_id.send_message(&ctx.http, message).await);
+        return Ok(());
+    };
+
+    let query = args.rest().trim();
+    if query.is_empty() {
+        let error = EmbedBuilder::error()
+            .title("!local")
+            .description("Missing track query argument")
+            .build(&style);
+
+        let message = CreateMessage::new().add_embed(error);
+        check_msg(msg.channel_id.send_message(&ctx.http, message).await);
+        return Ok(());
+    }
+
+    let Some(track) = library.search(query).into_iter().next() else {
+        let error = EmbedBuilder::error()
+            .title("!local")
+            .description(format!("No local track found for {query}"))
+            .build(&style);
+
+        let message = CreateMessage::new().add_embed(error);
+        check_msg(msg.channel_id.send_message(&ctx.http, message).await);
+        return Ok(());
+    };
+
+    let voice_lock = match VoiceCommandContext::join(ctx, msg).await {
+        Ok(voice) => voice.call,
+        Err(err) => return err.reply(ctx, msg, "!local").await,
+    };
+
+    let title = track.display_title();
+    let description = format!("Track {title} added to queue");
+    let guild_id = msg.guild_id.expect("Expected guild to be defined");
+
+    let mut voice = voice_lock.lock().await;
+    let mut quota = user_quota(ctx, guild_id, voice.queue(), msg.author.id).await;
+    if let Err(rejection) = quota.admit(track.duration, false) {
+        std::mem::drop(voice);
+
+        let error = resolve::rejection("!local", &[format!("{title}: {rejection}")]).build(&style);
+        let message = CreateMessage::new().add_embed(error);
+        check_msg(msg.channel_id.send_message(&ctx.http, message).await);
+        return Ok(());
+    }
+
+    enqueue_local(&mut voice, track.path.clone(), &title, msg.author.id).await;
+    apply_fair_queue(ctx, guild_id, voice.queue()).await;
+    std::mem::drop(voice);
+
+    let embed = EmbedBuilder::new()
+        .title("!local")
+        .description(description)
+        .build(&style);
+
+    let message = CreateMessage::new().add_embed(embed);
+    check_msg(msg.channel_id.send_message(&ctx.http, message).await);
+
+    Ok(())
+}
+
+#[command]
+#[only_in(guilds)]
+#[bucket = "album"]
+async fn album(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let style = get_embed_style(ctx).await;
+    let Some(library) = get_library(ctx).await else {
+        let error = EmbedBuilder::error()
+            .title("!album")
+            .description("Local library is not configured")
+            .build(&style);
+
+        let message = CreateMessage::new().add_embed(error);
+        check_msg(msg.channel_id.send_message(&ctx.http, message).await);
+        return Ok(());
+    };
+
+    let name = args.rest().trim();
+    let tracks = library.album(name);
+    let Some(album_name) = tracks.first().and_then(|track| track.album.clone()) else {
+        let error = EmbedBuilder::error()
+            .title("!album")
+            .description(format!("No local album found for {name}"))
+            .build(&style);
+
+        let message = CreateMessage::new().add_embed(error);
+        check_msg(msg.channel_id.send_message(&ctx.http, message).await);
+        return Ok(());
+    };
+
+    let voice_lock = match VoiceCommandContext::join(ctx, msg).await {
+        Ok(voice) => voice.call,
+        Err(err) => return err.reply(ctx, msg, "!album").await,
+    };
+
+    let guild_id = msg.guild_id.expect("Expected guild to be defined");
+    let mut voice = voice_lock.lock().await;
+    let mut quota = user_quota(ctx, guild_id, voice.queue(), msg.author.id).await;
+    let mut enqueued = 0;
+    let mut rejected = Vec::new();
+    for track in tracks.iter() {
+        let title = track.display_title();
+        if let Err(rejection) = quota.admit(track.duration, false) {
+            rejected.push(format!("{title}: {rejection}"));
+            continue;
+        }
+
+        enqueue_local(&mut voice, track.path.clone(), &title, msg.author.id).await;
+        enqueued += 1;
+    }
+
+    apply_fair_queue(ctx, guild_id, voice.queue()).await;
+    std::mem::drop(voice);
+
+    let embed = if enqueued == 0 {
+        resolve::rejection("!album", &rejected)
+    } else {
+        let mut description = format!("{enqueued} tracks from **{album_name}** added to the queue");
+        render::list(&mut description, "Rejected", "tracks", &rejected);
+
+        EmbedBuilder::new().title("!album").description(description)
+    };
+
+    let message = CreateMessage::new().add_embed(embed.build(&style));
+    check_msg(msg.channel_id.send_message(&ctx.http, message).await);
+
+    Ok(())
+}
+
+#[command]
+#[only_in(guilds)]
+#[bucket = "radio"]
+async fn radio(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let style = get_embed_style(ctx).await;
+    let guild_id = msg.guild_id.expect("Expected guild to be defined");
+    let radio_store = get_radio_store(ctx).await;
+
+    let subcommand = args.single::<String>();
+    let manages = matches!(subcommand.as_deref(), Ok("add" | "replace" | "remove"));
+    if manages && !can_manage_stations(ctx, msg, guild_id).await {
+        let error = EmbedBuilder::error()
+            .title("!radio")
+            .description(
+                "Only DJs and members with the Manage Server permission can change stations",
+            )
+            .build(&style);
+
+        let message = CreateMessage::new().add_embed(error);
+        check_msg(msg.channel_id.send_message(&ctx.http, message).await);
+        return Ok(());
+    }
+
+    let embed = match subcommand.as_deref() {
+        Ok(subcommand @ ("add" | "replace")) => {
+            let (Ok(name), Ok(url)) = (args.single::<String>(), args.single::<String>()) else {
+                let error = EmbedBuilder::error()
+                    .title("!radio")
+                    .description(format!(
+                        "Expected station name and URL, as in `!radio {subcommand} <name> <url>`"
+                    ))
+                    .build(&style);
+
+                let message = CreateMessage::new().add_embed(error);
+                check_msg(msg.channel_id.send_message(&ctx.http, message).await);
+                return Ok(());
+            };
+
+            let allow_private_hosts = get_config(ctx).await.allow_private_hosts;
+            if !url.starts_with("http") {
+                EmbedBuilder::error()
+                    .title("!radio")
+                    .description(format!("Invalid station URL {url}"))
+                    .build(&style)
+            } else if !allow_private_hosts && stream::is_private_url(&url).await {
+                EmbedBuilder::error()
+                    .title("!radio")
+                    .description(format!(
+                        "Station URL {url} points to a private network host"
+                    ))
+                    .build(&style)
+            } else {
+                let station = Station { name, url };
+                let description = format!("Station **{}** saved", station.name);
+                let replace = subcommand == "replace";
+                match radio_store.add(guild_id.get(), station, replace).await {
+                    Ok(_) => EmbedBuilder::new()
+                        .title("!radio")
+                        .description(description)
+                        .build(&style),
+                    Err(err) => {
+                        tracing::error!("Failed saving radio station: {err}");
+                        EmbedBuilder::error()
+                            .title("!radio")
+                            .description(format!("Could not save station: {err}"))
+                            .build(&style)
+                    }
+                }
+            }
+        }
+        Ok("list") => {
+            let stations = radio_store.list(guild_id.get()).await;
+            let description = if stations.is_empty() {
+                String::from("No saved stations. Use `!radio add <name> <url>` to save one")
+            } else {
+                stations
+                    .iter()
+                    .map(|station| format!("**{}**: {}\n", station.name, station.url))
+                    .collect()
+            };
+
+            EmbedBuilder::new()
+                .title("!radio")
+                .description(description)
+                .build(&style)
+        }
+        Ok("remove") => {
+            let name = args.single::<String>().unwrap_or_default();
+            match radio_store.remove(guild_id.get(), &name).await {
+                Ok(Some(station)) => EmbedBuilder::new()
+                    .title("!radio")
+                    .description(format!("Station **{}** removed", station.name))
+                    .build(&style),
+                Ok(None) => EmbedBuilder::error()
+                    .title("!radio")
+                    .description(format!("No station named {name}"))
+                    .build(&style),
+                Err(err) => {
+                    tracing::error!("Failed removing radio station: {err}");
+                    EmbedBuilder::error()
+                        .title("!radio")
+                        .description("Could not remove station")
+                        .build(&style)
+                }
+            }
+        }
+        Ok(name) => {
+            let Some(station) = radio_store.get(guild_id.get(), name).await else {
+                let error = EmbedBuilder::error()
+                    .title("!radio")
+                    .description(format!("No station named {name}. See `!radio list`"))
+                    .build(&style);
+
+                let message = CreateMessage::new().add_embed(error);
+                check_msg(msg.channel_id.send_message(&ctx.http, message).await);
+                return Ok(());
+            };
+
+            let voice_lock = match VoiceCommandContext::join(ctx, msg).await {
+                Ok(voice) => voice.call,
+                Err(err) => return err.reply(ctx, msg, "!radio").await,
+            };
+
+            let mut voice = voice_lock.lock().await;
+            let mut quota = user_quota(ctx, guild_id, voice.queue(), msg.author.id).await;
+            if let Err(rejection) = quota.admit(None, true) {
+                std::mem::drop(voice);
+
+                let rejected = format!("**{}**: {rejection}", station.name);
+                let message = CreateMessage::new()
+                    .add_embed(resolve::rejection("!radio", &[rejected]).build(&style));
+                check_msg(msg.channel_id.send_message(&ctx.http, message).await);
+                return Ok(());
+            }
+
+            let http_client = get_http_client(ctx).await;
+            enqueue_radio(
+                &mut voice,
+                &voice_lock,
+                http_client,
+                &station,
+                0,
+                msg.author.id,
+            )
+            .await;
+            std::mem::drop(voice);
+
+            EmbedBuilder::new()
+                .title("!radio")
+                .description(format!("Station **{}** added to queue", station.name))
+                .build(&style)
+        }
+        Err(_) => EmbedBuilder::error()
+            .title("!radio")
+            .description("Missing station name argument")
+            .build(&style),
+    };
+
+    let message = CreateMessage::new().add_embed(embed);
+    check_msg(msg.channel_id.send_message(&ctx.http, message).await);
+
+    Ok(())
+}
+
+#[command]
+#[only_in(guilds)]
+#[sub_commands(playlist_load)]
+#[bucket = "playlist"]
+async fn playlist(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let style = get_embed_style(ctx).await;
+    let guild_id = msg.guild_id.expect("Expected guild to be defined");
+    let playlist_store = get_playlist_store(ctx).await;
+
+    let subcommand = args.single::<String>().ok();
+    let name = args.single::<String>().ok();
+
+    let embed = match (subcommand.as_deref(), name) {
+        (Some("list"), _) => {
+            let playlists = playlist_store.list(guild_id.get()).await;
+            let description = if playlists.is_empty() {
+                String::from("No saved playlists. Use `!playlist save <name>` to save the queue")
+            } else {
+                playlists
+                    .iter()
+                    .map(|playlist| {
+                        format!(
+                            "**{}**: {} tracks, by {}\n",
+                            playlist.name,
+                            playlist.tracks.len(),
+                            serenity::model::id::UserId::new(playlist.owner).mention()
+                        )
+                    })
+                    .collect()
+            };
+
+            EmbedBuilder::new()
+                .title("!playlist")
+                .description(description)
+                .build(&style)
+        }
+        (Some("save"), Some(name)) => {
+            let manager = songbird::get(ctx)
+                .await
+                .expect("Expected songbird in context");
+
+            let tracks = match manager.get(guild_id) {
+                Some(voice_lock) => voice_lock.lock().await.queue().current_queue(),
+                None => Vec::new(),
+            };
+
+            let mut saved = Vec::with_capacity(tracks.len());
+            for track in &tracks {
+                let typemap = track.typemap().read().await;
+                if typemap.get::<TrackSourceKey>() != Some(&TrackSource::YtDlp) {
+                    continue;
+                }
+
+                if let (Some(title), Some(url)) =
+                    (typemap.get::<TrackTitleKey>(), typemap.get::<TrackUrlKey>())
+                {
+                    saved.push(playlist::Metadata {
+                        url: url.to_string(),
+                        title: title.to_string(),
+                        duration: None,
+                        live: false,
+                        uploader: None,
+                        thumbnail: None,
+                    });
+                }
+            }
+
+            if saved.is_empty() {
+                EmbedBuilder::error()
+                    .title("!playlist")
+                    .description("Queue has no tracks that can be saved")
+                    .build(&style)
+            } else {
+                let count = saved.len().min(saved_playlist::MAX_TRACKS);
+                match playlist_store
+                    .save(guild_id.get(), msg.author.id.get(), &name, saved)
+                    .await
+                {
+                    Ok(_) => EmbedBuilder::new()
+                        .title("!playlist")
+                        .description(format!("Playlist **{name}** saved with {count} tracks"))
+                        .build(&style),
+                    Err(err) => EmbedBuilder::error()
+                        .title("!playlist")
+                        .description(format!("Could not save playlist: {err}"))
+                        .build(&style),
+                }
+            }
+        }
+        (Some("add"), Some(name)) => {
+            let query = args.rest().trim();
+            let resolved = if query.is_empty() {
+                Err(String::from(
+                    "Expected playlist name and track, as in `!playlist add <name> <url>`",
+                ))
+            } else {
+                resolve(ctx, query)
+                    .await
+                    .map_err(|err| err.description(query))
+            };
+
+            let tracks = match resolved {
+                Ok(Resolved::Track(metadata)) => Ok(vec![metadata]),
+                Ok(Resolved::Playlist(tracks)) => Ok(tracks),
+                Ok(Resolved::Stream { .. }) => Err(String::from(
+                    "Live streams cannot be saved in playlists, use `!radio add` instead",
+                )),
+                Err(err) => Err(err),
+            };
+
+            match tracks {
+                Ok(tracks) => {
+                    let added = tracks.len();
+                    match playlist_store
+                        .add(guild_id.get(), msg.author.id.get(), &name, tracks)
+                        .await
+                    {
+                        Ok(total) => EmbedBuilder::new()
+                            .title("!playlist")
+                            .description(format!(
+                                "{added} tracks added to playlist **{name}**, now with {total} tracks"
+                            ))
+                            .build(&style),
+                        Err(err) => EmbedBuilder::error()
+                            .title("!playlist")
+                            .description(format!("Could not update playlist: {err}"))
+                            .build(&style),
+                    }
+                }
+                Err(err) => EmbedBuilder::error()
+                    .title("!playlist")
+                    .description(err)
+                    .build(&style),
+            }
+        }
+        (Some("delete"), Some(name)) => {
+            match playlist_store
+                .delete(guild_id.get(), msg.author.id.get(), &name)
+                .await
+            {
+                Ok(Some(playlist)) => EmbedBuilder::new()
+                    .title("!playlist")
+                    .description(format!("Playlist **{}** deleted", playlist.name))
+                    .build(&style),
+                Ok(None) => EmbedBuilder::error()
+                    .title("!playlist")
+                    .description(format!("No playlist named {name}"))
+                    .build(&style),
+                Err(err) => EmbedBuilder::error()
+                    .title("!playlist")
+                    .description(format!("Could not delete playlist: {err}"))
+                    .build(&style),
+            }
+        }
+        (Some("save" | "add" | "delete"), None) => EmbedBuilder::error()
+            .title("!playlist")
+            .description("Missing playlist name argument")
+            .build(&style),
+        _ => EmbedBuilder::error()
+            .title("!playlist")
+            .description("Expected one of **save**, **add**, **load**, **list** or **delete**")
+            .build(&style),
+    };
+
+    let message = CreateMessage::new().add_embed(embed);
+    check_msg(msg.channel_id.send_message(&ctx.http, message).await);
+
+    Ok(())
+}
+
+/// Maximum amount of entries imported from a single playlist file.
+const MAX_IMPORT_ENTRIES: usize = 200;
+
+/// Maximum size, in bytes, of an imported playlist file.
+const MAX_PLAYLIST_FILE_SIZE: u32 = 1024 * 1024;
+
+/// Amount of playlist file entries resolved at the same time.
+const IMPORT_CONCURRENCY: usize = 4;
+
+// split from `!playlist`, so that only enqueueing saved playlists has a stricter cooldown
+#[command("load")]
+#[only_in(guilds)]
+#[bucket = "playlist-load"]
+async fn playlist_load(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let style = get_embed_style(ctx).await;
+    let guild_id = msg.guild_id.expect("Expected guild to be defined");
+    let playlist_store = get_playlist_store(ctx).await;
+
+    let Ok(name) = args.single::<String>() else {
+        let error = EmbedBuilder::error()
+            .title("!playlist")
+            .description("Missing playlist name argument")
+            .build(&style);
+
+        let message = CreateMessage::new().add_embed(error);
+        check_msg(msg.channel_id.send_message(&ctx.http, message).await);
+        return Ok(());
+    };
+
+    let Some(saved) = playlist_store.get(guild_id.get(), &name).await else {
+        let error = EmbedBuilder::error()
+            .title("!playlist")
+            .description(format!("No playlist named {name}. See `!playlist list`"))
+            .build(&style);
+
+        let message = CreateMessage::new().add_embed(error);
+        check_msg(msg.channel_id.send_message(&ctx.http, message).await);
+        return Ok(());
+    };
+
+    let voice_lock = match VoiceCommandContext::join(ctx, msg).await {
+        Ok(voice) => voice.call,
+        Err(err) => return err.reply(ctx, msg, "!playlist").await,
+    };
+
+    let mut voice = voice_lock.lock().await;
+    let mut quota = user_quota(ctx, guild_id, voice.queue(), msg.author.id).await;
+    let (admitted, rejected) = resolve::admit(&mut quota, Resolved::Playlist(saved.tracks));
+
+    let enqueued = match &admitted {
+        Some(Resolved::Playlist(tracks)) => tracks.len(),
+        _ => 0,
+    };
+
+    if let Some(admitted) = admitted {
+        enqueue_resolved(ctx, &mut voice, admitted, msg.author.id).await;
+    }
+
+    apply_fair_queue(ctx, guild_id, voice.queue()).await;
+    prefetch(ctx, voice.queue()).await;
+    std::mem::drop(voice);
+
+    let mut description = format!(
+        "{enqueued} tracks from playlist **{}** added to queue",
+        saved.name
+    );
+    render::list(&mut description, "Rejected", "tracks", &rejected);
+
+    let embed = match enqueued {
+        0 => EmbedBuilder::error(),
+        _ => EmbedBuilder::new(),
+    };
+
+    let embed = embed
+        .title("!playlist")
+        .description(description)
+        .build(&style);
+    let message = CreateMessage::new().add_embed(embed);
+    check_msg(msg.channel_id.send_message(&ctx.http, message).await);
+
+    Ok(())
+}
+
+#[command]
+#[only_in(guilds)]
+#[bucket = "import"]
+async fn import(ctx: &Context, msg: &Message) -> CommandResult {
+    let style = get_embed_style(ctx).await;
+    let attachment = msg.attachments.iter().find(|attachment| {
+        let filename = attachment.filename.to_lowercase();
+        playlist_file::EXTENSIONS
+            .iter()
+            .any(|ext| filename.ends_with(&format!(".{ext}")))
+    });
+
+    let Some(attachment) = attachment else {
+        let error = EmbedBuilder::error()
+            .title("!import")
+            .description("Missing playlist file. Upload a M3U, M3U8, PLS or XSPF file along with the command")
+            .build(&style);
+
+        let message = CreateMessage::new().add_embed(error);
+        check_msg(msg.channel_id.send_message(&ctx.http, message).await);
+        return Ok(());
+    };
+
+    if attachment.size > MAX_PLAYLIST_FILE_SIZE {
+        let error = EmbedBuilder::error()
+            .title("!import")
+            .description("Playlist file is too large")
+            .build(&style);
+
+        let message = CreateMessage::new().add_embed(error);
+        check_msg(msg.channel_id.send_message(&ctx.http, message).await);
+        return Ok(());
+    }
+
+    let content = match attachment.download().await {
+        Ok(content) => String::from_utf8_lossy(&content).into_owned(),
+        Err(err) => {
+            tracing::error!("Failed downloading playlist file: {err:?}");
+
+            let error = EmbedBuilder::error()
+                .title("!import")
+                .description("Could not download playlist file")
+                .build(&style);
+
+            let message = CreateMessage::new().add_embed(error);
+            check_msg(msg.channel_id.send_message(&ctx.http, message).await);
+            return Ok(());
+        }
+    };
+
+    let format = playlist_file::Format::detect(&attachment.filename, &content);
+    let mut entries = match playlist_file::parse(format, &content) {
+        Ok(entries) => entries,
+        Err(err) => {
+            let error = EmbedBuilder::error()
+                .title("!import")
+                .description(format!("Could not read playlist file: {err}"))
+                .build(&style);
+
+            let message = CreateMessage::new().add_embed(error);
+            check_msg(msg.channel_id.send_message(&ctx.http, message).await);
+            return Ok(());
+        }
+    };
+
+    let voice_lock = match VoiceCommandContext::join(ctx, msg).await {
+        Ok(voice) => voice.call,
+        Err(err) => return err.reply(ctx, msg, "!import").await,
+    };
+
+    let skipped = entries.len().saturating_sub(MAX_IMPORT_ENTRIES);
+    entries.truncate(MAX_IMPORT_ENTRIES);
+
+    let embed = EmbedBuilder::new()
+        .title("!import")
+        .description(format!(
+            "Importing {} entries from {}",
+            entries.len(),
+            attachment.filename
+        ))
+        .build(&style);
+
+    let message = CreateMessage::new().add_embed(embed);
+    check_msg(msg.channel_id.send_message(&ctx.http, message).await);
+
+    let mut resolved = Vec::with_capacity(entries.len());
+    let mut failures = Vec::new();
+    for chunk in entries.chunks(IMPORT_CONCURRENCY) {
+        let mut tasks = tokio::task::JoinSet::new();
+        for (idx, entry) in chunk.iter().cloned().enumerate() {
+            let ctx = ctx.clone();
+            tasks.spawn(async move {
+                let result = match entry.query() {
+                    Some(query) => resolve(&ctx, &query)
+                        .await
+                        .map_err(|err| err.description(&query)),
+                    None => Err(String::from("Entry has no location or title")),
+                };
+
+                (idx, entry, result)
+            });
+        }
+
+        let mut results = Vec::with_capacity(chunk.len());
+        while let Some(result) = tasks.join_next().await {
+            match result {
+                Ok(result) => results.push(result),
+                Err(err) => tracing::error!("Failed resolving playlist file entry: {err}"),
+            }
+        }
+
+        results.sort_by_key(|(idx, _, _)| *idx);
+        for (_, entry, result) in results {
+            match result {
+                Ok(track) => resolved.push(track),
+                Err(err) => failures.push(format!("{}: {err}", entry.label())),
+            }
+        }
+    }
+
+    let guild_id = msg.guild_id.expect("Expected guild to be defined");
+    let mut voice = voice_lock.lock().await;
+    let mut quota = user_quota(ctx, guild_id, voice.queue(), msg.author.id).await;
+    let mut rejected = Vec::new();
+    let mut enqueued = 0;
+    for track in resolved {
+        let (admitted, rejections) = resolve::admit(&mut quota, track);
+        rejected.extend(rejections);
+
+        let Some(track) = admitted else {
+            continue;
+        };
+
+        enqueued += match &track {
+            Resolved::Playlist(tracks) => tracks.len(),
+            _ => 1,
+        };
+
+        enqueue_resolved(ctx, &mut voice, track, msg.author.id).await;
+    }
+
+    apply_fair_queue(ctx, guild_id, voice.queue()).await;
+    prefetch(ctx, voice.queue()).await;
+    std::mem::drop(voice);
+
+    let mut description = format!("{enqueued} tracks added to the queue");
+    render::list(&mut description, "Failed importing", "entries", &failures);
+    render::list(&mut description, "Rejected", "tracks", &rejected);
+
+    if skipped > 0 {
+        description.push_str(&format!(
+            "\nOnly first {MAX_IMPORT_ENTRIES} entries were imported, {skipped} were ignored"
+        ));
+    }
+
+    let embed = EmbedBuilder::new()
+        .title("!import")
+        .description(description)
+        .build(&style);
+
+    let message = CreateMessage::new().add_embed(embed);
+    check_msg(msg.channel_id.send_message(&ctx.http, message).await);
+
+    Ok(())
+}
+
+#[command]
+#[only_in(guilds)]
+#[bucket = "export"]
+async fn export(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let style = get_embed_style(ctx).await;
+    let format = match args.single::<String>() {
+        Ok(format) => format.to_lowercase(),
+        Err(_) => String::from("json"),
+    };
+
+    if format != "json" && format != "m3u" {
+        let error = EmbedBuilder::error()
+            .title("!export")
+            .description(format!(
+                "Unknown export format {format}, expected json or m3u"
+            ))
+            .build(&style);
+
+        let message = CreateMessage::new().add_embed(error);
+        check_msg(msg.channel_id.send_message(&ctx.http, message).await);
+        return Ok(());
+    }
+
+    let VoiceCommandContext {
+        guild_id,
+        call: voice_lock,
+        ..
+    } = match VoiceCommandContext::current(ctx, msg).await {
+        Ok(voice) => voice,
+        Err(err) => return err.reply(ctx, msg, "!export").await,
+    };
+
+    let tracks = voice_lock.lock().await.queue().current_queue();
+    if tracks.is_empty() {
+        let embed = EmbedBuilder::new()
+            .title("!export")
+            .description("Queue is curently empty")
+            .build(&style);
+
+        let message = CreateMessage::new().add_embed(embed);
+        check_msg(msg.channel_id.send_message(&ctx.http, message).await);
+        return Ok(());
+    }
+
+    let mut exported = Vec::with_capacity(tracks.len());
+    for track in &tracks {
+        let typemap = track.typemap().read().await;
+        let (Some(title), Some(url)) =
+            (typemap.get::<TrackTitleKey>(), typemap.get::<TrackUrlKey>())
+        else {
+            continue;
+        };
+
+        exported.push(playlist::Metadata {
+            url: url.to_string(),
+            title: title.to_string(),
+            duration: None,
+            live: false,
+            uploader: None,
+            thumbnail: None,
+        });
+    }
+
+    let content = if format == "json" {
+        serde_json::to_vec_pretty(&exported)?
+    } else {
+        let entries = exported
+            .iter()
+            .map(|track| playlist_file::Entry {
+                location: Some(track.url.clone()),
+                title: Some(track.title.clone()),
+                duration: None,
+            })
+            .collect::<Vec<_>>();
+
+        playlist_file::to_m3u(&entries).into_bytes()
+    };
+
+    let embed = EmbedBuilder::new()
+        .title("!export")
+        .description(format!("Exported {} tracks from the queue", exported.len()))
+        .build(&style);
+
+    let attachment = CreateAttachment::bytes(content, format!("queue-{guild_id}.{format}"));
+    let message = CreateMessage::new().add_embed(embed).add_file(attachment);
+    check_msg(msg.channel_id.send_message(&ctx.http, message).await);
+
+    Ok(())
+}
+
+#[command]
+#[only_in(guilds)]
+#[required_permissions("MANAGE_GUILD")]
+#[bucket = "settings"]
+async fn settings(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let style = get_embed_style(ctx).await;
+    let guild_id = msg.guild_id.expect("Expected guild to be defined");
+    let settings_store = get_settings_store(ctx).await;
+
+    let subcommand = args.single::<String>().ok();
+    let setting = match args.single::<String>() {
+        Ok(name) => match Setting::from_name(&name) {
+            Some(setting) => Some(setting),
+            None => {
+                let names = Setting::ALL.map(|setting| setting.name()).join(", ");
+                let error = EmbedBuilder::error()
+                    .title("!settings")
+                    .description(format!(
+                        "Unknown setting {name}. Available settings: {names}"
+                    ))
+                    .build(&style);
+
+                let message = CreateMessage::new().add_embed(error);
+                check_msg(msg.channel_id.send_message(&ctx.http, message).await);
+                return Ok(());
+            }
+        },
+        Err(_) => None,
+    };
+
+    let embed = match (subcommand.as_deref(), setting) {
+        (None | Some("get"), setting) => {
+            let settings = settings_store.get(guild_id.get()).await;
+            let description = setting
+                .map_or(Setting::ALL.to_vec(), |setting| vec![setting])
+                .into_iter()
+                .map(|setting| {
+                    let value = settings
+                        .get(setting)
+                        .unwrap_or_else(|| String::from("*default*"));
+
+                    format!("**{}**: {value}\n", setting.name())
+                })
+                .collect::<String>();
+
+            EmbedBuilder::new()
+                .title("!settings")
+                .description(description)
+                .build(&style)
+        }
+        (Some("set"), Some(setting)) => {
+            let value = args.rest().trim();
+            match settings_store
+                .update(guild_id.get(), |settings| settings.set(setting, value))
+                .await
+            {
+                Ok(Ok(())) => EmbedBuilder::new()
+                    .title("!settings")
+                    .description(format!("Setting **{}** changed to {value}", setting.name()))
+                    .build(&style),
+                Ok(Err(err)) => EmbedBuilder::error()
+                    .title("!settings")
+                    .description(err.to_string())
+                    .build(&style),
+                Err(err) => {
+                    tracing::error!("Failed saving guild settings: {err}");
+                    EmbedBuilder::error()
+                        .title("!settings")
+                        .description("Could not save settings")
+                        .build(&style)
+                }
+            }
+        }
+        (Some("reset"), setting) => {
+            let result = settings_store
+                .update(guild_id.get(), |settings| match setting {
+                    Some(setting) => settings.reset(setting),
+                    None => *settings = Default::default(),
+                })
+                .await;
+
+            let description = match setting {
+                Some(setting) => format!("Setting **{}** restored to default", setting.name()),
+                None => String::from("All settings restored to default"),
+            };
+
+            match result {
+                Ok(_) => EmbedBuilder::new()
+                    .title("!settings")
+                    .description(description)
+                    .build(&style),
+                Err(err) => {
+                    tracing::error!("Failed saving guild settings: {err}");
+                    EmbedBuilder::error()
+                        .title("!settings")
+                        .description("Could not save settings")
+                        .build(&style)
+                }
+            }
+        }
+        (Some("set"), None) => EmbedBuilder::error()
+            .title("!settings")
+            .description("Expected setting name and value, as in `!settings set prefix ?`")
+            .build(&style),
+        _ => EmbedBuilder::error()
+            .title("!settings")
+            .description("Expected one of **get**, **set** or **reset**")
+            .build(&style),
+    };
+
+    let message = CreateMessage::new().add_embed(embed);
+    check_msg(msg.channel_id.send_message(&ctx.http, message).await);
+
+    Ok(())
+}
+
+#[command]
+#[owners_only]
+#[bucket = "cache"]
+async fn cache(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let style = get_embed_style(ctx).await;
+    let metadata_cache = get_metadata_cache(ctx).await;
+
+    let embed = match args.single::<String>().as_deref() {
+        Ok("stats") => {
+            let stats = metadata_cache.stats();
+            let lookups = stats.hits + stats.misses;
+            let hit_rate = if lookups > 0 {
+                stats.hits as f64 * 100.0 / lookups as f64
+            } else {
+                0.0
+            };
+
+            let ttl_hours = metadata_cache.ttl().as_secs() / 3600;
+            let description = format!(
+                "Entries: **{}**\nCached tracks: **{}/{}**\nHits: **{}**\nMisses: **{}**\nHit rate: **{hit_rate:.1}%**\nEntries expire after **{ttl_hours}h**",
+                stats.entries, stats.tracks, stats.capacity, stats.hits, stats.misses
+            );
+
+            EmbedBuilder::new()
+                .title("!cache")
+                .description(description)
+                .build(&style)
+        }
+        Ok("clear") => {
+            metadata_cache.clear();
+
+            EmbedBuilder::new()
+                .title("!cache")
+                .description("Metadata cache cleared")
+                .build(&style)
+        }
+        _ => EmbedBuilder::error()
+            .title("!cache")
+            .description("Expected either `stats` or `clear` argument")
+            .build(&style),
+    };
+
+    let message = CreateMessage::new().add_embed(embed);
+    check_msg(msg.channel_id.send_message(&ctx.http, message).await);
+
+    Ok(())
+}
+
+#[command]
+#[only_in(guilds)]
+#[bucket = "help"]
+async fn help(ctx: &Context, msg: &Message) -> CommandResult {
+    let style = get_embed_style(ctx).await;
+    let config = get_config(ctx).await;
+    let prefix = command_prefix(ctx, msg.guild_id).await;
+    let (p, name) = (prefix.as_str(), style.author_name.as_str());
+    let fields = vec![
+        EmbedField::new(format!("{p}help"), "Explains all available commands"),
+        EmbedField::new(format!("{p}join"), format!("Call **{name}** to join your current voice channel")),
+        EmbedField::new(format!("{p}mute"), format!("Mutes **{name}**. Beware, if playing a track, no sound will come out. See **{p}unmute** to unmute **{name}**")),
+        EmbedField::new(format!("{p}play"), "Play or enqueue a track. Must provide the track name, source **URL**, direct audio or radio stream **URL**, or upload audio files along with the command"),
+        EmbedField::new(format!("{p}skip"), format!("Skip track. Accepts an optional parameter to define amount of tracks to skip (max of {}). When the server enables **vote_skip**, members who are not DJs vote to skip the playing track instead", config.skip_limit)),
+        EmbedField::new(format!("{p}stop"), format!("Stop **{name}** if playing a track and clears all enqueued tracks")),
+        EmbedField::new(format!("{p}unmute"), format!("Unmute **{name}**. See **{p}mute** to mute **{name}**")),
+        EmbedField::new(format!("{p}queue"), format!("List first {} enqueued tracks and who requested them. There is currently no way to list all enqueue tracks", config.queue_display_limit)),
+        EmbedField::new(format!("{p}remove"), format!("Remove the track at the given position of **{p}queue**")),
+        EmbedField::new(format!("{p}now"), "Show playing track title. For radio streams, also shows the song currently on air"),
+        EmbedField::new(format!("{p}local"), "Play or enqueue the local library track best matching the query"),
+        EmbedField::new(format!("{p}album"), "Play or enqueue all tracks of a local library album, in order"),
+        EmbedField::new(format!("{p}radio"), "Play a saved radio station by name. Use **list** to see this server stations. DJs and server managers can **add <name> <url>**, **replace <name> <url>** or **remove <name>** them"),
+        EmbedField::new(format!("{p}playlist"), "Play a saved playlist by name with **load <name>**. Use **save <name>** to save the queue, **add <name> <track>**, **list** or **delete <name>** to manage this server playlists"),
+        EmbedField::new(format!("{p}import"), "Enqueue tracks from an uploaded M3U, M3U8, PLS or XSPF playlist file"),
+        EmbedField::new(format!("{p}export [json|m3u]"), "Upload the current queue as a JSON or M3U file"),
+        EmbedField::new(format!("{p}resume-session"), format!("Enqueue again the tracks left behind when **{name}** left a voice channel everybody had left")),
+        EmbedField::new(format!("{p}settings"), "Show this server settings with **get**, change them with **set <name> <value>** or restore defaults with **reset [name]**. Requires the Manage Server permission"),
+        EmbedField::new("DJ role", format!("Once a server sets **dj_role**, only DJs and server managers can use **{p}leave**, **{p}mute**, **{p}unmute**, **{p}stop**, **{p}skip** and **{p}remove**. Anyone can still skip or remove tracks they requested")),
+        EmbedField::new(format!("{p}cache"), "Show track metadata cache statistics with **stats** or empty it with **clear**. Restricted to bot owners"),
+    ];
+
+    let embed = EmbedBuilder::new()
+        .title(format!("{p}help"))
+        .description("Available commands")
+        .fields(fields)
+        .build(&style);
+
+    let message = CreateMessage::new().embed(embed);
+    check_msg(msg.channel_id.send_message(&ctx.http, message).await);
+
+    Ok(())
+}
+
+/// Resolves a `!play` query with the bot HTTP client, `yt-dlp` settings and metadata cache.
+async fn resolve(ctx: &Context, query: &str) -> Result<Resolved, ResolveError> {
+    let http_client = get_http_client(ctx).await;
+    let ytdlp_config = get_ytdlp_config(ctx).await;
+    let metadata_cache = get_metadata_cache(ctx).await;
+
+    let allow_private_hosts = get_config(ctx).await.allow_private_hosts;
+    resolve::resolve(
+        &http_client,
+        &ytdlp_config,
+        &metadata_cache,
+        allow_private_hosts,
+        query,
+    )
+    .await
+}
+
+async fn enqueue_resolved(ctx: &Context, call: &mut Call, resolved: Resolved, requester: UserId) {
+    match resolved {
+        Resolved::Stream { url, title, icy } => {
+            let http_client = get_http_client(ctx).await;
+            let src = HttpRequest::new(http_client.clone(), url.clone()).into();
+            enqueue_stream(call, src, http_client, &url, &title, icy, requester).await;
+        }
+        Resolved::Playlist(tracks) => {
+            for metadata in tracks.into_iter() {
+                enqueue_ytdlp(ctx, call, metadata, requester).await;
+            }
+        }
+        Resolved::Track(metadata) => {
+            enqueue_ytdlp(ctx, call, metadata, requester).await;
+        }
+    }
+}
+
+/// Enqueues a track streamed through `yt-dlp`, storing its title and source in the track typemap.
+async fn enqueue_ytdlp(
+    ctx: &Context,
+    call: &mut Call,
+    metadata: playlist::Metadata,
+    requester: UserId,
+) -> TrackHandle {
+    let src = YtDlp::new(
+        get_http_client(ctx).await,
+        get_ytdlp_config(ctx).await,
+        metadata.url.clone(),
+    )
+    .with_metadata(metadata.aux_metadata())
+    .with_audio_cache(get_audio_cache(ctx).await);
+
+    let track_handle = call.enqueue_with_preload(Track::from(src), None);
+    let mut typemap = track_handle.typemap().write().await;
+    typemap.insert::<TrackTitleKey>(metadata.title.into());
+    typemap.insert::<TrackSourceKey>(TrackSource::YtDlp);
+    typemap.insert::<TrackUrlKey>(metadata.url.into());
+    typemap.insert::<TrackRequesterKey>(requester);
+    std::mem::drop(typemap);
+
+    track_handle
+}
+
+/// Enqueues a file from the local library, storing its metadata in the track typemap.
+async fn enqueue_local(
+    call: &mut Call,
+    path: PathBuf,
+    title: &str,
+    requester: UserId,
+) -> TrackHandle {
+    let track_handle = call.enqueue_with_preload(Track::from(File::new(path.clone())), None);
+
+    let mut typemap = track_handle.typemap().write().await;
+    typemap.insert::<TrackTitleKey>(title.into());
+    typemap.insert::<TrackSourceKey>(TrackSource::Local);
+    typemap.insert::<TrackUrlKey>(path.to_string_lossy().into());
+    typemap.insert::<TrackRequesterKey>(requester);
+    std::mem::drop(typemap);
+
+    track_handle
+}
+
+/// Enqueues a file uploaded to Discord, using its file name as title.
+async fn enqueue_attachment(
+    call: &mut Call,
+    http_client: HttpClient,
+    url: &str,
+    filename: &str,
+    size: Option<u64>,
+    requester: UserId,
+) -> TrackHandle {
+    let mut src = HttpRequest::new(http_client, url.to_string());
+    src.content_length = size;
+
+    let track_handle = call.enqueue_with_preload(Track::from(src), None);
+    let mut typemap = track_handle.typemap().write().await;
+    typemap.insert::<TrackTitleKey>(filename.into());
+    typemap.insert::<TrackSourceKey>(TrackSource::Attachment);
+    typemap.insert::<TrackUrlKey>(url.into());
+    typemap.insert::<TrackRequesterKey>(requester);
+    std::mem::drop(typemap);
+
+    track_handle
+}
+
+/// Enqueues a direct audio stream, watching its ICY metadata while playing when `icy` is set.
+async fn enqueue_stream(
+    call: &mut Call,
+    src: Input,
+    http_client: HttpClient,
+    url: &str,
+    title: &str,
+    icy: bool,
+    requester: UserId,
+) -> TrackHandle {
+    let track_handle = call.enqueue_with_preload(Track::from(src), None);
+
+    let mut typemap = track_handle.typemap().write().await;
+    typemap.insert::<TrackTitleKey>(title.into());
+    typemap.insert::<TrackSourceKey>(TrackSource::Stream);
+    typemap.insert::<TrackUrlKey>(url.into());
+    typemap.insert::<TrackRequesterKey>(requester);
+
+    if icy {
+        let watcher = Arc::new(TitleWatcher::new(http_client, url.to_string()));
+        let events = [(TrackEvent::Play, true), (TrackEvent::End, false)];
+        for (event, start) in events {
+            let handler = TitleWatcherHandler {
+                watcher: watcher.clone(),
+                start,
+            };
+
+            if let Err(err) = track_handle.add_event(Event::Track(event), handler) {
+                tracing::error!("Failed watching stream title: {err}");
+            }
+        }
+
+        typemap.insert::<StreamTitleKey>(watcher);
+    }
+
+    std::mem::drop(typemap);
+    track_handle
+}
+
+/// Enqueues a radio station preset, reconnecting whenever its stream drops.
+///
+/// `attempt` is the amount of consecutive reconnections made, delaying the connection
+/// the more attempts were made.
+async fn enqueue_radio(
+    call: &mut Call,
+    call_lock: &Arc<Mutex<Call>>,
+    http_client: HttpClient,
+    station: &Station,
+    attempt: u32,
+    requester: UserId,
+) -> TrackHandle {
+    let delay = Duration::from_secs(2u64.pow(attempt.min(6)) - 1);
+    let src = DelayedRequest::new(http_client.clone(), station.url.clone(), delay).into();
+    let track_handle = enqueue_stream(
+        call,
+        src,
+        http_client.clone(),
+        &station.url,
+        &station.name,
+        true,
+        requester,
+    )
+    .await;
+
+    let handler = RadioReconnectHandler {
+        call: Arc::downgrade(call_lock),
+        http_client,
+        station: station.clone(),
+        attempt,
+        requester,
+    };
+
+    if let Err(err) = track_handle.add_event(Event::Track(TrackEvent::End), handler) {
+        tracing::error!("Failed watching radio stream drops: {err}");
+    }
+
+    track_handle
+}
+
+async fn register_call_events(ctx: &Context, guild_id: GuildId, voice_lock: &Arc<Mutex<Call>>) {
+    let settings = get_settings_store(ctx).await;
+    let audio_cache = get_audio_cache(ctx).await;
+
+    let mut voice = voice_lock.lock().await;
+    let handler = DefaultVolumeHandler { settings, guild_id };
+    voice.add_global_event(Event::Track(TrackEvent::Play), handler);
+
+    for event in [TrackEvent::Play, TrackEvent::Pause, TrackEvent::End] {
+        let handler = IdleHandler {
+            ctx: ctx.clone(),
+            guild_id,
+            queue: voice.queue().clone(),
+        };
+
+        voice.add_global_event(Event::Track(event), handler);
+    }
+
+    if let Some(audio_cache) = audio_cache {
+        let handler = PrefetchHandler {
+            queue: voice.queue().clone(),
+            audio_cache,
+        };
+
+        voice.add_global_event(Event::Track(TrackEvent::Play), handler);
+    }
+
+    // nothing plays right after joining, until tracks are enqueued
+    let idle = voice.queue().is_empty();
+    std::mem::drop(voice);
+
+    if idle {
+        start_idle_timer(ctx, guild_id).await;
+    }
+}
+
+/// Whether nothing is being played, either because the queue is empty or paused.
+async fn is_idle(queue: &TrackQueue) -> bool {
+    let Some(current) = queue.current() else {
+        return true;
+    };
+
+    match current.get_info().await {
+        Ok(state) => state.playing == PlayMode::Pause,
+        // track already ended
+        Err(_) => true,
+    }
+}
+
+/// Leaves the guild voice channel once nothing plays for its `auto_leave_delay`, or
+/// the configured one.
+async fn start_idle_timer(ctx: &Context, guild_id: GuildId) {
+    let settings = get_settings_store(ctx).await.get(guild_id.get()).await;
+    let delay = settings
+        .auto_leave_delay
+        .map(Duration::from_secs)
+        .unwrap_or(get_config(ctx).await.auto_leave_delay);
+
+    let idle_timers = get_idle_timers(ctx).await;
+    let ctx = ctx.clone();
+    let on_idle = async move { leave_idle(&ctx, guild_id, delay).await };
+    idle_timers.start(guild_id.get(), delay, on_idle);
+}
+
+async fn leave_idle(ctx: &Context, guild_id: GuildId, delay: Duration) {
+    let manager = songbird::get(ctx)
+        .await
+        .expect("Expected songbird in context");
+
+    let Some(voice_lock) = manager.get(guild_id) else {
+        return;
+    };
+
+    let (queue, voice_channel) = {
+        let voice = voice_lock.lock().await;
+        (voice.queue().clone(), voice.current_channel())
+    };
+
+    // tracks enqueued while still loading did not play yet, so did not cancel the timer
+    if !is_idle(&queue).await {
+        return;
+    }
+
+    if let Err(err) = manager.remove(guild_id).await {
+        return tracing::error!("Failed leaving idle voice channel of {guild_id}: {err:?}");
+    }
+
+    get_skip_votes(ctx).await.clear(guild_id.get());
+
+    let channel = voice_channel
+        .map(|channel_id| format!(" {}", ChannelId::new(channel_id.0.get()).mention()))
+        .unwrap_or_default();
+
+    let description = format!(
+        "Left voice channel{channel} after {} without playing anything",
+        format_duration(delay.as_secs())
+    );
+    send_goodbye(ctx, guild_id, description, false).await;
+}
+
+/// Channel where notices not answering a command are sent: the guild `announce_channel`,
+/// or the one the bot was last called from.
+async fn notice_channel(ctx: &Context, guild_id: GuildId) -> Option<ChannelId> {
+    let announce_channel = get_settings_store(ctx)
+        .await
+        .get(guild_id.get())
+        .await
+        .announce_channel;
+
+    match announce_channel {
+        Some(channel_id) => Some(ChannelId::new(channel_id)),
+        None => get_text_channels(ctx)
+            .await
+            .read()
+            .await
+            .get(&guild_id)
+            .copied(),
+    }
+}
+
+/// Pauses playback once everybody left the guild voice channel, leaving it unless someone
+/// comes back within its `grace_period`, or the configured one.
+async fn start_grace_period(ctx: &Context, guild_id: GuildId, voice_lock: &Mutex<Call>) {
+    let style = get_embed_style(ctx).await;
+    let settings = get_settings_store(ctx).await.get(guild_id.get()).await;
+    let delay = settings
+        .grace_period
+        .map(Duration::from_secs)
+        .unwrap_or(get_config(ctx).await.grace_period);
+
+    // started first, so pausing below does not start the idle timer
+    let grace_timers = get_grace_timers(ctx).await;
+    if grace_timers.is_running(guild_id.get()) {
+        return;
+    }
+
+    let leaving_ctx = ctx.clone();
+    let on_empty = async move { leave_empty(&leaving_ctx, guild_id, delay).await };
+    grace_timers.start(guild_id.get(), delay, on_empty);
+    get_idle_timers(ctx).await.cancel(guild_id.get());
+
+    let (queue, voice_channel) = {
+        let voice = voice_lock.lock().await;
+        (voice.queue().clone(), voice.current_channel())
+    };
+
+    // tracks already paused are left for members to resume themselves
+    if is_idle(&queue).await {
+        return;
+    }
+
+    let Some(current) = queue.current() else {
+        return;
+    };
+
+    if let Err(err) = current.pause() {
+        return tracing::error!("Failed pausing track of empty voice channel: {err}");
+    }
+    current.typemap().write().await.insert::<GracePausedKey>(());
+
+    let Some(text_channel) = notice_channel(ctx, guild_id).await else {
+        return;
+    };
+
+    let channel = voice_channel
+        .map(|channel_id| format!(" {}", ChannelId::new(channel_id.0.get()).mention()))
+        .unwrap_or_default();
+
+    let embed = EmbedBuilder::new()
+        .title("Paused")
+        .description(format!(
+            "Everybody left voice channel{channel}, playback resumes once someone joins back within {}",
+            format_duration(delay.as_secs())
+        ))
+        .build(&style);
+
+    let message = CreateMessage::new().add_embed(embed);
+    check_msg(text_channel.send_message(&ctx.http, message).await);
+}
+
+/// Resumes playback paused by [`start_grace_period`] once someone joins `channel_id` back.
+async fn end_grace_period(ctx: &Context, guild_id: GuildId, channel_id: ChannelId) {
+    let grace_timers = get_grace_timers(ctx).await;
+    if !grace_timers.is_running(guild_id.get()) {
+        return;
+    }
+
+    let manager = songbird::get(ctx)
+        .await
+        .expect("Expected songbird in context");
+
+    let Some(voice_lock) = manager.get(guild_id) else {
+        return;
+    };
+
+    let queue = {
+        let voice = voice_lock.lock().await;
+        if voice.current_channel() != Some(channel_id.into()) {
+            return;
+        }
+
+        voice.queue().clone()
+    };
+
+    grace_timers.cancel(guild_id.get());
+
+    let paused = match queue.current() {
+        Some(current) => {
+            let paused = current.typemap().write().await.remove::<GracePausedKey>();
+            paused.map(|_| current)
+        }
+        None => None,
+    };
+
+    // tracks paused before everybody left stay paused
+    let Some(current) = paused else {
+        if is_idle(&queue).await {
+            start_idle_timer(ctx, guild_id).await;
+        }
+        return;
+    };
+
+    if let Err(err) = current.play() {
+        tracing::error!("Failed resuming track after grace period: {err}");
+    }
+}
+
+/// Leaves the guild voice channel nobody came back to.
+async fn leave_empty(ctx: &Context, guild_id: GuildId, delay: Duration) {
+    let manager = songbird::get(ctx)
+        .await
+        .expect("Expected songbird in context");
+
+    let Some(voice_lock) = manager.get(guild_id) else {
+        return;
+    };
+
+    let voice_channel = voice_lock
+        .lock()
+        .await
+        .current_channel()
+        .map(|channel_id| ChannelId::new(channel_id.0.get()));
+
+    let parked = match leave_keeping_queue(ctx, guild_id, &voice_lock, voice_channel).await {
+        Ok(parked) => parked,
+        Err(err) => {
+            return tracing::error!("Failed leaving empty voice channel of {guild_id}: {err:?}")
+        }
+    };
+
+    let channel = voice_channel
+        .map(|channel_id| format!(" {}", channel_id.mention()))
+        .unwrap_or_default();
+
+    let description = format!(
+        "Left voice channel{channel} since nobody joined back within {}",
+        format_duration(delay.as_secs())
+    );
+    send_goodbye(ctx, guild_id, description, parked).await;
+}
+
+/// Drops the call of a guild whose voice channel the bot was disconnected from by a
+/// moderator, which songbird keeps around connected to nothing.
+async fn leave_disconnected(ctx: &Context, guild_id: GuildId, voice_channel: Option<ChannelId>) {
+    let manager = songbird::get(ctx)
+        .await
+        .expect("Expected songbird in context");
+
+    // calls left by the bot itself are already removed
+    let Some(voice_lock) = manager.get(guild_id) else {
+        return;
+    };
+
+    tracing::info!("Disconnected from voice channel of {guild_id}");
+    let parked = match leave_keeping_queue(ctx, guild_id, &voice_lock, voice_channel).await {
+        Ok(parked) => parked,
+        Err(err) => return tracing::error!("Failed dropping call of {guild_id}: {err:?}"),
+    };
+
+    let channel = voice_channel
+        .map(|channel_id| format!(" {}", channel_id.mention()))
+        .unwrap_or_default();
+
+    let description = format!("Disconnected from voice channel{channel}");
+    send_goodbye(ctx, guild_id, description, parked).await;
+}
+
+/// Removes the guild call along with its timers and votes, first keeping its queue for
+/// `!resume-session` unless the guild turned `keep_queue` off. Returns whether the queue
+/// was kept.
+async fn leave_keeping_queue(
+    ctx: &Context,
+    guild_id: GuildId,
+    voice_lock: &Mutex<Call>,
+    voice_channel: Option<ChannelId>,
+) -> Result<bool, JoinError> {
+    let keep_queue = get_settings_store(ctx)
+        .await
+        .get(guild_id.get())
+        .await
+        .keep_queue
+        .unwrap_or(true);
+
+    let mut parked = false;
+    if let Some(voice_channel) = voice_channel.filter(|_| keep_queue) {
+        let text_channel = get_text_channels(ctx)
+            .await
+            .read()
+            .await
+            .get(&guild_id)
+            .copied();
+
+        if let Some(session) = snapshot_session(voice_lock, voice_channel, text_channel).await {
+            match get_session_store(ctx)
+                .await
+                .park(guild_id.get(), session)
+                .await
+            {
+                Ok(()) => parked = true,
+                Err(err) => tracing::error!("Failed keeping session of {guild_id}: {err}"),
+            }
+        }
+    }
+
+    get_idle_timers(ctx).await.cancel(guild_id.get());
+    get_grace_timers(ctx).await.cancel(guild_id.get());
+
+    let manager = songbird::get(ctx)
+        .await
+        .expect("Expected songbird in context");
+    manager.remove(guild_id).await?;
+    get_skip_votes(ctx).await.clear(guild_id.get());
+
+    Ok(parked)
+}
+
+/// Sends a notice about leaving the guild voice channel, telling how to get the queue back
+/// when it was `parked`.
+async fn send_goodbye(ctx: &Context, guild_id: GuildId, mut description: String, parked: bool) {
+    let style = get_embed_style(ctx).await;
+    let Some(text_channel) = notice_channel(ctx, guild_id).await else {
+        return;
+    };
+
+    if parked {
+        description.push_str(". Use **!resume-session** to play the queue again");
+    }
+
+    let embed = EmbedBuilder::new()
+        .title("Goodbye")
         .description(description)
-        .build();
+        .build(&style);
 
     let message = CreateMessage::new().add_embed(embed);
-    check_msg(msg.channel_id.send_message(&ctx.http, message).await);
+    check_msg(text_channel.send_message(&ctx.http, message).await);
+}
 
-    Ok(())
+async fn set_text_channel(ctx: &Context, guild_id: GuildId, channel_id: ChannelId) {
+    let text_channels = get_text_channels(ctx).await;
+    text_channels.write().await.insert(guild_id, channel_id);
 }
 
-#[command]
-#[only_in(guilds)]
-async fn stop(ctx: &Context, msg: &Message, _args: Args) -> CommandResult {
-    let (guild_id, author_channel_id) = {
-        let guild = msg.guild(&ctx.cache).expect("Expected guild to be defined");
-        let channel_id = guild
-            .voice_states
-            .get(&msg.author.id)
-            .and_then(|vs| vs.channel_id);
+/// Captures the playback of every guild with an active call.
+async fn snapshot_sessions(
+    manager: &Songbird,
+    text_channels: &HashMap<GuildId, ChannelId>,
+) -> HashMap<u64, Session> {
+    let mut sessions = HashMap::new();
+    for (guild_id, voice_lock) in manager.iter() {
+        let guild_id = GuildId::new(guild_id.0.get());
+        let Some(voice_channel) = voice_lock.lock().await.current_channel() else {
+            continue;
+        };
 
-        (guild.id, channel_id)
-    };
+        let voice_channel = ChannelId::new(voice_channel.0.get());
+        let text_channel = text_channels.get(&guild_id).copied();
+        if let Some(session) = snapshot_session(&voice_lock, voice_channel, text_channel).await {
+            sessions.insert(guild_id.get(), session);
+        }
+    }
 
-    let manager = songbird::get(ctx)
-        .await
-        .expect("Expected songbird in context");
+    sessions
+}
 
-    let Some(voice_lock) = manager.get(guild_id) else {
-        let error = EmbedBuilder::error()
-            .title("!stop")
-            .description("User not in a voice channel")
-            .build();
+/// Captures the playback of a call connected to `voice_channel`, unless it has nothing
+/// enqueued.
+async fn snapshot_session(
+    voice_lock: &Mutex<Call>,
+    voice_channel: ChannelId,
+    text_channel: Option<ChannelId>,
+) -> Option<Session> {
+    let handles = voice_lock.lock().await.queue().current_queue();
+
+    let mut tracks = Vec::with_capacity(handles.len());
+    let mut left_out = 0;
+    let mut first_kept = false;
+    for (position, handle) in handles.iter().enumerate() {
+        let typemap = handle.typemap().read().await;
+        let (Some(source), Some(url), Some(title), Some(requester)) = (
+            typemap.get::<TrackSourceKey>(),
+            typemap.get::<TrackUrlKey>(),
+            typemap.get::<TrackTitleKey>(),
+            typemap.get::<TrackRequesterKey>(),
+        ) else {
+            continue;
+        };
 
-        let message = CreateMessage::new().add_embed(error);
-        check_msg(msg.channel_id.send_message(&ctx.http, message).await);
-        return Ok(());
-    };
+        // uploaded files are played from signed CDN URLs, which expire
+        if *source == TrackSource::Attachment {
+            left_out += 1;
+            continue;
+        }
 
-    let current_channel = voice_lock.lock().await.current_channel();
-    if author_channel_id.map(songbird::id::ChannelId::from) != current_channel {
-        let error = EmbedBuilder::error()
-            .title("!stop")
-            .description("User not in the same voice channel")
-            .build();
+        first_kept |= position == 0;
+        tracks.push(SessionTrack {
+            source: *source,
+            url: url.to_string(),
+            title: title.to_string(),
+            requester: requester.get(),
+        });
+    }
 
-        let message = CreateMessage::new().add_embed(error);
-        check_msg(msg.channel_id.send_message(&ctx.http, message).await);
-        return Ok(());
+    if tracks.is_empty() {
+        return None;
     }
 
-    voice_lock.lock().await.queue().stop();
+    // the playing track left out, the next one starts from the beginning
+    let info = match handles.first() {
+        Some(handle) if first_kept => handle.get_info().await.ok(),
+        _ => None,
+    };
 
-    Ok(())
+    Some(Session {
+        voice_channel: voice_channel.get(),
+        text_channel: text_channel.map(|id| id.get()),
+        tracks,
+        position: info
+            .as_ref()
+            .map_or(0.0, |info| info.position.as_secs_f64()),
+        volume: info.as_ref().map_or(1.0, |info| info.volume),
+        looping: info.is_some_and(|info| info.loops != LoopState::Finite(0)),
+        left_out,
+    })
 }
 
-#[command]
-#[only_in(guilds)]
-async fn unmute(ctx: &Context, msg: &Message) -> CommandResult {
-    let (guild_id, author_channel_id) = {
-        let guild = msg.guild(&ctx.cache).expect("Expected guild to be defined");
-        let channel_id = guild
-            .voice_states
-            .get(&msg.author.id)
-            .and_then(|vs| vs.channel_id);
+async fn save_sessions(ctx: &Context) {
+    let manager = songbird::get(ctx)
+        .await
+        .expect("Expected songbird in context");
 
-        (guild.id, channel_id)
-    };
+    let text_channels = get_text_channels(ctx).await;
+    let text_channels = text_channels.read().await.clone();
+
+    let sessions = snapshot_sessions(&manager, &text_channels).await;
+    if let Err(err) = get_session_store(ctx).await.save(sessions).await {
+        tracing::error!("Failed saving sessions: {err}");
+    }
+}
 
+/// Rejoins the session voice channel and enqueues its tracks again, resuming the first
+/// one where it was left.
+async fn restore_session(ctx: &Context, guild_id: GuildId, session: Session) {
+    let style = get_embed_style(ctx).await;
     let manager = songbird::get(ctx)
         .await
         .expect("Expected songbird in context");
 
-    let Some(voice_lock) = manager.get(guild_id) else {
-        let error = EmbedBuilder::error()
-            .title("!unmute")
-            .description("User not in a voice channel")
-            .build();
-
-        let message = CreateMessage::new().add_embed(error);
-        check_msg(msg.channel_id.send_message(&ctx.http, message).await);
-        return Ok(());
+    let voice_channel = ChannelId::new(session.voice_channel);
+    let voice_lock = match manager.join(guild_id, voice_channel).await {
+        Ok(voice_lock) => voice_lock,
+        Err(err) => return tracing::error!("Failed rejoining voice channel of {guild_id}: {err}"),
     };
 
-    let current_channel = voice_lock.lock().await.current_channel();
-    if author_channel_id.map(songbird::id::ChannelId::from) != current_channel {
-        let error = EmbedBuilder::error()
-            .title("!unmute")
-            .description("User not in the same voice channel")
-            .build();
+    register_call_events(ctx, guild_id, &voice_lock).await;
+    if let Some(text_channel) = session.text_channel {
+        set_text_channel(ctx, guild_id, ChannelId::new(text_channel)).await;
+    }
 
-        let message = CreateMessage::new().add_embed(error);
-        check_msg(msg.channel_id.send_message(&ctx.http, message).await);
-        return Ok(());
+    let announce_channel = get_settings_store(ctx)
+        .await
+        .get(guild_id.get())
+        .await
+        .announce_channel;
+    let text_channel = announce_channel
+        .or(session.text_channel)
+        .map(ChannelId::new);
+
+    if let Err(err) = voice_lock.lock().await.deafen(true).await {
+        tracing::error!("Failed self deafening: {err}");
     }
 
-    let embed = if let Err(err) = voice_lock.lock().await.mute(false).await {
-        tracing::error!("Failed self unmuting: {err}");
+    let handles = enqueue_session(ctx, guild_id, &voice_lock, &session).await;
 
-        EmbedBuilder::error()
-            .title("!unmute")
-            .description("Could not unmute myself")
-            .build()
-    } else {
-        EmbedBuilder::new()
-            .title("!unmute")
-            .description("I'm now unmuted. Use `!mute` to mute me")
-            .build()
-    };
+    tracing::info!(
+        "Restored session of {guild_id} with {} tracks",
+        handles.len()
+    );
 
-    let message = CreateMessage::new().add_embed(embed);
-    check_msg(msg.channel_id.send_message(&ctx.http, message).await);
+    if let Some(text_channel) = text_channel {
+        let embed = EmbedBuilder::new()
+            .title("Session restored")
+            .description(format!(
+                "Resumed playing {} tracks in {}{}",
+                handles.len(),
+                voice_channel.mention(),
+                expired_attachments_note(&session)
+            ))
+            .build(&style);
 
-    Ok(())
+        let message = CreateMessage::new().add_embed(embed);
+        check_msg(text_channel.send_message(&ctx.http, message).await);
+    }
 }
 
-#[command]
-#[only_in(guilds)]
-async fn queue(ctx: &Context, msg: &Message) -> CommandResult {
-    let (guild_id, author_channel_id) = {
-        let guild = msg.guild(&ctx.cache).expect("Expected guild to be defined");
-        let channel_id = guild
-            .voice_states
-            .get(&msg.author.id)
-            .and_then(|vs| vs.channel_id);
+/// Keeps the session tracks within the queue limits of the member resuming it, along with
+/// the reasons of the rejected ones.
+fn admit_session(quota: &mut Quota, mut session: Session) -> (Session, Vec<String>) {
+    let mut rejected = Vec::new();
+    let tracks = std::mem::take(&mut session.tracks);
+    for (position, track) in tracks.into_iter().enumerate() {
+        // expired uploads are skipped when enqueued, so they don't take room in the queue
+        if track.source == TrackSource::Attachment {
+            session.tracks.push(track);
+            continue;
+        }
 
-        (guild.id, channel_id)
-    };
+        match quota.admit(None, track.source == TrackSource::Stream) {
+            Ok(()) => session.tracks.push(track),
+            Err(rejection) => {
+                // the next track must not resume where the rejected one was left
+                if position == 0 {
+                    session.position = 0.0;
+                    session.looping = false;
+                }
 
-    let manager = songbird::get(ctx)
-        .await
-        .expect("Expected songbird in context");
+                rejected.push(format!("{}: {rejection}", track.title));
+            }
+        }
+    }
 
-    let Some(voice_lock) = manager.get(guild_id) else {
-        let error = EmbedBuilder::error()
-            .title("!queue")
-            .description("User not in a voice channel")
-            .build();
+    (session, rejected)
+}
 
-        let message = CreateMessage::new().add_embed(error);
-        check_msg(msg.channel_id.send_message(&ctx.http, message).await);
-        return Ok(());
-    };
+fn expired_attachments_note(session: &Session) -> String {
+    match session.expired_attachments() {
+        0 => String::new(),
+        count => format!(", {count} uploaded files could not be restored since their links expire"),
+    }
+}
 
-    let current_channel = voice_lock.lock().await.current_channel();
-    if author_channel_id.map(songbird::id::ChannelId::from) != current_channel {
-        let error = EmbedBuilder::error()
-            .title("!queue")
-            .description("User not in the same voice channel")
-            .build();
+/// Enqueues the session tracks into the call, resuming the first one where it was left
+/// when nothing was enqueued before.
+async fn enqueue_session(
+    ctx: &Context,
+    guild_id: GuildId,
+    voice_lock: &Arc<Mutex<Call>>,
+    session: &Session,
+) -> Vec<TrackHandle> {
+    let http_client = get_http_client(ctx).await;
+    let radio_store = get_radio_store(ctx).await;
+
+    let mut voice = voice_lock.lock().await;
+    let was_empty = voice.queue().is_empty();
+
+    let mut handles = Vec::with_capacity(session.tracks.len());
+    for track in &session.tracks {
+        let requester = UserId::new(track.requester);
+        let handle = match track.source {
+            TrackSource::YtDlp => {
+                let metadata = playlist::Metadata {
+                    url: track.url.clone(),
+                    title: track.title.clone(),
+                    duration: None,
+                    live: false,
+                    uploader: None,
+                    thumbnail: None,
+                };
+
+                enqueue_ytdlp(ctx, &mut voice, metadata, requester).await
+            }
+            // saved before uploaded files were left out of sessions, their URLs expired
+            TrackSource::Attachment => continue,
+            TrackSource::Local => {
+                enqueue_local(
+                    &mut voice,
+                    PathBuf::from(&track.url),
+                    &track.title,
+                    requester,
+                )
+                .await
+            }
+            TrackSource::Stream => {
+                let station = radio_store
+                    .list(guild_id.get())
+                    .await
+                    .into_iter()
+                    .find(|station| station.url == track.url);
+
+                match station {
+                    Some(station) => {
+                        let http_client = http_client.clone();
+                        enqueue_radio(&mut voice, voice_lock, http_client, &station, 0, requester)
+                            .await
+                    }
+                    None => {
+                        let src = HttpRequest::new(http_client.clone(), track.url.clone()).into();
+                        let http_client = http_client.clone();
+                        enqueue_stream(
+                            &mut voice,
+                            src,
+                            http_client,
+                            &track.url,
+                            &track.title,
+                            true,
+                            requester,
+                        )
+                        .await
+                    }
+                }
+            }
+        };
 
-        let message = CreateMessage::new().add_embed(error);
-        check_msg(msg.channel_id.send_message(&ctx.http, message).await);
-        return Ok(());
+        handles.push(handle);
     }
 
-    let mut tracks = voice_lock.lock().await.queue().current_queue();
-    let current_track_title = match tracks.pop() {
-        Some(track) => get_track_title(&track).await,
-        None => {
-            let embed = EmbedBuilder::new()
-                .title("!queue")
-                .description("Queue is curently empty")
-                .build();
+    apply_fair_queue(ctx, guild_id, voice.queue()).await;
+    prefetch(ctx, voice.queue()).await;
+    std::mem::drop(voice);
 
-            let message = CreateMessage::new().add_embed(embed);
-            check_msg(msg.channel_id.send_message(&ctx.http, message).await);
-            return Ok(());
+    // tracks appended to an existing queue are left as the guild plays them
+    if !was_empty {
+        return handles;
+    }
+
+    for handle in &handles {
+        if let Err(err) = handle.set_volume(session.volume) {
+            tracing::error!("Failed restoring track volume: {err}");
         }
-    };
+    }
 
-    let len = tracks.len().max(50);
-    let mut description = format!(
-        "Now playing: **{current_track_title}**\n\nTotal tracks in queue: **{}**\n\n",
-        tracks.len()
-    );
-    description.reserve(len * 10);
+    // a legacy attachment first in the session was skipped, its state is not the next track's
+    let first_source = session.tracks.first().map(|track| track.source);
+    if first_source == Some(TrackSource::Attachment) {
+        return handles;
+    }
 
-    for (idx, handle) in tracks.iter().take(len).enumerate() {
-        let title = get_track_title(handle).await;
+    if let Some(first) = handles.first() {
+        if session.looping {
+            if let Err(err) = first.enable_loop() {
+                tracing::error!("Failed restoring track loop: {err}");
+            }
+        }
 
-        description.push_str(&format!("{idx}. {title}\n"));
+        if session.position > 0.0 && first_source != Some(TrackSource::Stream) {
+            let _ = first.seek(Duration::from_secs_f64(session.position));
+        }
     }
 
-    let embed = EmbedBuilder::new()
-        .title("!queue")
-        .description(description)
-        .build();
+    handles
+}
 
-    let message = CreateMessage::new().embed(embed);
-    check_msg(msg.channel_id.send_message(&ctx.http, message).await);
+/// Room left in the guild queue for tracks requested by `user_id`.
+async fn user_quota(
+    ctx: &Context,
+    guild_id: GuildId,
+    queue: &TrackQueue,
+    user_id: UserId,
+) -> Quota {
+    let settings = get_settings_store(ctx).await.get(guild_id.get()).await;
+    let tracks = queue.current_queue();
+
+    let mut user_tracks = 0;
+    for track in &tracks {
+        if get_track_requester(track).await == Some(user_id) {
+            user_tracks += 1;
+        }
+    }
 
-    Ok(())
+    Quota::new(Limits::from_settings(&settings), tracks.len(), user_tracks)
 }
 
-#[command]
-#[only_in(guilds)]
-async fn now(ctx: &Context, msg: &Message) -> CommandResult {
-    let (guild_id, author_channel_id) = {
-        let guild = msg.guild(&ctx.cache).expect("Expected guild to be defined");
-        let channel_id = guild
-            .voice_states
-            .get(&msg.author.id)
-            .and_then(|vs| vs.channel_id);
+/// Interleaves the tracks waiting to be played by requester, when the guild enables `fair_queue`.
+async fn apply_fair_queue(ctx: &Context, guild_id: GuildId, queue: &TrackQueue) {
+    let settings = get_settings_store(ctx).await.get(guild_id.get()).await;
+    if settings.fair_queue != Some(true) {
+        return;
+    }
 
-        (guild.id, channel_id)
-    };
+    let mut pending = Vec::new();
+    for handle in queue.current_queue().iter().skip(1) {
+        pending.push((handle.uuid(), get_track_requester(handle).await));
+    }
 
-    let manager = songbird::get(ctx)
-        .await
-        .expect("Expected songbird in context");
+    let positions = fair_queue::round_robin(pending, |(_, requester)| *requester)
+        .into_iter()
+        .enumerate()
+        .map(|(position, (uuid, _))| (uuid, position))
+        .collect::<HashMap<_, _>>();
 
-    let Some(voice_lock) = manager.get(guild_id) else {
-        let error = EmbedBuilder::error()
-            .title("!now")
-            .description("User not in a voice channel")
-            .build();
+    queue.modify_queue(|q| {
+        // the first track is being played and must stay in place
+        if q.len() < 3 {
+            return;
+        }
 
-        let message = CreateMessage::new().add_embed(error);
-        check_msg(msg.channel_id.send_message(&ctx.http, message).await);
-        return Ok(());
-    };
+        let mut pending = q.split_off(1);
+        pending
+            .make_contiguous()
+            .sort_by_key(|track| positions.get(&track.uuid()).copied().unwrap_or(usize::MAX));
+        q.append(&mut pending);
+    });
+}
 
-    let current_channel = voice_lock.lock().await.current_channel();
-    if author_channel_id.map(songbird::id::ChannelId::from) != current_channel {
-        let error = EmbedBuilder::error()
-            .title("!now")
-            .description("User not in the same voice channel")
-            .build();
+async fn prefetch(ctx: &Context, queue: &TrackQueue) {
+    if let Some(audio_cache) = get_audio_cache(ctx).await {
+        prefetch_upcoming(queue, &audio_cache).await;
+    }
+}
 
-        let message = CreateMessage::new().add_embed(error);
-        check_msg(msg.channel_id.send_message(&ctx.http, message).await);
-        return Ok(());
+/// Downloads tracks following the current one into the audio cache.
+async fn prefetch_upcoming(queue: &TrackQueue, audio_cache: &Arc<AudioCache>) {
+    let upcoming = queue.current_queue();
+    for track in upcoming.iter().skip(1).take(audio_cache.prefetch_count()) {
+        let typemap = track.typemap().read().await;
+        if typemap.get::<TrackSourceKey>() != Some(&TrackSource::YtDlp) {
+            continue;
+        }
+
+        if let Some(url) = typemap.get::<TrackUrlKey>().cloned() {
+            std::mem::drop(typemap);
+            audio_cache.clone().prefetch(url.to_string()).await;
+        }
     }
+}
 
-    let Some(current_track) = voice_lock.lock().await.queue().current() else {
-        let embed = EmbedBuilder::new()
-            .title("!now")
-            .description("Not currently playing a track")
-            .build();
+async fn get_config(ctx: &Context) -> Arc<Config> {
+    let typemap = ctx.data.read().await;
+    typemap
+        .get::<ConfigKey>()
+        .cloned()
+        .expect("ConfigKey guaranteed to exist in typemap")
+}
 
-        let message = CreateMessage::new().add_embed(embed);
-        check_msg(msg.channel_id.send_message(&ctx.http, message).await);
-        return Ok(());
-    };
+async fn get_embed_style(ctx: &Context) -> Arc<EmbedStyle> {
+    let typemap = ctx.data.read().await;
+    typemap
+        .get::<EmbedStyleKey>()
+        .cloned()
+        .expect("EmbedStyleKey guaranteed to exist in typemap")
+}
 
-    let title = get_track_title(&current_track).await;
-    let embed = EmbedBuilder::new()
-        .title("!now")
-        .description(format!("Now playing {title}"))
-        .build();
+async fn get_http_client(ctx: &Context) -> HttpClient {
+    let typemap = ctx.data.read().await;
+    typemap
+        .get::<HttpKey>()
+        .cloned()
+        .expect("HttpKey guaranteed to exist in typemap")
+}
 
-    let message = CreateMessage::new().add_embed(embed);
-    check_msg(msg.channel_id.send_message(&ctx.http, message).await);
+async fn get_ytdlp_config(ctx: &Context) -> Arc<ytdlp::Config> {
+    let typemap = ctx.data.read().await;
+    typemap
+        .get::<YtDlpKey>()
+        .cloned()
+        .expect("YtDlpKey guaranteed to exist in typemap")
+}
 
-    Ok(())
+async fn get_metadata_cache(ctx: &Context) -> Arc<MetadataCache> {
+    let typemap = ctx.data.read().await;
+    typemap
+        .get::<MetadataCacheKey>()
+        .cloned()
+        .expect("MetadataCacheKey guaranteed to exist in typemap")
 }
 
-#[command]
-#[only_in(guilds)]
-async fn help(ctx: &Context, msg: &Message) -> CommandResult {
-    let fields = vec![
-        EmbedField::new("!help", "Explains all available commands"),
-        EmbedField::new("!join", "Call **Nina** to join your current voice channel"),
-        EmbedField::new("!mute", "Mutes **Nina**. Beware, if playing a track, no sound will come out. See **!unmute** to unmute **Nina**"),
-        EmbedField::new("!play", "Play or enqueue a track. Must provide the track name or source **URL**"),
-        EmbedField::new("!skip", "Skip track. Accepts an optional parameter to define amount of tracks to skip (max of 20)"),
-        EmbedField::new("!stop", "Stop **Nina** if playing a track and clears all enqueued tracks"),
-        EmbedField::new("!unmute", "Unmute **Nina**. See **!mute** to mute **Nina**"),
-        EmbedField::new("!queue", "List first 50 enqueued tracks. There is currently no way to list all enqueue tracks"),
-        EmbedField::new("!now", "Show playing track title"),
-    ];
+async fn get_attachment_config(ctx: &Context) -> attachment::Config {
+    let typemap = ctx.data.read().await;
+    typemap
+        .get::<AttachmentKey>()
+        .copied()
+        .expect("AttachmentKey guaranteed to exist in typemap")
+}
 
-    let embed = EmbedBuilder::new()
-        .title("!help")
-        .description("Available commands")
-        .fields(fields)
-        .build();
+async fn get_audio_cache(ctx: &Context) -> Option<Arc<AudioCache>> {
+    let typemap = ctx.data.read().await;
+    typemap.get::<AudioCacheKey>().cloned()
+}
 
-    let message = CreateMessage::new().embed(embed);
-    check_msg(msg.channel_id.send_message(&ctx.http, message).await);
+async fn get_library(ctx: &Context) -> Option<Arc<Library>> {
+    let typemap = ctx.data.read().await;
+    typemap.get::<LibraryKey>().cloned()
+}
 
-    Ok(())
+async fn get_radio_store(ctx: &Context) -> Arc<RadioStore> {
+    let typemap = ctx.data.read().await;
+    typemap
+        .get::<RadioKey>()
+        .cloned()
+        .expect("RadioKey guaranteed to exist in typemap")
 }
 
-async fn get_http_client(ctx: &Context) -> HttpClient {
+async fn get_playlist_store(ctx: &Context) -> Arc<PlaylistStore> {
     let typemap = ctx.data.read().await;
     typemap
-        .get::<HttpKey>()
+        .get::<PlaylistKey>()
         .cloned()
-        .expect("HttpKey guaranteed to exist in typemap")
+        .expect("PlaylistKey guaranteed to exist in typemap")
+}
+
+async fn get_settings_store(ctx: &Context) -> Arc<SettingsStore> {
+    let typemap = ctx.data.read().await;
+    typemap
+        .get::<SettingsKey>()
+        .cloned()
+        .expect("SettingsKey guaranteed to exist in typemap")
+}
+
+async fn get_session_store(ctx: &Context) -> Arc<SessionStore> {
+    let typemap = ctx.data.read().await;
+    typemap
+        .get::<SessionKey>()
+        .cloned()
+        .expect("SessionKey guaranteed to exist in typemap")
+}
+
+async fn get_skip_votes(ctx: &Context) -> Arc<SkipVotes> {
+    let typemap = ctx.data.read().await;
+    typemap
+        .get::<SkipVotesKey>()
+        .cloned()
+        .expect("SkipVotesKey guaranteed to exist in typemap")
+}
+
+async fn get_idle_timers(ctx: &Context) -> Arc<IdleTimers> {
+    let typemap = ctx.data.read().await;
+    typemap
+        .get::<IdleTimersKey>()
+        .cloned()
+        .expect("IdleTimersKey guaranteed to exist in typemap")
+}
+
+async fn get_grace_timers(ctx: &Context) -> Arc<IdleTimers> {
+    let typemap = ctx.data.read().await;
+    typemap
+        .get::<GraceTimersKey>()
+        .cloned()
+        .expect("GraceTimersKey guaranteed to exist in typemap")
+}
+
+async fn get_text_channels(ctx: &Context) -> Arc<RwLock<HashMap<GuildId, ChannelId>>> {
+    let typemap = ctx.data.read().await;
+    typemap
+        .get::<TextChannelKey>()
+        .cloned()
+        .expect("TextChannelKey guaranteed to exist in typemap")
 }
 
 async fn get_track_title(track: &TrackHandle) -> Arc<str> {
@@ -885,6 +3734,19 @@ async fn get_track_title(track: &TrackHandle) -> Arc<str> {
         .expect("Track title guaranteed to exists in typemap")
 }
 
+async fn get_track_requester(track: &TrackHandle) -> Option<UserId> {
+    let typemap = track.typemap().read().await;
+    typemap.get::<TrackRequesterKey>().copied()
+}
+
+/// Title and requester of a track, as listed by `!queue`.
+async fn queue_entry(track: &TrackHandle) -> render::QueueEntry {
+    render::QueueEntry {
+        title: get_track_title(track).await.to_string(),
+        requester: get_track_requester(track).await.map(UserId::get),
+    }
+}
+
 fn check_msg(result: serenity::Result<Message>) {
     if let Err(err) = result {
         tracing::error!("Error sending message: {:?}", err);
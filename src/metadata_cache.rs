@@ -0,0 +1,340 @@
+use std::collections::HashMap;
+use std::error;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::playlist::Metadata;
+use crate::storage;
+
+const DEFAULT_CAPACITY: usize = 5000;
+const DEFAULT_TTL: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+/// Interval between writes of a changed cache to disk, batching the inserts in between.
+const FLUSH_INTERVAL: Duration = Duration::from_secs(30);
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Config {
+    /// Maximum amount of tracks kept in cache, summing all entries.
+    pub capacity: usize,
+    pub ttl: Duration,
+    /// File used to persist the cache between restarts, if any.
+    pub path: Option<PathBuf>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            capacity: DEFAULT_CAPACITY,
+            ttl: DEFAULT_TTL,
+            path: None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Stats {
+    pub entries: usize,
+    pub tracks: usize,
+    pub capacity: usize,
+    pub hits: u64,
+    pub misses: u64,
+}
+
+/// Least recently used cache of resolved `yt-dlp` metadata, keyed by normalized
+/// URL or search query.
+#[derive(Debug)]
+pub struct MetadataCache {
+    config: Config,
+    inner: Mutex<Inner>,
+    /// Whether entries changed since the cache was last written to disk.
+    dirty: AtomicBool,
+    /// Held while writing to disk, so older snapshots never overwrite newer ones.
+    flushing: tokio::sync::Mutex<()>,
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    entries: HashMap<String, Entry>,
+    tracks: usize,
+    clock: u64,
+    hits: u64,
+    misses: u64,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+struct Entry {
+    metadata: Vec<Metadata>,
+    /// Unix timestamp, in seconds, of when the entry was inserted.
+    inserted_at: u64,
+    #[serde(skip)]
+    last_used: u64,
+}
+
+impl MetadataCache {
+    pub fn new(config: Config) -> Self {
+        Self {
+            config,
+            inner: Mutex::new(Inner::default()),
+            dirty: AtomicBool::new(false),
+            flushing: tokio::sync::Mutex::new(()),
+        }
+    }
+
+    /// Creates the cache, restoring entries persisted in disk if configured.
+    pub async fn load(config: Config) -> Result<Self, Box<dyn error::Error + Send + Sync>> {
+        let cache = Self::new(config);
+        let Some(path) = &cache.config.path else {
+            return Ok(cache);
+        };
+
+        let content = match tokio::fs::read(path).await {
+            Ok(content) => content,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(cache),
+            Err(err) => return Err(err.into()),
+        };
+
+        let entries: HashMap<String, Entry> = serde_json::from_slice(&content)?;
+        let now = unix_now();
+        {
+            let mut inner = cache.inner.lock().expect("Metadata cache lock poisoned");
+            for (key, entry) in entries {
+                if !cache.is_expired(&entry, now) {
+                    inner.insert(key, entry, cache.config.capacity);
+                }
+            }
+        }
+
+        Ok(cache)
+    }
+
+    pub fn get(&self, query: &str) -> Option<Vec<Metadata>> {
+        let key = normalize_key(query);
+        let now = unix_now();
+        let mut inner = self.inner.lock().expect("Metadata cache lock poisoned");
+
+        let expired = match inner.entries.get(&key) {
+            Some(entry) => self.is_expired(entry, now),
+            None => {
+                inner.misses += 1;
+                return None;
+            }
+        };
+
+        if expired {
+            inner.remove(&key);
+            inner.misses += 1;
+            return None;
+        }
+
+        inner.clock += 1;
+        inner.hits += 1;
+        let clock = inner.clock;
+        let entry = inner.entries.get_mut(&key)?;
+        entry.last_used = clock;
+
+        Some(entry.metadata.clone())
+    }
+
+    pub fn insert(&self, query: &str, metadata: Vec<Metadata>) {
+        if metadata.is_empty() || metadata.len() > self.config.capacity {
+            return;
+        }
+
+        let entry = Entry {
+            metadata,
+            inserted_at: unix_now(),
+            last_used: 0,
+        };
+
+        {
+            let mut inner = self.inner.lock().expect("Metadata cache lock poisoned");
+            inner.insert(normalize_key(query), entry, self.config.capacity);
+        }
+
+        self.dirty.store(true, Ordering::Release);
+    }
+
+    pub fn clear(&self) {
+        {
+            let mut inner = self.inner.lock().expect("Metadata cache lock poisoned");
+            inner.entries.clear();
+            inner.tracks = 0;
+        }
+
+        self.dirty.store(true, Ordering::Release);
+    }
+
+    pub fn stats(&self) -> Stats {
+        let inner = self.inner.lock().expect("Metadata cache lock poisoned");
+        Stats {
+            entries: inner.entries.len(),
+            tracks: inner.tracks,
+            capacity: self.config.capacity,
+            hits: inner.hits,
+            misses: inner.misses,
+        }
+    }
+
+    pub fn ttl(&self) -> Duration {
+        self.config.ttl
+    }
+
+    fn is_expired(&self, entry: &Entry, now: u64) -> bool {
+        now.saturating_sub(entry.inserted_at) >= self.config.ttl.as_secs()
+    }
+
+    /// Writes the cache to disk periodically whenever it changed, if persisting is configured.
+    pub fn persist(self: Arc<Self>) {
+        if self.config.path.is_none() {
+            return;
+        }
+
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(FLUSH_INTERVAL);
+            loop {
+                interval.tick().await;
+                self.flush().await;
+            }
+        });
+    }
+
+    /// Writes the cache to disk if it changed since the last write.
+    pub async fn flush(&self) {
+        let Some(path) = &self.config.path else {
+            return;
+        };
+
+        let _flushing = self.flushing.lock().await;
+        if !self.dirty.swap(false, Ordering::AcqRel) {
+            return;
+        }
+
+        let content = {
+            let inner = self.inner.lock().expect("Metadata cache lock poisoned");
+            serde_json::to_vec(&inner.entries)
+        };
+
+        let result = match content {
+            Ok(content) => storage::write_atomic(path, &content)
+                .await
+                .map_err(|e| e.into()),
+            Err(err) => Err(Box::new(err) as Box<dyn error::Error + Send + Sync>),
+        };
+
+        if let Err(err) = result {
+            // retried on the next flush
+            self.dirty.store(true, Ordering::Release);
+            tracing::error!("Failed persisting metadata cache: {err}");
+        }
+    }
+}
+
+impl Inner {
+    fn insert(&mut self, key: String, mut entry: Entry, capacity: usize) {
+        self.remove(&key);
+
+        while self.tracks + entry.metadata.len() > capacity {
+            let least_used = self
+                .entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(key, _)| key.clone());
+
+            match least_used {
+                Some(key) => self.remove(&key),
+                None => break,
+            }
+        }
+
+        self.clock += 1;
+        entry.last_used = self.clock;
+        self.tracks += entry.metadata.len();
+        self.entries.insert(key, entry);
+    }
+
+    fn remove(&mut self, key: &str) {
+        if let Some(entry) = self.entries.remove(key) {
+            self.tracks -= entry.metadata.len();
+        }
+    }
+}
+
+/// Normalizes an URL or search query so equivalent requests share the same cache entry.
+///
+/// URLs lose surrounding whitespace, fragments and tracking parameters, while search
+/// queries are lowercased with whitespace collapsed.
+pub fn normalize_key(query: &str) -> String {
+    let query = query.trim();
+    if !query.starts_with("http") {
+        let words = query.split_whitespace().collect::<Vec<&str>>();
+        return format!("search:{}", words.join(" ").to_lowercase());
+    }
+
+    let url = query.split('#').next().unwrap_or(query);
+    let Some((base, params)) = url.split_once('?') else {
+        return url.trim_end_matches('/').to_string();
+    };
+
+    let params = params
+        .split('&')
+        .filter(|param| {
+            let name = param.split('=').next().unwrap_or(param);
+            !param.is_empty() && !matches!(name, "si" | "feature" | "pp")
+        })
+        .collect::<Vec<&str>>();
+
+    if params.is_empty() {
+        base.trim_end_matches('/').to_string()
+    } else {
+        format!("{}?{}", base.trim_end_matches('/'), params.join("&"))
+    }
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn metadata(title: &str) -> Metadata {
+        Metadata {
+            url: format!("https://www.youtube.com/watch?v={title}"),
+            title: title.to_string(),
+            duration: None,
+            live: false,
+            uploader: None,
+            thumbnail: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn flushes_changes_to_disk() {
+        let dir = std::env::temp_dir().join(format!("rina-metadata-cache-{}", std::process::id()));
+        tokio::fs::create_dir_all(&dir).await.unwrap();
+        let config = Config {
+            path: Some(dir.join("cache.json")),
+            ..Config::default()
+        };
+
+        let cache = MetadataCache::load(config.clone()).await.unwrap();
+        cache.insert("first song", vec![metadata("first")]);
+        cache.insert("second song", vec![metadata("second")]);
+        cache.flush().await;
+
+        let restored = MetadataCache::load(config).await.unwrap();
+        assert_eq!(restored.stats().entries, 2);
+        assert_eq!(restored.get("First  Song").unwrap()[0].title, "first");
+        assert!(!dir.join("cache.json.tmp").exists());
+
+        tokio::fs::remove_dir_all(&dir).await.unwrap();
+    }
+}
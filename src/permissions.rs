@@ -0,0 +1,165 @@
+use std::ops::Range;
+
+use crate::args;
+use crate::guild_settings::GuildSettings;
+
+/// Commands restricted to DJs once a guild sets a `dj_role`.
+pub const DJ_COMMANDS: [&str; 6] = ["leave", "mute", "unmute", "stop", "skip", "remove"];
+
+/// Guild rules deciding who controls playback.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Rules {
+    pub dj_role: Option<u64>,
+    /// Whether members who are not DJs vote to skip the playing track.
+    pub vote_skip: bool,
+}
+
+impl Rules {
+    pub fn from_settings(settings: &GuildSettings) -> Self {
+        Self {
+            dj_role: settings.dj_role,
+            vote_skip: settings.vote_skip.is_some(),
+        }
+    }
+
+    /// Decides whether a member may run `command` with `arg` as first argument.
+    /// `is_dj` tells if the member has the DJ role or manages the guild.
+    pub fn judge(&self, is_dj: bool, command: &str, arg: Option<&str>) -> Verdict {
+        let voting = command == "skip" && self.vote_skip;
+        if is_dj || !DJ_COMMANDS.contains(&command) || (self.dj_role.is_none() && !voting) {
+            return Verdict::Allowed;
+        }
+
+        match command {
+            "skip" => {
+                // invalid amounts are reported by the command itself
+                let amount = args::skip_amount(arg, usize::MAX).unwrap_or(1);
+                Verdict::OwnTracks {
+                    positions: 0..amount,
+                    vote: voting && amount == 1,
+                }
+            }
+            "remove" => match args::queue_position(arg) {
+                Ok(position) => Verdict::OwnTracks {
+                    positions: position..position + 1,
+                    vote: false,
+                },
+                Err(_) => Verdict::Allowed,
+            },
+            _ => Verdict::Denied,
+        }
+    }
+
+    /// Reason shown to members denied by [`Rules::judge`]. `own_tracks` tells if the command
+    /// would have been allowed on tracks requested by the member.
+    pub fn denial(&self, own_tracks: bool) -> String {
+        let djs = match self.dj_role {
+            Some(dj_role) => format!("members with the <@&{dj_role}> role"),
+            None => String::from("members with the Manage Server permission"),
+        };
+
+        match own_tracks {
+            true => format!("Only {djs} can use this command on tracks requested by others"),
+            false => format!("Only {djs} can use this command"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Verdict {
+    Allowed,
+    Denied,
+    /// Allowed if the member requested every track at `positions` of the queue, counting
+    /// from the one being played. Otherwise, the member votes to skip instead if `vote`.
+    OwnTracks {
+        positions: Range<usize>,
+        vote: bool,
+    },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DJ_ONLY: Rules = Rules {
+        dj_role: Some(42),
+        vote_skip: false,
+    };
+
+    #[test]
+    fn allows_everyone_without_dj_role() {
+        let rules = Rules::default();
+        assert_eq!(rules.judge(false, "stop", None), Verdict::Allowed);
+        assert_eq!(rules.judge(false, "skip", Some("5")), Verdict::Allowed);
+    }
+
+    #[test]
+    fn restricts_playback_control_to_djs() {
+        assert_eq!(DJ_ONLY.judge(false, "stop", None), Verdict::Denied);
+        assert_eq!(DJ_ONLY.judge(true, "stop", None), Verdict::Allowed);
+        assert_eq!(DJ_ONLY.judge(false, "play", Some("song")), Verdict::Allowed);
+        assert_eq!(DJ_ONLY.judge(false, "queue", None), Verdict::Allowed);
+    }
+
+    #[test]
+    fn allows_skipping_and_removing_own_tracks() {
+        let skip = DJ_ONLY.judge(false, "skip", Some("3"));
+        assert_eq!(
+            skip,
+            Verdict::OwnTracks {
+                positions: 0..3,
+                vote: false
+            }
+        );
+
+        let remove = DJ_ONLY.judge(false, "remove", Some("4"));
+        assert_eq!(
+            remove,
+            Verdict::OwnTracks {
+                positions: 4..5,
+                vote: false
+            }
+        );
+    }
+
+    #[test]
+    fn votes_to_skip_a_single_track() {
+        let rules = Rules {
+            dj_role: None,
+            vote_skip: true,
+        };
+
+        let single = rules.judge(false, "skip", None);
+        assert_eq!(
+            single,
+            Verdict::OwnTracks {
+                positions: 0..1,
+                vote: true
+            }
+        );
+
+        let many = rules.judge(false, "skip", Some("2"));
+        assert_eq!(
+            many,
+            Verdict::OwnTracks {
+                positions: 0..2,
+                vote: false
+            }
+        );
+
+        assert_eq!(rules.judge(false, "stop", None), Verdict::Allowed);
+        assert_eq!(rules.judge(true, "skip", None), Verdict::Allowed);
+    }
+
+    #[test]
+    fn names_who_may_run_denied_commands() {
+        assert_eq!(
+            DJ_ONLY.denial(false),
+            "Only members with the <@&42> role can use this command"
+        );
+        assert_eq!(
+            Rules::default().denial(true),
+            "Only members with the Manage Server permission can use this command on tracks requested by others"
+        );
+    }
+}
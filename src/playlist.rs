@@ -1,35 +1,69 @@
 use std::error;
-use std::io::BufRead;
 
-use serde::Deserialize;
-use tokio::process::Command;
+use serde::{Deserialize, Serialize};
+use songbird::input::AuxMetadata;
 
-pub async fn query(url: &str) -> Result<Vec<Metadata>, Box<dyn error::Error + Send + Sync>> {
-    let args = [
-        "-j",
-        url,
-        "-f",
-        "ba[abr>0][vcodec=none]/best",
-        "--flat-playlist",
-    ];
+use crate::ytdlp;
 
-    let output = Command::new("yt-dlp").args(args).output().await?;
-    if !output.status.success() {
-        return Err("Failed querying playlist".into());
-    }
-
-    let metadata = output
-        .stdout
-        .lines()
-        .map_while(|line| line.ok())
-        .map(|line| serde_json::from_str(&line))
-        .collect::<Result<Vec<Metadata>, serde_json::Error>>()?;
+pub async fn query(
+    config: &ytdlp::Config,
+    url: &str,
+) -> Result<Vec<Metadata>, Box<dyn error::Error + Send + Sync>> {
+    let metadata = ytdlp::run(config, url, &["--flat-playlist"])
+        .await
+        .map_err(|err| format!("Failed querying playlist: {err}"))?
+        .into_iter()
+        .map(|output| Metadata {
+            title: output.title.unwrap_or_else(|| String::from("Unknown")),
+            url: output.url,
+            duration: output.duration,
+            live: output.is_live.unwrap_or(false),
+            uploader: output.uploader.or(output.channel),
+            thumbnail: output.thumbnail,
+        })
+        .collect();
 
     Ok(metadata)
 }
 
-#[derive(Clone, Debug, Deserialize)]
+#[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct Metadata {
     pub url: String,
     pub title: String,
+    #[serde(default)]
+    pub duration: Option<f64>,
+    #[serde(default)]
+    pub live: bool,
+    #[serde(default)]
+    pub uploader: Option<String>,
+    #[serde(default)]
+    pub thumbnail: Option<String>,
+}
+
+impl Metadata {
+    /// Builds metadata from a resolved track, returning `None` if its source URL is unknown.
+    pub fn from_aux(metadata: &AuxMetadata) -> Option<Self> {
+        Some(Self {
+            url: metadata.source_url.clone()?,
+            title: metadata
+                .title
+                .clone()
+                .unwrap_or_else(|| String::from("Unknown")),
+            duration: metadata.duration.map(|duration| duration.as_secs_f64()),
+            live: false,
+            uploader: metadata.artist.clone().or_else(|| metadata.channel.clone()),
+            thumbnail: metadata.thumbnail.clone(),
+        })
+    }
+
+    pub fn aux_metadata(&self) -> AuxMetadata {
+        AuxMetadata {
+            title: Some(self.title.clone()),
+            artist: self.uploader.clone(),
+            duration: self.duration.map(std::time::Duration::from_secs_f64),
+            source_url: Some(self.url.clone()),
+            thumbnail: self.thumbnail.clone(),
+            ..Default::default()
+        }
+    }
 }
@@ -0,0 +1,453 @@
+use std::fmt;
+use std::path::Path;
+use std::time::Duration;
+
+/// Extensions of playlist files supported by [`parse`].
+pub const EXTENSIONS: [&str; 4] = ["m3u", "m3u8", "pls", "xspf"];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    M3u,
+    Pls,
+    Xspf,
+}
+
+impl Format {
+    /// Detects the format from the file name extension, or from its content otherwise.
+    pub fn detect(filename: &str, content: &str) -> Self {
+        let extension = Path::new(filename)
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(str::to_lowercase);
+
+        match extension.as_deref() {
+            Some("pls") => Self::Pls,
+            Some("xspf") => Self::Xspf,
+            Some("m3u" | "m3u8") => Self::M3u,
+            _ if content.trim_start().starts_with("[playlist]") => Self::Pls,
+            _ if content.contains("<playlist") => Self::Xspf,
+            _ => Self::M3u,
+        }
+    }
+}
+
+/// A playlist file entry.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Entry {
+    /// URL or file path of the entry, if any.
+    pub location: Option<String>,
+    pub title: Option<String>,
+    pub duration: Option<Duration>,
+}
+
+impl Entry {
+    /// Query used to resolve the entry: its URL when remote, or a search by its title
+    /// otherwise, since local paths from other machines cannot be played.
+    pub fn query(&self) -> Option<String> {
+        if let Some(location) = self.location.as_deref().filter(|l| l.starts_with("http")) {
+            return Some(location.to_string());
+        }
+
+        if let Some(title) = self.title.as_deref().filter(|t| !t.trim().is_empty()) {
+            return Some(title.trim().to_string());
+        }
+
+        let location = self.location.as_deref()?;
+        let stem = location
+            .rsplit(['/', '\\'])
+            .next()
+            .map(|name| name.rsplit_once('.').map_or(name, |(stem, _)| stem))
+            .filter(|stem| !stem.trim().is_empty())?;
+
+        Some(stem.trim().to_string())
+    }
+
+    /// Human readable description of the entry, used when reporting failures.
+    pub fn label(&self) -> String {
+        self.title
+            .clone()
+            .or_else(|| self.location.clone())
+            .unwrap_or_else(|| String::from("Unknown"))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParseError(String);
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+pub fn parse(format: Format, content: &str) -> Result<Vec<Entry>, ParseError> {
+    let entries = match format {
+        Format::M3u => parse_m3u(content),
+        Format::Pls => parse_pls(content),
+        Format::Xspf => parse_xspf(content),
+    };
+
+    if entries.is_empty() {
+        return Err(ParseError(String::from("Playlist file has no entries")));
+    }
+
+    Ok(entries)
+}
+
+/// Writes entries as an extended M3U playlist, skipping those without location.
+pub fn to_m3u(entries: &[Entry]) -> String {
+    let mut content = String::from("#EXTM3U\n");
+
+    for entry in entries {
+        let Some(location) = entry.location.as_deref() else {
+            continue;
+        };
+
+        let duration = entry
+            .duration
+            .map_or(-1, |duration| duration.as_secs() as i64);
+        let title = entry.title.as_deref().unwrap_or_default();
+
+        content.push_str(&format!("#EXTINF:{duration},{title}\n{location}\n"));
+    }
+
+    content
+}
+
+/// Parses plain and extended M3U, where `#EXTINF:<seconds>,<title>` describes the next entry.
+fn parse_m3u(content: &str) -> Vec<Entry> {
+    let mut entries = Vec::new();
+    let mut pending = Entry::default();
+
+    for line in content
+        .lines()
+        .map(|line| line.trim_start_matches('\u{feff}').trim())
+    {
+        if let Some(info) = line.strip_prefix("#EXTINF:") {
+            let (duration, title) = info.split_once(',').unwrap_or((info, ""));
+            // duration may be followed by attributes, as in `#EXTINF:123 tvg-id="x",Title`
+            let seconds = duration.split_whitespace().next().unwrap_or_default();
+
+            pending.duration = seconds
+                .parse::<f64>()
+                .ok()
+                .filter(|secs| *secs > 0.0)
+                .and_then(|secs| Duration::try_from_secs_f64(secs).ok());
+            pending.title = Some(title.trim().to_string()).filter(|t| !t.is_empty());
+        } else if line.is_empty() || line.starts_with('#') {
+            continue;
+        } else {
+            pending.location = Some(line.to_string());
+            entries.push(std::mem::take(&mut pending));
+        }
+    }
+
+    entries
+}
+
+/// Parses PLS files, grouping `FileN`, `TitleN` and `LengthN` keys by their index.
+fn parse_pls(content: &str) -> Vec<Entry> {
+    let mut entries = std::collections::BTreeMap::<u32, Entry>::new();
+
+    for line in content.lines().map(str::trim) {
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+
+        let key = key.trim().to_lowercase();
+        let value = value.trim();
+        let field_index = ["file", "title", "length"].into_iter().find_map(|field| {
+            let index = key.strip_prefix(field)?.parse::<u32>().ok()?;
+            Some((field, index))
+        });
+
+        let Some((field, index)) = field_index else {
+            continue;
+        };
+
+        let entry = entries.entry(index).or_default();
+        match field {
+            "file" => entry.location = Some(value.to_string()),
+            "title" => entry.title = Some(value.to_string()).filter(|t| !t.is_empty()),
+            _ => {
+                entry.duration = value
+                    .parse::<i64>()
+                    .ok()
+                    .and_then(|secs| u64::try_from(secs).ok())
+                    .filter(|secs| *secs > 0)
+                    .map(Duration::from_secs)
+            }
+        }
+    }
+
+    entries
+        .into_values()
+        .filter(|entry| entry.location.is_some())
+        .collect()
+}
+
+/// Parses XSPF `<track>` elements, reading their location, title, creator and duration.
+fn parse_xspf(content: &str) -> Vec<Entry> {
+    let mut entries = Vec::new();
+    let mut rest = content;
+
+    while let Some(start) = find_open_tag(rest, "track") {
+        let Some(end) = rest[start..].find("</track>") else {
+            break;
+        };
+
+        let track = &rest[start..start + end];
+        rest = &rest[start + end + "</track>".len()..];
+
+        let title = match (xml_text(track, "creator"), xml_text(track, "title")) {
+            (Some(creator), Some(title)) => Some(format!("{creator} - {title}")),
+            (None, title) => title,
+            (creator, None) => creator,
+        };
+
+        let location =
+            xml_text(track, "location").map(|location| match location.strip_prefix("file://") {
+                Some(path) => percent_decode(path),
+                None => location,
+            });
+
+        entries.push(Entry {
+            location,
+            title,
+            duration: xml_text(track, "duration")
+                .and_then(|ms| ms.parse::<u64>().ok())
+                .filter(|ms| *ms > 0)
+                .map(Duration::from_millis),
+        });
+    }
+
+    entries
+}
+
+/// Position of the first `<tag>` element in `xml`, with or without attributes, ignoring
+/// longer tags sharing its prefix such as `<trackList>`.
+fn find_open_tag(xml: &str, tag: &str) -> Option<usize> {
+    let open = format!("<{tag}");
+    let mut offset = 0;
+
+    while let Some(found) = xml[offset..].find(&open) {
+        let start = offset + found;
+        let next = xml[start + open.len()..].chars().next();
+        if next.is_some_and(|c| c == '>' || c.is_whitespace()) {
+            return Some(start);
+        }
+
+        offset = start + open.len();
+    }
+
+    None
+}
+
+/// Text content of the first `<tag>` element inside `xml`, with entities unescaped.
+fn xml_text(xml: &str, tag: &str) -> Option<String> {
+    let open = format!("<{tag}>");
+    let close = format!("</{tag}>");
+
+    let start = xml.find(&open)? + open.len();
+    let end = xml[start..].find(&close)? + start;
+    let text = xml[start..end].trim();
+    let text = text
+        .strip_prefix("<![CDATA[")
+        .and_then(|text| text.strip_suffix("]]>"))
+        .map(String::from)
+        .unwrap_or_else(|| unescape_xml(text));
+
+    Some(text).filter(|text| !text.is_empty())
+}
+
+fn unescape_xml(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_format() {
+        assert_eq!(Format::detect("mix.PLS", ""), Format::Pls);
+        assert_eq!(Format::detect("mix.xspf", ""), Format::Xspf);
+        assert_eq!(
+            Format::detect("mix.txt", "[playlist]\nFile1=a"),
+            Format::Pls
+        );
+        assert_eq!(
+            Format::detect("mix", "<playlist version=\"1\">"),
+            Format::Xspf
+        );
+        assert_eq!(Format::detect("mix", "https://example.com/a"), Format::M3u);
+    }
+
+    #[test]
+    fn ignores_out_of_range_m3u_durations() {
+        for duration in ["inf", "1e30", "NaN"] {
+            let content = format!("#EXTINF:{duration},Song\nsong.mp3\n");
+            let entries = parse(Format::M3u, &content).unwrap();
+
+            assert_eq!(entries.len(), 1);
+            assert_eq!(entries[0].title.as_deref(), Some("Song"));
+            assert_eq!(entries[0].duration, None, "{duration}");
+        }
+    }
+
+    #[test]
+    fn parses_extended_m3u() {
+        let content =
+            "#EXTM3U\n#EXTINF:123 tvg-id=\"x\",Artist - Song\nhttps://example.com/a\n\nsong.mp3\n";
+        let entries = parse(Format::M3u, content).unwrap();
+
+        assert_eq!(
+            entries,
+            [
+                Entry {
+                    location: Some(String::from("https://example.com/a")),
+                    title: Some(String::from("Artist - Song")),
+                    duration: Some(Duration::from_secs(123)),
+                },
+                Entry {
+                    location: Some(String::from("song.mp3")),
+                    ..Entry::default()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn parses_pls() {
+        let content =
+            "[playlist]\nFile2=b.mp3\nTitle1=First\nFile1=a.mp3\nLength1=-1\nTitle3=Orphan\n";
+        let entries = parse(Format::Pls, content).unwrap();
+
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].location.as_deref(), Some("a.mp3"));
+        assert_eq!(entries[0].title.as_deref(), Some("First"));
+        assert_eq!(entries[0].duration, None);
+        assert_eq!(entries[1].location.as_deref(), Some("b.mp3"));
+    }
+
+    #[test]
+    fn parses_xspf() {
+        let content = "<playlist><trackList><track>\
+            <location>file:///music/My%20Song.flac</location>\
+            <creator>Artist</creator><title>Tom &amp; Jerry</title>\
+            <duration>90000</duration>\
+            </track></trackList></playlist>";
+        let entries = parse(Format::Xspf, content).unwrap();
+
+        assert_eq!(
+            entries,
+            [Entry {
+                location: Some(String::from("/music/My Song.flac")),
+                title: Some(String::from("Artist - Tom & Jerry")),
+                duration: Some(Duration::from_secs(90)),
+            }]
+        );
+    }
+
+    #[test]
+    fn parses_xspf_tracks_with_attributes() {
+        let content = "<playlist><trackList>\
+            <track id=\"1\"><location>https://example.com/a</location></track>\
+            <track><location>https://example.com/b</location></track>\
+            <track\n><location>https://example.com/c</location></track>\
+            </trackList></playlist>";
+        let entries = parse(Format::Xspf, content).unwrap();
+
+        let locations = entries
+            .iter()
+            .map(|entry| entry.location.as_deref().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            locations,
+            [
+                "https://example.com/a",
+                "https://example.com/b",
+                "https://example.com/c"
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_empty_playlists() {
+        assert!(parse(Format::M3u, "#EXTM3U\n").is_err());
+    }
+
+    #[test]
+    fn queries_by_url_title_or_file_name() {
+        let remote = Entry {
+            location: Some(String::from("https://example.com/a")),
+            title: Some(String::from("Song")),
+            ..Entry::default()
+        };
+        assert_eq!(remote.query().as_deref(), Some("https://example.com/a"));
+
+        let titled = Entry {
+            location: Some(String::from("C:\\music\\a.mp3")),
+            title: Some(String::from("Song")),
+            ..Entry::default()
+        };
+        assert_eq!(titled.query().as_deref(), Some("Song"));
+
+        let untitled = Entry {
+            location: Some(String::from("C:\\music\\Artist - Song.mp3")),
+            ..Entry::default()
+        };
+        assert_eq!(untitled.query().as_deref(), Some("Artist - Song"));
+    }
+
+    #[test]
+    fn writes_m3u_round_trip() {
+        let entries = vec![
+            Entry {
+                location: Some(String::from("https://example.com/a")),
+                title: Some(String::from("Song")),
+                duration: Some(Duration::from_secs(60)),
+            },
+            Entry {
+                title: Some(String::from("No location")),
+                ..Entry::default()
+            },
+        ];
+
+        let content = to_m3u(&entries);
+        assert_eq!(content, "#EXTM3U\n#EXTINF:60,Song\nhttps://example.com/a\n");
+        assert_eq!(parse(Format::M3u, &content).unwrap(), entries[..1]);
+    }
+}
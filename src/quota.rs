@@ -0,0 +1,163 @@
+use std::fmt;
+use std::time::Duration;
+
+use crate::guild_settings::{format_duration, GuildSettings};
+
+/// Guild limits on what can be enqueued, set with `!settings`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Limits {
+    pub max_queue_length: Option<usize>,
+    pub max_user_tracks: Option<usize>,
+    pub max_track_duration: Option<Duration>,
+    pub allow_streams: bool,
+}
+
+impl Limits {
+    pub fn from_settings(settings: &GuildSettings) -> Self {
+        Self {
+            max_queue_length: settings.max_queue_length,
+            max_user_tracks: settings.max_user_tracks,
+            max_track_duration: settings.max_track_duration.map(Duration::from_secs),
+            allow_streams: settings.allow_streams.unwrap_or(true),
+        }
+    }
+}
+
+/// Why a track was not enqueued.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Rejection {
+    StreamsNotAllowed,
+    TooLong { duration: Duration, limit: Duration },
+    QueueFull { limit: usize },
+    UserQuota { limit: usize },
+}
+
+impl fmt::Display for Rejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::StreamsNotAllowed => write!(f, "Live streams are not allowed in this server"),
+            Self::TooLong { duration, limit } => write!(
+                f,
+                "Track is {} long, over the limit of {}",
+                format_duration(duration.as_secs()),
+                format_duration(limit.as_secs())
+            ),
+            Self::QueueFull { limit } => write!(f, "Queue is limited to {limit} tracks"),
+            Self::UserQuota { limit } => {
+                write!(f, "Each member can have up to {limit} tracks in the queue")
+            }
+        }
+    }
+}
+
+/// Room left in a guild queue for tracks requested by a single user.
+#[derive(Clone, Debug)]
+pub struct Quota {
+    limits: Limits,
+    queue_length: usize,
+    user_tracks: usize,
+}
+
+impl Quota {
+    /// `queue_length` counts every enqueued track, including the one being played, while
+    /// `user_tracks` only counts the ones requested by the user.
+    pub fn new(limits: Limits, queue_length: usize, user_tracks: usize) -> Self {
+        Self {
+            limits,
+            queue_length,
+            user_tracks,
+        }
+    }
+
+    /// Checks whether a track fits the limits, counting it as enqueued if it does.
+    /// Tracks of unknown duration are only rejected when live.
+    pub fn admit(&mut self, duration: Option<Duration>, live: bool) -> Result<(), Rejection> {
+        if live && !self.limits.allow_streams {
+            return Err(Rejection::StreamsNotAllowed);
+        }
+
+        if let (Some(duration), Some(limit)) = (duration, self.limits.max_track_duration) {
+            if duration > limit {
+                return Err(Rejection::TooLong { duration, limit });
+            }
+        }
+
+        if let Some(limit) = self.limits.max_queue_length {
+            if self.queue_length >= limit {
+                return Err(Rejection::QueueFull { limit });
+            }
+        }
+
+        if let Some(limit) = self.limits.max_user_tracks {
+            if self.user_tracks >= limit {
+                return Err(Rejection::UserQuota { limit });
+            }
+        }
+
+        self.queue_length += 1;
+        self.user_tracks += 1;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limits() -> Limits {
+        Limits {
+            max_queue_length: Some(3),
+            max_user_tracks: Some(2),
+            max_track_duration: Some(Duration::from_secs(600)),
+            allow_streams: false,
+        }
+    }
+
+    #[test]
+    fn counts_admitted_tracks() {
+        let mut quota = Quota::new(limits(), 0, 0);
+
+        assert_eq!(quota.admit(None, false), Ok(()));
+        assert_eq!(quota.admit(Some(Duration::from_secs(60)), false), Ok(()));
+        assert_eq!(
+            quota.admit(None, false),
+            Err(Rejection::UserQuota { limit: 2 })
+        );
+    }
+
+    #[test]
+    fn fills_up_the_queue() {
+        let mut quota = Quota::new(limits(), 2, 0);
+
+        assert_eq!(quota.admit(None, false), Ok(()));
+        assert_eq!(
+            quota.admit(None, false),
+            Err(Rejection::QueueFull { limit: 3 })
+        );
+    }
+
+    #[test]
+    fn rejects_streams_and_long_tracks() {
+        let mut quota = Quota::new(limits(), 0, 0);
+
+        assert_eq!(quota.admit(None, true), Err(Rejection::StreamsNotAllowed));
+        assert_eq!(
+            quota.admit(Some(Duration::from_secs(601)), false),
+            Err(Rejection::TooLong {
+                duration: Duration::from_secs(601),
+                limit: Duration::from_secs(600)
+            })
+        );
+
+        // rejected tracks do not count towards the limits
+        assert_eq!(quota.admit(None, false), Ok(()));
+    }
+
+    #[test]
+    fn unlimited_by_default() {
+        let limits = Limits::from_settings(&GuildSettings::default());
+        let mut quota = Quota::new(limits, 1000, 1000);
+
+        assert_eq!(quota.admit(Some(Duration::from_secs(36000)), true), Ok(()));
+    }
+}
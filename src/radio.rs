@@ -0,0 +1,94 @@
+use std::collections::{BTreeMap, HashMap};
+use std::error;
+
+use serde::{Deserialize, Serialize};
+
+use crate::storage::{Collection, Storage};
+
+/// Names reserved for `!radio` subcommands.
+const RESERVED_NAMES: [&str; 4] = ["add", "replace", "list", "remove"];
+
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Station {
+    pub name: String,
+    pub url: String,
+}
+
+/// Radio station presets, keyed by guild id and lowercased station name.
+type Stations = HashMap<u64, BTreeMap<String, Station>>;
+
+#[derive(Debug)]
+pub struct RadioStore(Collection<Stations>);
+
+impl RadioStore {
+    pub async fn load(storage: Storage) -> Result<Self, Box<dyn error::Error + Send + Sync>> {
+        Collection::load(storage, "radios").await.map(Self)
+    }
+
+    pub async fn get(&self, guild_id: u64, name: &str) -> Option<Station> {
+        let key = name.to_lowercase();
+        self.0
+            .read(|stations| stations.get(&guild_id)?.get(&key).cloned())
+            .await
+    }
+
+    pub async fn list(&self, guild_id: u64) -> Vec<Station> {
+        self.0
+            .read(|stations| {
+                stations
+                    .get(&guild_id)
+                    .map(|guild| guild.values().cloned().collect())
+                    .unwrap_or_default()
+            })
+            .await
+    }
+
+    /// Adds a station, returning the replaced one. Stations with the same name are only
+    /// replaced when `replace` is set.
+    pub async fn add(
+        &self,
+        guild_id: u64,
+        station: Station,
+        replace: bool,
+    ) -> Result<Option<Station>, Box<dyn error::Error + Send + Sync>> {
+        let key = station.name.to_lowercase();
+        if RESERVED_NAMES.contains(&key.as_str()) {
+            return Err(format!("`{}` is a reserved name", station.name).into());
+        }
+
+        let name = station.name.clone();
+        self.0
+            .update(|stations| {
+                let guild = stations.entry(guild_id).or_default();
+                if !replace && guild.contains_key(&key) {
+                    return Err(format!(
+                        "station **{name}** already exists, use `!radio replace` to change it"
+                    ));
+                }
+
+                Ok(guild.insert(key, station))
+            })
+            .await?
+            .map_err(Into::into)
+    }
+
+    /// Removes a station, returning it if it existed.
+    pub async fn remove(
+        &self,
+        guild_id: u64,
+        name: &str,
+    ) -> Result<Option<Station>, Box<dyn error::Error + Send + Sync>> {
+        let key = name.to_lowercase();
+        self.0
+            .update(|stations| {
+                let guild = stations.get_mut(&guild_id)?;
+                let removed = guild.remove(&key);
+                if guild.is_empty() {
+                    stations.remove(&guild_id);
+                }
+
+                removed
+            })
+            .await
+    }
+}
@@ -0,0 +1,117 @@
+/// Track of a queue as listed by `!queue`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct QueueEntry {
+    pub title: String,
+    /// Id of the user who enqueued the track.
+    pub requester: Option<u64>,
+}
+
+impl QueueEntry {
+    fn line(&self) -> String {
+        match self.requester {
+            Some(requester) => format!("{} - <@{requester}>", self.title),
+            None => self.title.clone(),
+        }
+    }
+}
+
+/// Description of `!queue`, listing the `upcoming` tracks shown out of `total` tracks
+/// waiting after the one being played.
+pub fn queue(current: &QueueEntry, upcoming: &[QueueEntry], total: usize) -> String {
+    let mut description = format!(
+        "Now playing: **{}**{}\n\nTotal tracks in queue: **{}**\n\n",
+        current.title,
+        current
+            .requester
+            .map(|requester| format!(" - <@{requester}>"))
+            .unwrap_or_default(),
+        total
+    );
+
+    for (idx, entry) in upcoming.iter().enumerate() {
+        description.push_str(&format!("{}. {}\n", idx + 1, entry.line()));
+    }
+
+    description
+}
+
+/// Appends up to 10 `items` to a reply, below a `{heading} N {noun}:` line.
+pub fn list(description: &mut String, heading: &str, noun: &str, items: &[String]) {
+    if items.is_empty() {
+        return;
+    }
+
+    if !description.ends_with('\n') {
+        description.push('\n');
+    }
+
+    description.push_str(&format!("\n{heading} {} {noun}:\n", items.len()));
+    for item in items.iter().take(10) {
+        description.push_str(&format!("- {item}\n"));
+    }
+
+    if items.len() > 10 {
+        description.push_str(&format!("- ...and {} more\n", items.len() - 10));
+    }
+}
+
+/// Reply to a skip vote that did not reach the required amount yet.
+pub fn vote_progress(title: &str, votes: usize, required: usize) -> String {
+    format!("Voted to skip **{title}**: {votes}/{required} votes")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(title: &str, requester: Option<u64>) -> QueueEntry {
+        QueueEntry {
+            title: String::from(title),
+            requester,
+        }
+    }
+
+    #[test]
+    fn renders_queue_with_requesters() {
+        let current = entry("Intro", Some(1));
+        let upcoming = [entry("Verse", None), entry("Chorus", Some(2))];
+
+        assert_eq!(
+            queue(&current, &upcoming, 2),
+            "Now playing: **Intro** - <@1>\n\nTotal tracks in queue: **2**\n\n\
+             1. Verse\n\
+             2. Chorus - <@2>\n"
+        );
+    }
+
+    #[test]
+    fn counts_tracks_not_displayed() {
+        let current = entry("Intro", None);
+        let upcoming = [entry("Verse", None)];
+
+        assert_eq!(
+            queue(&current, &upcoming, 2),
+            "Now playing: **Intro**\n\nTotal tracks in queue: **2**\n\n1. Verse\n"
+        );
+    }
+
+    #[test]
+    fn lists_at_most_ten_items() {
+        let items = (1..=12).map(|i| i.to_string()).collect::<Vec<_>>();
+        let mut description = String::from("Added 3 tracks");
+        list(&mut description, "Rejected", "tracks", &items);
+
+        let lines = description.lines().collect::<Vec<_>>();
+        assert_eq!(lines[2], "Rejected 12 tracks:");
+        assert_eq!(lines[3], "- 1");
+        assert_eq!(lines.last(), Some(&"- ...and 2 more"));
+        assert_eq!(lines.len(), 14);
+    }
+
+    #[test]
+    fn skips_empty_lists() {
+        let mut description = String::from("Added 3 tracks");
+        list(&mut description, "Rejected", "tracks", &[]);
+        assert_eq!(description, "Added 3 tracks");
+    }
+}
@@ -0,0 +1,318 @@
+use std::fmt;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+
+use reqwest::Client as HttpClient;
+use songbird::input::Compose;
+
+use crate::attachment::{self, Attachment};
+use crate::embed::EmbedBuilder;
+use crate::metadata_cache::MetadataCache;
+use crate::playlist;
+use crate::quota::{Quota, Rejection};
+use crate::render;
+use crate::stream;
+use crate::ytdlp::{self, YtDlp};
+
+/// What a `!play` query resolves to.
+#[derive(Clone, Debug)]
+pub enum Resolved {
+    Stream {
+        url: String,
+        title: String,
+        icy: bool,
+    },
+    Playlist(Vec<playlist::Metadata>),
+    Track(playlist::Metadata),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ResolveError {
+    Playlist,
+    NotFound,
+    /// URL pointing to a loopback, private or link-local host.
+    PrivateHost,
+}
+
+impl ResolveError {
+    pub fn description(&self, query: &str) -> String {
+        match self {
+            Self::Playlist => String::from("Could not load track from playlist"),
+            Self::NotFound => format!("Could not find track {query}"),
+            Self::PrivateHost => {
+                format!("Could not play {query}, private network hosts are not allowed")
+            }
+        }
+    }
+}
+
+/// Resolves `query` into a direct stream, a playlist or a single track, using the
+/// metadata cache when possible. URLs of private network hosts are rejected before any
+/// request is made, unless `allow_private_hosts`.
+pub async fn resolve(
+    http_client: &HttpClient,
+    ytdlp_config: &Arc<ytdlp::Config>,
+    metadata_cache: &MetadataCache,
+    allow_private_hosts: bool,
+    query: &str,
+) -> Result<Resolved, ResolveError> {
+    if query.starts_with("http") && !allow_private_hosts && stream::is_private_url(query).await {
+        return Err(ResolveError::PrivateHost);
+    }
+
+    if query.starts_with("http") {
+        if let Some(info) = stream::probe(http_client, query).await {
+            return Ok(Resolved::Stream {
+                url: query.to_string(),
+                title: info.name.unwrap_or_else(|| stream::name_from_url(query)),
+                icy: info.icy,
+            });
+        }
+    }
+
+    // FIXME: only works for youtube playlists, and it doesn't cover all cases
+    if query.starts_with("http") && query.contains("&list=") {
+        if let Some(metadata) = metadata_cache.get(query) {
+            return Ok(Resolved::Playlist(metadata));
+        }
+
+        return match playlist::query(ytdlp_config, query).await {
+            Ok(metadata) => {
+                metadata_cache.insert(query, metadata.clone());
+                Ok(Resolved::Playlist(metadata))
+            }
+            Err(err) => {
+                tracing::error!("Failed quering playlist metadata: {err}");
+                Err(ResolveError::Playlist)
+            }
+        };
+    }
+
+    if let Some(metadata) = metadata_cache.get(query).and_then(|m| m.into_iter().next()) {
+        return Ok(Resolved::Track(metadata));
+    }
+
+    let (http_client, ytdlp_config) = (http_client.clone(), Arc::clone(ytdlp_config));
+    let mut src = if query.starts_with("http") {
+        YtDlp::new(http_client, ytdlp_config, query.to_string())
+    } else {
+        YtDlp::new_search(http_client, ytdlp_config, query.to_string())
+    };
+
+    let metadata = match src.aux_metadata().await {
+        Ok(aux_metadata) => {
+            playlist::Metadata::from_aux(&aux_metadata).map(|metadata| playlist::Metadata {
+                live: src.is_live(),
+                ..metadata
+            })
+        }
+        Err(err) => {
+            tracing::error!("Failed resolving track metadata: {err}");
+            None
+        }
+    };
+
+    let metadata = metadata.ok_or(ResolveError::NotFound)?;
+    metadata_cache.insert(query, vec![metadata.clone()]);
+
+    Ok(Resolved::Track(metadata))
+}
+
+/// Keeps the tracks fitting `quota`, listing the rejected ones as `title: reason`.
+pub fn admit(quota: &mut Quota, resolved: Resolved) -> (Option<Resolved>, Vec<String>) {
+    let rejected = |title: &str, rejection: Rejection| format!("{title}: {rejection}");
+    let duration = |metadata: &playlist::Metadata| {
+        metadata
+            .duration
+            .and_then(|secs| Duration::try_from_secs_f64(secs).ok())
+    };
+
+    match resolved {
+        Resolved::Stream { ref title, .. } => match quota.admit(None, true) {
+            Ok(()) => (Some(resolved), Vec::new()),
+            Err(rejection) => (None, vec![rejected(title, rejection)]),
+        },
+        Resolved::Track(metadata) => match quota.admit(duration(&metadata), metadata.live) {
+            Ok(()) => (Some(Resolved::Track(metadata)), Vec::new()),
+            Err(rejection) => (None, vec![rejected(&metadata.title, rejection)]),
+        },
+        Resolved::Playlist(tracks) => {
+            let mut admitted = Vec::with_capacity(tracks.len());
+            let mut rejections = Vec::new();
+            for metadata in tracks {
+                match quota.admit(duration(&metadata), metadata.live) {
+                    Ok(()) => admitted.push(metadata),
+                    Err(rejection) => rejections.push(rejected(&metadata.title, rejection)),
+                }
+            }
+
+            let admitted = (!admitted.is_empty()).then_some(Resolved::Playlist(admitted));
+            (admitted, rejections)
+        }
+    }
+}
+
+/// Reply of commands whose tracks were all rejected by the queue limits.
+pub fn rejection(command: &str, rejected: &[String]) -> EmbedBuilder {
+    let description = match rejected {
+        [rejected] => format!("Could not add {rejected}"),
+        _ => {
+            let mut description = String::from("No tracks added to the queue");
+            render::list(&mut description, "Rejected", "tracks", rejected);
+            description
+        }
+    };
+
+    EmbedBuilder::error()
+        .title(command)
+        .description(description)
+}
+
+/// Reply of `!play` once its query is resolved and checked by [`admit`].
+pub fn reply(admitted: Option<&Resolved>, rejected: &[String]) -> EmbedBuilder {
+    let Some(resolved) = admitted else {
+        return rejection("!play", rejected);
+    };
+
+    let mut description = match resolved {
+        Resolved::Stream { title, .. } => format!("Stream {title} added to queue"),
+        Resolved::Playlist(tracks) => format!("{} tracks added to the queue", tracks.len()),
+        Resolved::Track(metadata) => format!("Track {} added to queue", metadata.title),
+    };
+    render::list(&mut description, "Rejected", "tracks", rejected);
+
+    EmbedBuilder::new().title("!play").description(description)
+}
+
+/// Queue `!play` adds tracks to, locked from the moment its quota is taken until the
+/// admitted tracks are enqueued.
+pub trait PlayQueue {
+    /// Room left in the queue for the author of `!play`.
+    fn quota(&mut self) -> impl Future<Output = Quota> + Send;
+
+    fn enqueue(&mut self, resolved: Resolved) -> impl Future<Output = ()> + Send;
+
+    fn enqueue_attachment(&mut self, attachment: &Attachment) -> impl Future<Output = ()> + Send;
+}
+
+/// What `!play` needs from the chat platform it runs on.
+pub trait PlayContext {
+    type JoinError: fmt::Display;
+
+    type Queue<'a>: PlayQueue
+    where
+        Self: 'a;
+
+    /// Joins the voice channel of the command author, or checks the bot is already there.
+    fn join(&mut self) -> impl Future<Output = Result<(), Self::JoinError>> + Send;
+
+    /// Locks the queue of the call joined by [`PlayContext::join`].
+    fn lock(&self) -> impl Future<Output = Self::Queue<'_>> + Send;
+
+    fn resolve(&self, query: &str) -> impl Future<Output = Result<Resolved, ResolveError>> + Send;
+
+    fn reply(&self, reply: EmbedBuilder) -> impl Future<Output = ()> + Send;
+}
+
+/// Runs `!play` with an optional `query` and the files uploaded along with it. Files are
+/// enqueued first, then the tracks `query` resolves to.
+pub async fn play_command<C: PlayContext>(
+    context: &mut C,
+    query: Option<&str>,
+    attachments: &[Attachment],
+    attachment_config: &attachment::Config,
+) {
+    if query.is_none() && attachments.is_empty() {
+        let reply = EmbedBuilder::error()
+            .title("!play")
+            .description("Missing music or URL argument");
+        return context.reply(reply).await;
+    }
+
+    let mut rejected = String::new();
+    let attachments = attachments
+        .iter()
+        .filter(|attachment| {
+            let content_type = attachment.content_type.as_deref();
+            match attachment::validate(content_type, attachment.size, attachment_config) {
+                Ok(_) => true,
+                Err(err) => {
+                    rejected.push_str(&format!("{}: {err}\n", attachment.filename));
+                    false
+                }
+            }
+        })
+        .collect::<Vec<_>>();
+
+    if !rejected.is_empty() {
+        let reply = EmbedBuilder::error()
+            .title("!play")
+            .description(format!("Could not play attachments:\n{rejected}"));
+        context.reply(reply).await;
+
+        if query.is_none() && attachments.is_empty() {
+            return;
+        }
+    }
+
+    if let Err(err) = context.join().await {
+        let reply = EmbedBuilder::error()
+            .title("!play")
+            .description(err.to_string());
+        return context.reply(reply).await;
+    }
+
+    if !attachments.is_empty() {
+        let mut description = String::new();
+        let mut queue = context.lock().await;
+        let mut quota = queue.quota().await;
+        for attachment in attachments {
+            if let Err(rejection) = quota.admit(None, false) {
+                description.push_str(&format!(
+                    "Track {} not added: {rejection}\n",
+                    attachment.filename
+                ));
+                continue;
+            }
+
+            queue.enqueue_attachment(attachment).await;
+            description.push_str(&format!("Track {} added to queue\n", attachment.filename));
+        }
+
+        std::mem::drop(queue);
+        let reply = EmbedBuilder::new().title("!play").description(description);
+        context.reply(reply).await;
+    }
+
+    if let Some(query) = query {
+        let reply = play(query, context.resolve(query), context.lock()).await;
+        context.reply(reply).await;
+    }
+}
+
+/// Reply of `!play` once `resolving` completes, enqueueing the tracks within the queue
+/// limits into the queue given by `lock`.
+pub async fn play<Q: PlayQueue>(
+    query: &str,
+    resolving: impl Future<Output = Result<Resolved, ResolveError>>,
+    lock: impl Future<Output = Q>,
+) -> EmbedBuilder {
+    match resolving.await {
+        Ok(resolved) => {
+            let mut queue = lock.await;
+            let mut quota = queue.quota().await;
+            let (admitted, rejected) = admit(&mut quota, resolved);
+
+            let reply = reply(admitted.as_ref(), &rejected);
+            if let Some(admitted) = admitted {
+                queue.enqueue(admitted).await;
+            }
+
+            reply
+        }
+        Err(err) => EmbedBuilder::error()
+            .title("!play")
+            .description(err.description(query)),
+    }
+}
@@ -0,0 +1,149 @@
+use std::collections::{BTreeMap, HashMap};
+use std::error;
+
+use serde::{Deserialize, Serialize};
+
+use crate::playlist::Metadata;
+use crate::storage::{Collection, Storage};
+
+/// Maximum amount of tracks kept in a single saved playlist.
+pub const MAX_TRACKS: usize = 500;
+
+/// Playlist saved by a user, shared with every member of its guild.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct SavedPlaylist {
+    pub name: String,
+    /// Id of the user who created the playlist, the only one allowed to modify it.
+    pub owner: u64,
+    pub tracks: Vec<Metadata>,
+}
+
+/// Saved playlists, keyed by guild id and lowercased playlist name.
+type Playlists = HashMap<u64, BTreeMap<String, SavedPlaylist>>;
+
+#[derive(Debug)]
+pub struct PlaylistStore(Collection<Playlists>);
+
+impl PlaylistStore {
+    pub async fn load(storage: Storage) -> Result<Self, Box<dyn error::Error + Send + Sync>> {
+        Collection::load(storage, "playlists").await.map(Self)
+    }
+
+    pub async fn get(&self, guild_id: u64, name: &str) -> Option<SavedPlaylist> {
+        let key = name.to_lowercase();
+        self.0
+            .read(|playlists| playlists.get(&guild_id)?.get(&key).cloned())
+            .await
+    }
+
+    pub async fn list(&self, guild_id: u64) -> Vec<SavedPlaylist> {
+        self.0
+            .read(|playlists| {
+                playlists
+                    .get(&guild_id)
+                    .map(|guild| guild.values().cloned().collect())
+                    .unwrap_or_default()
+            })
+            .await
+    }
+
+    /// Creates or replaces a playlist owned by `owner`, returning whether it was replaced.
+    pub async fn save(
+        &self,
+        guild_id: u64,
+        owner: u64,
+        name: &str,
+        mut tracks: Vec<Metadata>,
+    ) -> Result<bool, Box<dyn error::Error + Send + Sync>> {
+        let key = name.to_lowercase();
+        tracks.truncate(MAX_TRACKS);
+
+        self.0
+            .update(|playlists| {
+                let existing = playlists.get(&guild_id).and_then(|guild| guild.get(&key));
+                check_owner(existing, owner)?;
+
+                let guild = playlists.entry(guild_id).or_default();
+                let playlist = SavedPlaylist {
+                    name: name.to_string(),
+                    owner,
+                    tracks,
+                };
+
+                Ok(guild.insert(key, playlist).is_some())
+            })
+            .await?
+    }
+
+    /// Appends tracks to a playlist owned by `owner`, creating it if needed.
+    /// Returns the amount of tracks in the playlist afterwards.
+    pub async fn add(
+        &self,
+        guild_id: u64,
+        owner: u64,
+        name: &str,
+        tracks: Vec<Metadata>,
+    ) -> Result<usize, Box<dyn error::Error + Send + Sync>> {
+        let key = name.to_lowercase();
+
+        self.0
+            .update(|playlists| {
+                let existing = playlists.get(&guild_id).and_then(|guild| guild.get(&key));
+                check_owner(existing, owner)?;
+
+                let len = existing.map_or(0, |playlist| playlist.tracks.len());
+                if len + tracks.len() > MAX_TRACKS {
+                    return Err(format!("Playlists are limited to {MAX_TRACKS} tracks").into());
+                }
+
+                let guild = playlists.entry(guild_id).or_default();
+                let playlist = guild.entry(key).or_insert_with(|| SavedPlaylist {
+                    name: name.to_string(),
+                    owner,
+                    tracks: Vec::new(),
+                });
+
+                playlist.tracks.extend(tracks);
+                Ok(playlist.tracks.len())
+            })
+            .await?
+    }
+
+    /// Deletes a playlist owned by `owner`, returning it if it existed.
+    pub async fn delete(
+        &self,
+        guild_id: u64,
+        owner: u64,
+        name: &str,
+    ) -> Result<Option<SavedPlaylist>, Box<dyn error::Error + Send + Sync>> {
+        let key = name.to_lowercase();
+
+        self.0
+            .update(|playlists| {
+                let Some(guild) = playlists.get_mut(&guild_id) else {
+                    return Ok(None);
+                };
+
+                check_owner(guild.get(&key), owner)?;
+                let removed = guild.remove(&key);
+                if guild.is_empty() {
+                    playlists.remove(&guild_id);
+                }
+
+                Ok(removed)
+            })
+            .await?
+    }
+}
+
+fn check_owner(
+    playlist: Option<&SavedPlaylist>,
+    owner: u64,
+) -> Result<(), Box<dyn error::Error + Send + Sync>> {
+    match playlist {
+        Some(playlist) if playlist.owner != owner => {
+            Err(format!("Playlist **{}** belongs to another user", playlist.name).into())
+        }
+        _ => Ok(()),
+    }
+}
@@ -0,0 +1,156 @@
+use std::collections::HashMap;
+use std::error;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use serde::{Deserialize, Serialize};
+
+use crate::storage::{Collection, Storage};
+use crate::TrackSource;
+
+/// Snapshot of a guild playback, restored when the bot starts again.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Session {
+    pub voice_channel: u64,
+    /// Channel where the bot was last called from, notified when the session is restored.
+    #[serde(default)]
+    pub text_channel: Option<u64>,
+    /// Enqueued tracks, starting from the one being played.
+    pub tracks: Vec<SessionTrack>,
+    /// Playback position of the first track, in seconds.
+    #[serde(default)]
+    pub position: f64,
+    #[serde(default = "default_volume")]
+    pub volume: f32,
+    /// Whether the first track was looping.
+    #[serde(default)]
+    pub looping: bool,
+    /// Amount of uploaded files left out, since their URLs expire.
+    #[serde(default)]
+    pub left_out: usize,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct SessionTrack {
+    pub source: TrackSource,
+    pub url: String,
+    pub title: String,
+    /// Id of the user who enqueued the track.
+    pub requester: u64,
+}
+
+impl Session {
+    /// Uploaded files that can not be played again, including those saved before they were
+    /// left out of sessions.
+    pub fn expired_attachments(&self) -> usize {
+        let saved = self
+            .tracks
+            .iter()
+            .filter(|track| track.source == TrackSource::Attachment)
+            .count();
+
+        self.left_out + saved
+    }
+}
+
+fn default_volume() -> f32 {
+    1.0
+}
+
+/// Sessions of every guild with an active call, keyed by guild id.
+#[derive(Debug)]
+pub struct SessionStore {
+    sessions: Collection<HashMap<u64, Session>>,
+    /// Sessions of guilds whose voice channel was left empty, kept for `!resume-session`.
+    parked: Collection<HashMap<u64, Session>>,
+    restored: AtomicBool,
+    /// Set once the final snapshot is saved on shutdown, so leaving voice channels
+    /// afterwards does not overwrite it.
+    flushed: AtomicBool,
+}
+
+impl SessionStore {
+    pub async fn load(storage: Storage) -> Result<Self, Box<dyn error::Error + Send + Sync>> {
+        Ok(Self {
+            sessions: Collection::load(storage.clone(), "sessions").await?,
+            parked: Collection::load(storage, "parked_sessions").await?,
+            restored: AtomicBool::new(false),
+            flushed: AtomicBool::new(false),
+        })
+    }
+
+    /// Takes the saved sessions to be restored, only once per process since `ready`
+    /// is also dispatched on gateway reconnections.
+    pub async fn take_for_restore(&self) -> Option<HashMap<u64, Session>> {
+        if self.restored.swap(true, Ordering::SeqCst) {
+            return None;
+        }
+
+        self.sessions.read(|sessions| Some(sessions.clone())).await
+    }
+
+    /// Replaces all saved sessions with the current ones.
+    pub async fn save(
+        &self,
+        sessions: HashMap<u64, Session>,
+    ) -> Result<(), Box<dyn error::Error + Send + Sync>> {
+        if self.flushed.load(Ordering::SeqCst) {
+            return Ok(());
+        }
+
+        self.sessions.update(|saved| *saved = sessions).await
+    }
+
+    /// Saves the final sessions, ignoring any later [`SessionStore::save`].
+    pub async fn flush(
+        &self,
+        sessions: HashMap<u64, Session>,
+    ) -> Result<(), Box<dyn error::Error + Send + Sync>> {
+        let result = self.save(sessions).await;
+        self.flushed.store(true, Ordering::SeqCst);
+
+        result
+    }
+
+    /// Keeps the session of a guild leaving its voice channel, replacing any previous one.
+    pub async fn park(
+        &self,
+        guild_id: u64,
+        session: Session,
+    ) -> Result<(), Box<dyn error::Error + Send + Sync>> {
+        self.parked
+            .update(|parked| {
+                parked.insert(guild_id, session);
+            })
+            .await
+    }
+
+    /// Takes the session kept by [`SessionStore::park`], if any.
+    pub async fn unpark(
+        &self,
+        guild_id: u64,
+    ) -> Result<Option<Session>, Box<dyn error::Error + Send + Sync>> {
+        self.parked.update(|parked| parked.remove(&guild_id)).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_expired_attachments_of_legacy_sessions() {
+        let session: Session = serde_json::from_str(
+            r#"{
+                "voice_channel": 1,
+                "tracks": [
+                    {"source": "attachment", "url": "https://cdn/a.mp3", "title": "a.mp3", "requester": 2},
+                    {"source": "yt_dlp", "url": "https://example.com/b", "title": "b", "requester": 2}
+                ]
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(session.left_out, 0);
+        assert_eq!(session.expired_attachments(), 1);
+    }
+}
@@ -0,0 +1,140 @@
+use std::error;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use tokio::sync::Mutex;
+
+/// Directory where persistent data is stored, one JSON file per collection.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Storage {
+    dir: PathBuf,
+}
+
+impl Storage {
+    pub async fn new(dir: PathBuf) -> Result<Self, Box<dyn error::Error + Send + Sync>> {
+        tokio::fs::create_dir_all(&dir).await?;
+        Ok(Self { dir })
+    }
+
+    /// Loads collection `name`, or its default value if never saved.
+    pub async fn load<T: DeserializeOwned + Default>(
+        &self,
+        name: &str,
+    ) -> Result<T, Box<dyn error::Error + Send + Sync>> {
+        match tokio::fs::read(self.path(name)).await {
+            Ok(content) => Ok(serde_json::from_slice(&content)?),
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(T::default()),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    /// Saves collection `name`, replacing its file atomically.
+    pub async fn save<T: Serialize>(
+        &self,
+        name: &str,
+        value: &T,
+    ) -> Result<(), Box<dyn error::Error + Send + Sync>> {
+        let content = serde_json::to_vec_pretty(value)?;
+        write_atomic(&self.path(name), &content).await?;
+        Ok(())
+    }
+
+    fn path(&self, name: &str) -> PathBuf {
+        self.dir.join(format!("{name}.json"))
+    }
+}
+
+/// Replaces the file at `path` by writing `content` to a sibling temporary file first, so
+/// that crashes never leave it half written.
+pub async fn write_atomic(path: &Path, content: &[u8]) -> std::io::Result<()> {
+    let mut tmp_path = path.as_os_str().to_owned();
+    tmp_path.push(".tmp");
+
+    tokio::fs::write(&tmp_path, content).await?;
+    tokio::fs::rename(&tmp_path, path).await
+}
+
+/// In-memory value mirrored to a [`Storage`] collection on every update.
+#[derive(Debug)]
+pub struct Collection<T> {
+    storage: Storage,
+    name: &'static str,
+    value: Mutex<T>,
+}
+
+impl<T: Serialize + DeserializeOwned + Default + Clone> Collection<T> {
+    pub async fn load(
+        storage: Storage,
+        name: &'static str,
+    ) -> Result<Self, Box<dyn error::Error + Send + Sync>> {
+        let value = storage.load(name).await?;
+
+        Ok(Self {
+            storage,
+            name,
+            value: Mutex::new(value),
+        })
+    }
+
+    pub async fn read<R>(&self, f: impl FnOnce(&T) -> R) -> R {
+        f(&*self.value.lock().await)
+    }
+
+    /// Updates the value, persisting it before returning `f` result. The value is left
+    /// untouched if saving fails.
+    pub async fn update<R>(
+        &self,
+        f: impl FnOnce(&mut T) -> R,
+    ) -> Result<R, Box<dyn error::Error + Send + Sync>> {
+        let mut value = self.value.lock().await;
+        let mut updated = value.clone();
+        let result = f(&mut updated);
+        self.storage.save(self.name, &updated).await?;
+        *value = updated;
+
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn persists_updates() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = Storage::new(dir.path().to_path_buf()).await.unwrap();
+        let numbers = Collection::<Vec<u32>>::load(storage.clone(), "numbers")
+            .await
+            .unwrap();
+
+        let len = numbers
+            .update(|numbers| {
+                numbers.push(1);
+                numbers.len()
+            })
+            .await
+            .unwrap();
+        assert_eq!(len, 1);
+
+        let saved = storage.load::<Vec<u32>>("numbers").await.unwrap();
+        assert_eq!(saved, [1]);
+    }
+
+    #[tokio::test]
+    async fn keeps_value_when_saving_fails() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = Storage::new(dir.path().join("data")).await.unwrap();
+        let numbers = Collection::<Vec<u32>>::load(storage, "numbers")
+            .await
+            .unwrap();
+
+        std::fs::remove_dir(dir.path().join("data")).unwrap();
+        let update = numbers.update(|numbers| numbers.push(1)).await;
+
+        assert!(update.is_err());
+        assert!(numbers.read(Vec::is_empty).await);
+    }
+}
@@ -0,0 +1,492 @@
+use std::error;
+use std::io;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
+
+use hyper::client::connect::dns::Name;
+use reqwest::dns::{Addrs, Resolve, Resolving};
+use reqwest::header::{CONTENT_TYPE, RANGE};
+use reqwest::redirect::Policy;
+use reqwest::{Client as HttpClient, ClientBuilder};
+use songbird::input::core::io::MediaSource;
+use songbird::input::{AudioStream, AudioStreamError, Compose, HttpRequest, Input};
+use songbird::{Event, EventContext};
+use tokio::task::AbortHandle;
+
+const AUDIO_EXTENSIONS: [&str; 8] = ["mp3", "ogg", "oga", "opus", "aac", "m4a", "flac", "wav"];
+
+/// Hosts always resolved through `yt-dlp`, so they aren't probed as direct streams.
+const YTDLP_HOSTS: [&str; 5] = [
+    "youtube.com",
+    "youtu.be",
+    "soundcloud.com",
+    "bandcamp.com",
+    "twitch.tv",
+];
+
+/// Time given to stream servers to answer probes, so unresponsive hosts don't hold `!play`.
+const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Details of a direct audio stream, such as an internet radio.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct StreamInfo {
+    /// Station name sent in `icy-name` header, if any.
+    pub name: Option<String>,
+    /// Whether the server announced support for ICY metadata.
+    pub icy: bool,
+}
+
+/// Checks if `url` points directly to audio instead of a page `yt-dlp` must scrape,
+/// first by its extension, then by the response headers.
+pub async fn probe(client: &HttpClient, url: &str) -> Option<StreamInfo> {
+    let Ok(parsed) = reqwest::Url::parse(url) else {
+        return None;
+    };
+
+    let host = parsed.host_str().unwrap_or_default();
+    if YTDLP_HOSTS
+        .iter()
+        .any(|h| host == *h || host.ends_with(&format!(".{h}")))
+    {
+        return None;
+    }
+
+    let has_audio_extension = parsed
+        .path()
+        .rsplit_once('.')
+        .is_some_and(|(_, ext)| AUDIO_EXTENSIONS.contains(&ext.to_lowercase().as_str()));
+
+    // Icecast servers often reject HEAD requests, so headers are read from a GET
+    // request for the first byte, dropped before consuming its body
+    let request = client
+        .get(url)
+        .header("Icy-MetaData", "1")
+        .header(RANGE, "bytes=0-0")
+        .timeout(PROBE_TIMEOUT);
+
+    let response = match request.send().await {
+        Ok(response) if response.status().is_success() => response,
+        Ok(_) | Err(_) if has_audio_extension => return Some(StreamInfo::default()),
+        Ok(_) | Err(_) => return None,
+    };
+
+    let headers = response.headers();
+    let icy = headers.contains_key("icy-metaint")
+        || headers.contains_key("icy-name")
+        || headers.contains_key("icy-br");
+
+    let content_type = headers
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default()
+        .to_lowercase();
+
+    let is_audio = content_type.starts_with("audio/")
+        || content_type == "application/ogg"
+        || content_type == "application/octet-stream" && has_audio_extension;
+
+    if !icy && !is_audio && !has_audio_extension {
+        return None;
+    }
+
+    let name = headers
+        .get("icy-name")
+        .and_then(|value| value.to_str().ok())
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty());
+
+    Some(StreamInfo { name, icy })
+}
+
+/// Whether `url` points to a loopback, private or link-local host, which users must not
+/// make the bot request. Hosts failing to resolve are not considered private, since
+/// requesting them fails anyway.
+pub async fn is_private_url(url: &str) -> bool {
+    let Ok(parsed) = reqwest::Url::parse(url) else {
+        return false;
+    };
+
+    let Some(host) = parsed.host_str() else {
+        return false;
+    };
+
+    // IPv6 hosts are written between brackets
+    if let Ok(ip) = host.trim_matches(['[', ']']).parse::<IpAddr>() {
+        return is_private_ip(ip);
+    }
+
+    let port = parsed.port_or_known_default().unwrap_or(80);
+    match tokio::net::lookup_host(format!("{host}:{port}")).await {
+        Ok(mut addrs) => addrs.any(|addr| is_private_ip(addr.ip())),
+        Err(_) => false,
+    }
+}
+
+/// Restricts the client built by `builder` to public hosts, covering redirects and hosts
+/// resolving to other addresses once checked by [`is_private_url`]. `allowed_host`, such as
+/// a proxy running alongside the bot, stays reachable.
+pub fn public_hosts_only(builder: ClientBuilder, allowed_host: Option<String>) -> ClientBuilder {
+    builder
+        .dns_resolver(Arc::new(PublicResolver { allowed_host }))
+        .redirect(Policy::custom(|attempt| {
+            // hostnames go through `PublicResolver`, while IP addresses are never resolved
+            let private = attempt
+                .url()
+                .host_str()
+                .and_then(|host| host.trim_matches(['[', ']']).parse::<IpAddr>().ok())
+                .is_some_and(is_private_ip);
+
+            if private {
+                attempt.error("redirected to a private network host")
+            } else if attempt.previous().len() >= MAX_REDIRECTS {
+                attempt.error("too many redirects")
+            } else {
+                attempt.follow()
+            }
+        }))
+}
+
+/// Same limit as the default reqwest redirect policy.
+const MAX_REDIRECTS: usize = 10;
+
+/// DNS resolver leaving private addresses out of its answers, so connections are made to
+/// the very addresses checked.
+struct PublicResolver {
+    allowed_host: Option<String>,
+}
+
+impl Resolve for PublicResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let allowed = self.allowed_host.as_deref() == Some(name.as_str());
+        let host = name.as_str().to_string();
+
+        Box::pin(async move {
+            let addrs = tokio::net::lookup_host((host.as_str(), 0))
+                .await?
+                .filter(|addr| allowed || !is_private_ip(addr.ip()))
+                .collect::<Vec<SocketAddr>>();
+
+            if addrs.is_empty() {
+                let err = io::Error::new(
+                    io::ErrorKind::PermissionDenied,
+                    format!("{host} is a private network host"),
+                );
+                return Err(Box::new(err) as Box<dyn error::Error + Send + Sync>);
+            }
+
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+/// Whether `ip` belongs to a network not reachable from the internet.
+pub fn is_private_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+            ip.is_private()
+                || ip.is_loopback()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                // 0.0.0.0/8 and carrier-grade NAT 100.64.0.0/10
+                || a == 0
+                || a == 100 && (64..128).contains(&b)
+        }
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_private_ip(IpAddr::V4(ip)),
+            None => {
+                let first = ip.segments()[0];
+                ip.is_loopback()
+                    || ip.is_unspecified()
+                    // unique local fc00::/7 and link-local fe80::/10
+                    || first & 0xfe00 == 0xfc00
+                    || first & 0xffc0 == 0xfe80
+            }
+        },
+    }
+}
+
+/// Fallback name of a stream without `icy-name` header, using the last URL path segment.
+pub fn name_from_url(url: &str) -> String {
+    let Ok(parsed) = reqwest::Url::parse(url) else {
+        return url.to_string();
+    };
+
+    let segment = parsed
+        .path_segments()
+        .and_then(|mut segments| segments.next_back())
+        .filter(|segment| !segment.is_empty());
+
+    match (parsed.host_str(), segment) {
+        (Some(host), Some(segment)) => format!("{host}/{segment}"),
+        (Some(host), None) => host.to_string(),
+        _ => url.to_string(),
+    }
+}
+
+/// HTTP stream which waits before connecting, used to back off reconnections to radios.
+#[derive(Clone, Debug)]
+pub struct DelayedRequest {
+    request: HttpRequest,
+    delay: Duration,
+}
+
+impl DelayedRequest {
+    pub fn new(client: HttpClient, url: String, delay: Duration) -> Self {
+        Self {
+            request: HttpRequest::new(client, url),
+            delay,
+        }
+    }
+}
+
+impl From<DelayedRequest> for Input {
+    fn from(val: DelayedRequest) -> Self {
+        Input::Lazy(Box::new(val))
+    }
+}
+
+#[async_trait::async_trait]
+impl Compose for DelayedRequest {
+    fn create(&mut self) -> Result<AudioStream<Box<dyn MediaSource>>, AudioStreamError> {
+        Err(AudioStreamError::Unsupported)
+    }
+
+    async fn create_async(
+        &mut self,
+    ) -> Result<AudioStream<Box<dyn MediaSource>>, AudioStreamError> {
+        tokio::time::sleep(self.delay).await;
+        self.request.create_async().await
+    }
+
+    fn should_create_async(&self) -> bool {
+        true
+    }
+}
+
+/// Keeps track of the song currently played by a radio station through its ICY metadata.
+///
+/// A separate connection is used to read metadata, since songbird cannot decode audio
+/// interleaved with it.
+#[derive(Debug)]
+pub struct TitleWatcher {
+    client: HttpClient,
+    url: String,
+    title: Arc<RwLock<Option<String>>>,
+    task: Mutex<Option<AbortHandle>>,
+}
+
+impl TitleWatcher {
+    pub fn new(client: HttpClient, url: String) -> Self {
+        Self {
+            client,
+            url,
+            title: Arc::new(RwLock::new(None)),
+            task: Mutex::new(None),
+        }
+    }
+
+    /// Last song announced by the station, if any.
+    pub fn title(&self) -> Option<String> {
+        self.title
+            .read()
+            .expect("Stream title lock poisoned")
+            .clone()
+    }
+
+    /// Starts reading metadata in background, unless already started.
+    pub fn start(&self) {
+        let mut task = self.task.lock().expect("Stream task lock poisoned");
+        if task.as_ref().is_some_and(|task| !task.is_finished()) {
+            return;
+        }
+
+        let handle = tokio::spawn(watch_title(
+            self.client.clone(),
+            self.url.clone(),
+            self.title.clone(),
+        ));
+
+        *task = Some(handle.abort_handle());
+    }
+
+    pub fn stop(&self) {
+        if let Some(task) = self.task.lock().expect("Stream task lock poisoned").take() {
+            task.abort();
+        }
+    }
+}
+
+impl Drop for TitleWatcher {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// Starts the [`TitleWatcher`] when its track starts playing, and stops it when the track ends.
+pub struct TitleWatcherHandler {
+    pub watcher: Arc<TitleWatcher>,
+    pub start: bool,
+}
+
+#[async_trait::async_trait]
+impl songbird::EventHandler for TitleWatcherHandler {
+    async fn act(&self, _ctx: &EventContext<'_>) -> Option<Event> {
+        if self.start {
+            self.watcher.start();
+        } else {
+            self.watcher.stop();
+        }
+
+        None
+    }
+}
+
+async fn watch_title(client: HttpClient, url: String, title: Arc<RwLock<Option<String>>>) {
+    let request = client.get(&url).header("Icy-MetaData", "1");
+    let mut response = match request.send().await {
+        Ok(response) => response,
+        Err(err) => return tracing::error!("Failed reading stream metadata: {err}"),
+    };
+
+    let metaint = response
+        .headers()
+        .get("icy-metaint")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.trim().parse::<usize>().ok());
+
+    let Some(metaint) = metaint.filter(|metaint| *metaint > 0) else {
+        return tracing::info!("Stream {url} does not send ICY metadata");
+    };
+
+    let mut parser = IcyParser::new(metaint);
+    while let Ok(Some(chunk)) = response.chunk().await {
+        for metadata in parser.feed(&chunk) {
+            if let Some(stream_title) = parse_stream_title(&metadata) {
+                *title.write().expect("Stream title lock poisoned") = Some(stream_title);
+            }
+        }
+    }
+}
+
+/// Splits an ICY stream into its metadata blocks, skipping audio data.
+#[derive(Debug)]
+struct IcyParser {
+    metaint: usize,
+    state: IcyState,
+}
+
+#[derive(Debug)]
+enum IcyState {
+    Audio { remaining: usize },
+    Length,
+    Metadata { remaining: usize, buf: Vec<u8> },
+}
+
+impl IcyParser {
+    fn new(metaint: usize) -> Self {
+        Self {
+            metaint,
+            state: IcyState::Audio { remaining: metaint },
+        }
+    }
+
+    fn feed(&mut self, mut data: &[u8]) -> Vec<String> {
+        let mut metadata = Vec::new();
+
+        while !data.is_empty() {
+            match &mut self.state {
+                IcyState::Audio { remaining } => {
+                    let skipped = (*remaining).min(data.len());
+                    *remaining -= skipped;
+                    data = &data[skipped..];
+
+                    if *remaining == 0 {
+                        self.state = IcyState::Length;
+                    }
+                }
+                IcyState::Length => {
+                    let len = usize::from(data[0]) * 16;
+                    data = &data[1..];
+
+                    self.state = if len == 0 {
+                        IcyState::Audio {
+                            remaining: self.metaint,
+                        }
+                    } else {
+                        IcyState::Metadata {
+                            remaining: len,
+                            buf: Vec::with_capacity(len),
+                        }
+                    };
+                }
+                IcyState::Metadata { remaining, buf } => {
+                    let read = (*remaining).min(data.len());
+                    buf.extend_from_slice(&data[..read]);
+                    *remaining -= read;
+                    data = &data[read..];
+
+                    if *remaining == 0 {
+                        let text = String::from_utf8_lossy(buf);
+                        metadata.push(text.trim_end_matches('\0').to_string());
+
+                        self.state = IcyState::Audio {
+                            remaining: self.metaint,
+                        };
+                    }
+                }
+            }
+        }
+
+        metadata
+    }
+}
+
+/// Extracts the song from metadata like `StreamTitle='Artist - Song';StreamUrl='';`.
+fn parse_stream_title(metadata: &str) -> Option<String> {
+    let start = metadata.find("StreamTitle='")? + "StreamTitle='".len();
+    let rest = &metadata[start..];
+    let end = rest.find("';").unwrap_or(rest.trim_end_matches('\'').len());
+
+    let title = rest[..end].trim();
+    (!title.is_empty()).then(|| title.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_private_addresses() {
+        for ip in [
+            "127.0.0.1",
+            "10.1.2.3",
+            "172.16.0.1",
+            "192.168.1.10",
+            "169.254.169.254",
+            "100.64.0.1",
+            "0.0.0.0",
+            "::1",
+            "fd00::1",
+            "fe80::1",
+            "::ffff:192.168.0.1",
+        ] {
+            assert!(is_private_ip(ip.parse().unwrap()), "{ip} should be private");
+        }
+
+        for ip in ["8.8.8.8", "100.128.0.1", "2606:4700::1111"] {
+            assert!(!is_private_ip(ip.parse().unwrap()), "{ip} should be public");
+        }
+    }
+
+    #[tokio::test]
+    async fn detects_private_urls() {
+        assert!(is_private_url("http://localhost:8000/stream").await);
+        assert!(is_private_url("http://[::1]/radio.mp3").await);
+        assert!(is_private_url("http://192.168.0.2/").await);
+        assert!(!is_private_url("https://93.184.215.14/song.mp3").await);
+        assert!(!is_private_url("not a url").await);
+    }
+}
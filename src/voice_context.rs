@@ -0,0 +1,142 @@
+use std::fmt;
+use std::sync::Arc;
+
+use serenity::all::CreateMessage;
+use serenity::client::Context;
+use serenity::framework::standard::CommandResult;
+use serenity::model::channel::Message;
+use serenity::model::id::{ChannelId, GuildId};
+use serenity::prelude::{Mentionable, Mutex};
+use songbird::Call;
+
+use rina::embed::EmbedBuilder;
+
+use crate::discord_embed::BuildEmbed;
+
+/// Call a command works with, shared by its author and the bot.
+#[derive(Clone)]
+pub struct VoiceCommandContext {
+    pub guild_id: GuildId,
+    /// Voice channel of both the author and the bot.
+    pub channel_id: ChannelId,
+    pub call: Arc<Mutex<Call>>,
+    /// Channel the command was sent from.
+    pub text_channel_id: ChannelId,
+    /// Whether the bot joined the voice channel to run the command.
+    pub joined: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VoiceCommandError {
+    AuthorNotConnected,
+    BotNotConnected,
+    DifferentChannel,
+    JoinFailed(ChannelId),
+}
+
+impl fmt::Display for VoiceCommandError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::AuthorNotConnected => write!(f, "User not in a voice channel"),
+            Self::BotNotConnected => write!(f, "I'm not in a voice channel"),
+            Self::DifferentChannel => write!(f, "User not in the same voice channel"),
+            Self::JoinFailed(channel_id) => {
+                write!(f, "Could not join voice channel {}", channel_id.mention())
+            }
+        }
+    }
+}
+
+impl std::error::Error for VoiceCommandError {}
+
+impl VoiceCommandError {
+    /// Replies to the command with an error embed titled `title`, as in `!skip`.
+    pub async fn reply(self, ctx: &Context, msg: &Message, title: &str) -> CommandResult {
+        let style = crate::get_embed_style(ctx).await;
+        let error = EmbedBuilder::error()
+            .title(title)
+            .description(self.to_string())
+            .build(&style);
+
+        let message = CreateMessage::new().add_embed(error);
+        crate::check_msg(msg.channel_id.send_message(&ctx.http, message).await);
+
+        Ok(())
+    }
+}
+
+impl VoiceCommandContext {
+    /// Resolves the call of the bot, as long as the author is in the same voice channel.
+    pub async fn current(ctx: &Context, msg: &Message) -> Result<Self, VoiceCommandError> {
+        let (guild_id, author_channel_id) = author_voice_channel(ctx, msg);
+        let manager = songbird::get(ctx)
+            .await
+            .expect("Expected songbird in context");
+
+        let call = manager
+            .get(guild_id)
+            .ok_or(VoiceCommandError::BotNotConnected)?;
+        let channel_id = author_channel_id.ok_or(VoiceCommandError::AuthorNotConnected)?;
+
+        Self::shared(guild_id, channel_id, call, msg, false).await
+    }
+
+    /// Like [`VoiceCommandContext::current`], but joining the author voice channel first
+    /// when the bot is not in one.
+    pub async fn join(ctx: &Context, msg: &Message) -> Result<Self, VoiceCommandError> {
+        let (guild_id, author_channel_id) = author_voice_channel(ctx, msg);
+        let channel_id = author_channel_id.ok_or(VoiceCommandError::AuthorNotConnected)?;
+        let manager = songbird::get(ctx)
+            .await
+            .expect("Expected songbird in context");
+
+        if let Some(call) = manager.get(guild_id) {
+            return Self::shared(guild_id, channel_id, call, msg, false).await;
+        }
+
+        let call = match manager.join(guild_id, channel_id).await {
+            Ok(call) => call,
+            Err(err) => {
+                tracing::error!("Failed joining voice channel {channel_id}: {err}");
+                return Err(VoiceCommandError::JoinFailed(channel_id));
+            }
+        };
+
+        crate::register_call_events(ctx, guild_id, &call).await;
+        crate::set_text_channel(ctx, guild_id, msg.channel_id).await;
+
+        Self::shared(guild_id, channel_id, call, msg, true).await
+    }
+
+    async fn shared(
+        guild_id: GuildId,
+        channel_id: ChannelId,
+        call: Arc<Mutex<Call>>,
+        msg: &Message,
+        joined: bool,
+    ) -> Result<Self, VoiceCommandError> {
+        let current_channel = call.lock().await.current_channel();
+        if current_channel != Some(songbird::id::ChannelId::from(channel_id)) {
+            return Err(VoiceCommandError::DifferentChannel);
+        }
+
+        Ok(Self {
+            guild_id,
+            channel_id,
+            call,
+            text_channel_id: msg.channel_id,
+            joined,
+        })
+    }
+}
+
+/// Guild of the message and the voice channel its author is connected to, if any.
+fn author_voice_channel(ctx: &Context, msg: &Message) -> (GuildId, Option<ChannelId>) {
+    let guild = msg.guild(&ctx.cache).expect("Expected guild to be defined");
+    let channel_id = guild
+        .voice_states
+        .get(&msg.author.id)
+        .and_then(|vs| vs.channel_id);
+
+    (guild.id, channel_id)
+}
@@ -0,0 +1,67 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+
+use songbird::tracks::TrackHandle;
+
+/// Votes to skip the track being played, per guild.
+#[derive(Debug, Default)]
+pub struct SkipVotes(Mutex<HashMap<u64, Ballot>>);
+
+#[derive(Debug)]
+struct Ballot {
+    track: TrackHandle,
+    voters: HashSet<u64>,
+}
+
+impl SkipVotes {
+    /// Registers the vote of `user_id` to skip `track`, returning how many of `listeners`
+    /// voted for it. Votes given to a previous track are discarded.
+    pub fn vote(
+        &self,
+        guild_id: u64,
+        track: &TrackHandle,
+        user_id: u64,
+        listeners: &HashSet<u64>,
+    ) -> usize {
+        let mut ballots = self.0.lock().expect("Skip votes lock poisoned");
+        let ballot = ballots.entry(guild_id).or_insert_with(|| Ballot {
+            track: track.clone(),
+            voters: HashSet::new(),
+        });
+
+        if ballot.track.uuid() != track.uuid() {
+            ballot.track = track.clone();
+            ballot.voters.clear();
+        }
+
+        ballot.voters.insert(user_id);
+        ballot.voters.intersection(listeners).count()
+    }
+
+    pub fn clear(&self, guild_id: u64) {
+        self.0
+            .lock()
+            .expect("Skip votes lock poisoned")
+            .remove(&guild_id);
+    }
+}
+
+/// Votes needed to skip a track, given the amount of listeners and the percentage of them
+/// that must agree. At least one vote is always needed.
+pub fn required_votes(listeners: usize, percent: u8) -> usize {
+    (listeners * usize::from(percent)).div_ceil(100).max(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn requires_at_least_one_vote() {
+        assert_eq!(required_votes(0, 50), 1);
+        assert_eq!(required_votes(1, 1), 1);
+        assert_eq!(required_votes(3, 50), 2);
+        assert_eq!(required_votes(4, 50), 2);
+        assert_eq!(required_votes(5, 100), 5);
+    }
+}
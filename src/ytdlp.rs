@@ -0,0 +1,293 @@
+use std::collections::HashMap;
+use std::error;
+use std::io::{BufRead, ErrorKind};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use reqwest::Client as HttpClient;
+use serde::Deserialize;
+use songbird::input::core::io::MediaSource;
+use songbird::input::{
+    AudioStream, AudioStreamError, AuxMetadata, Compose, File, HttpRequest, Input,
+};
+use tokio::process::Command;
+
+use crate::audio_cache::AudioCache;
+
+const DEFAULT_PROGRAM: &str = "yt-dlp";
+const DEFAULT_FORMAT: &str = "ba[abr>0][vcodec=none]/best";
+
+/// Settings used for every `yt-dlp` invocation, either when querying playlists
+/// or when resolving single tracks.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Config {
+    pub program: String,
+    pub format: String,
+    pub cookies: Option<PathBuf>,
+    pub proxy: Option<String>,
+    pub extra_args: Vec<String>,
+}
+
+impl Config {
+    /// Arguments shared by all `yt-dlp` invocations, user arguments included.
+    pub fn args(&self) -> Vec<String> {
+        let mut args = vec![String::from("-f"), self.format.clone()];
+
+        if let Some(cookies) = &self.cookies {
+            args.push(String::from("--cookies"));
+            args.push(cookies.to_string_lossy().into_owned());
+        }
+
+        if let Some(proxy) = &self.proxy {
+            args.push(String::from("--proxy"));
+            args.push(proxy.clone());
+        }
+
+        args.extend(self.extra_args.iter().cloned());
+        args
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            program: String::from(DEFAULT_PROGRAM),
+            format: String::from(DEFAULT_FORMAT),
+            cookies: None,
+            proxy: None,
+            extra_args: Vec::new(),
+        }
+    }
+}
+
+/// Runs `yt-dlp` in JSON mode against `target`, parsing each output line.
+pub async fn run(
+    config: &Config,
+    target: &str,
+    args: &[&str],
+) -> Result<Vec<Output>, Box<dyn error::Error + Send + Sync>> {
+    let output = Command::new(&config.program)
+        .arg("-j")
+        .arg(target)
+        .args(config.args())
+        .args(args)
+        .output()
+        .await
+        .map_err(|err| spawn_error(config, err))?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("{} failed: {}", config.program, stderr.trim()).into());
+    }
+
+    let outputs = output
+        .stdout
+        .lines()
+        .map_while(|line| line.ok())
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| serde_json::from_str(&line))
+        .collect::<Result<Vec<Output>, serde_json::Error>>()?;
+
+    Ok(outputs)
+}
+
+/// Downloads the audio of `url` into `output`, which may contain `yt-dlp` output
+/// template fields such as `%(ext)s`.
+pub async fn download(
+    config: &Config,
+    url: &str,
+    output: &str,
+) -> Result<(), Box<dyn error::Error + Send + Sync>> {
+    let output = Command::new(&config.program)
+        .arg(url)
+        .args(config.args())
+        .args(["--no-playlist", "--no-progress", "-o", output])
+        .output()
+        .await
+        .map_err(|err| spawn_error(config, err))?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("{} failed: {}", config.program, stderr.trim()).into());
+    }
+
+    Ok(())
+}
+
+/// Error of `yt-dlp` failing to start, naming the configured executable when missing.
+fn spawn_error(config: &Config, err: std::io::Error) -> Box<dyn error::Error + Send + Sync> {
+    if err.kind() == ErrorKind::NotFound {
+        format!("Could not find executable {}", config.program).into()
+    } else {
+        Box::new(err)
+    }
+}
+
+/// Subset of fields printed by `yt-dlp -j`.
+#[derive(Clone, Debug, Deserialize)]
+pub struct Output {
+    pub url: String,
+    pub webpage_url: Option<String>,
+    pub title: Option<String>,
+    pub duration: Option<f64>,
+    pub is_live: Option<bool>,
+    pub uploader: Option<String>,
+    pub channel: Option<String>,
+    pub thumbnail: Option<String>,
+    pub filesize: Option<u64>,
+    pub http_headers: Option<HashMap<String, String>>,
+}
+
+impl Output {
+    pub fn aux_metadata(&self) -> AuxMetadata {
+        AuxMetadata {
+            title: self.title.clone(),
+            artist: self.uploader.clone(),
+            channel: self.channel.clone(),
+            duration: self.duration.map(std::time::Duration::from_secs_f64),
+            source_url: Some(self.webpage_url.clone().unwrap_or_else(|| self.url.clone())),
+            thumbnail: self.thumbnail.clone(),
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+enum Query {
+    Url(String),
+    Search(String),
+}
+
+/// A lazily instantiated `yt-dlp` source, like songbird's `YoutubeDl`, but
+/// honoring the user defined [`Config`].
+#[derive(Clone, Debug)]
+pub struct YtDlp {
+    client: HttpClient,
+    config: Arc<Config>,
+    query: Query,
+    metadata: Option<AuxMetadata>,
+    live: bool,
+    audio_cache: Option<Arc<AudioCache>>,
+}
+
+impl YtDlp {
+    pub fn new(client: HttpClient, config: Arc<Config>, url: String) -> Self {
+        Self {
+            client,
+            config,
+            query: Query::Url(url),
+            metadata: None,
+            live: false,
+            audio_cache: None,
+        }
+    }
+
+    pub fn new_search(client: HttpClient, config: Arc<Config>, query: String) -> Self {
+        Self {
+            client,
+            config,
+            query: Query::Search(query),
+            metadata: None,
+            live: false,
+            audio_cache: None,
+        }
+    }
+
+    /// Uses already known metadata, avoiding a `yt-dlp` call only to resolve it.
+    pub fn with_metadata(mut self, metadata: AuxMetadata) -> Self {
+        self.metadata = Some(metadata);
+        self
+    }
+
+    /// Plays from `audio_cache` when the track was already downloaded, streaming otherwise.
+    pub fn with_audio_cache(mut self, audio_cache: Option<Arc<AudioCache>>) -> Self {
+        self.audio_cache = audio_cache;
+        self
+    }
+
+    /// Whether the track is a live stream, only known once its metadata is resolved.
+    pub fn is_live(&self) -> bool {
+        self.live
+    }
+
+    async fn query(&mut self) -> Result<Output, AudioStreamError> {
+        let target = match &self.query {
+            Query::Url(url) => url.clone(),
+            Query::Search(query) => format!("ytsearch1:{query}"),
+        };
+
+        let mut outputs = run(&self.config, &target, &["--no-playlist"])
+            .await
+            .map_err(AudioStreamError::Fail)?;
+
+        if outputs.is_empty() {
+            let msg = format!("No results found for {target}");
+            return Err(AudioStreamError::Fail(msg.into()));
+        }
+
+        let output = outputs.swap_remove(0);
+        self.metadata = Some(output.aux_metadata());
+        self.live = output.is_live.unwrap_or(false);
+
+        Ok(output)
+    }
+}
+
+impl From<YtDlp> for Input {
+    fn from(val: YtDlp) -> Self {
+        Input::Lazy(Box::new(val))
+    }
+}
+
+#[async_trait::async_trait]
+impl Compose for YtDlp {
+    fn create(&mut self) -> Result<AudioStream<Box<dyn MediaSource>>, AudioStreamError> {
+        Err(AudioStreamError::Unsupported)
+    }
+
+    async fn create_async(
+        &mut self,
+    ) -> Result<AudioStream<Box<dyn MediaSource>>, AudioStreamError> {
+        if let (Some(audio_cache), Query::Url(url)) = (&self.audio_cache, &self.query) {
+            if let Some(path) = audio_cache.lookup(url).await {
+                return File::new(path).create_async().await;
+            }
+        }
+
+        let output = self.query().await?;
+
+        let mut headers = HeaderMap::default();
+        if let Some(map) = output.http_headers {
+            headers.extend(map.iter().filter_map(|(k, v)| {
+                let name = HeaderName::from_bytes(k.as_bytes()).ok()?;
+                let value = HeaderValue::from_str(v).ok()?;
+                Some((name, value))
+            }));
+        }
+
+        let mut request = HttpRequest {
+            client: self.client.clone(),
+            request: output.url,
+            headers,
+            content_length: output.filesize,
+        };
+
+        request.create_async().await
+    }
+
+    fn should_create_async(&self) -> bool {
+        true
+    }
+
+    async fn aux_metadata(&mut self) -> Result<AuxMetadata, AudioStreamError> {
+        if let Some(metadata) = &self.metadata {
+            return Ok(metadata.clone());
+        }
+
+        self.query().await?;
+        self.metadata
+            .clone()
+            .ok_or_else(|| AudioStreamError::Fail("Failed resolving track metadata".into()))
+    }
+}
@@ -0,0 +1,296 @@
+//! Test doubles for the processes and services Rina talks to: a scripted `yt-dlp`
+//! and a local HTTP server standing for audio hosts.
+
+#![allow(dead_code)]
+
+use std::collections::HashMap;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use rina::ytdlp;
+use serde_json::Value;
+use tempfile::TempDir;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+/// Script answering `yt-dlp -j <target> ...` with the output registered for `<target>`,
+/// recording the arguments of every call. Unknown targets fail as unsupported URLs.
+const FAKE_YTDLP: &str = r#"#!/bin/sh
+dir="$(dirname "$0")/responses"
+target=
+prev=
+for arg in "$@"; do
+    if [ "$prev" = "-j" ]; then
+        target=$arg
+    fi
+    prev=$arg
+done
+
+key=$(printf '%s' "$target" | tr -c 'A-Za-z0-9' '_')
+printf '%s\n' "$*" >> "$dir/$key.calls"
+
+if [ ! -f "$dir/$key.code" ]; then
+    echo "ERROR: Unsupported URL: $target" >&2
+    exit 1
+fi
+
+cat "$dir/$key.out"
+cat "$dir/$key.err" >&2
+exit "$(cat "$dir/$key.code")"
+"#;
+
+/// Scripted `yt-dlp` executable, run through [`FakeYtDlp::config`]. Lives in a temporary
+/// directory removed once dropped.
+pub struct FakeYtDlp {
+    /// Holds the script and its responses.
+    dir: TempDir,
+    program: PathBuf,
+    responses: PathBuf,
+}
+
+impl FakeYtDlp {
+    pub fn new() -> Self {
+        let dir = TempDir::new().expect("Failed creating fake yt-dlp directory");
+        let responses = dir.path().join("responses");
+        fs::create_dir(&responses).expect("Failed creating fake yt-dlp responses directory");
+
+        let program = dir.path().join("yt-dlp");
+        fs::write(&program, FAKE_YTDLP).expect("Failed writing fake yt-dlp");
+        fs::set_permissions(&program, fs::Permissions::from_mode(0o755))
+            .expect("Failed making fake yt-dlp executable");
+
+        Self {
+            dir,
+            program,
+            responses,
+        }
+    }
+
+    /// Default `yt-dlp` settings, running the scripted executable.
+    pub fn config(&self) -> ytdlp::Config {
+        ytdlp::Config {
+            program: self.program.to_string_lossy().into_owned(),
+            ..ytdlp::Config::default()
+        }
+    }
+
+    /// Makes `yt-dlp -j <target>` print each of `lines` as JSON and exit successfully.
+    pub fn respond(&self, target: &str, lines: &[Value]) {
+        let stdout = lines
+            .iter()
+            .map(|line| format!("{line}\n"))
+            .collect::<String>();
+        self.script(target, &stdout, "", 0);
+    }
+
+    /// Makes `yt-dlp -j <target>` print `stderr` and exit with `code`.
+    pub fn fail(&self, target: &str, stderr: &str, code: i32) {
+        self.script(target, "", stderr, code);
+    }
+
+    /// Arguments of every call made for `target`, in order.
+    pub fn calls(&self, target: &str) -> Vec<String> {
+        fs::read_to_string(self.file(target, "calls"))
+            .map(|calls| calls.lines().map(String::from).collect())
+            .unwrap_or_default()
+    }
+
+    fn script(&self, target: &str, stdout: &str, stderr: &str, code: i32) {
+        fs::write(self.file(target, "out"), stdout).expect("Failed writing fake stdout");
+        fs::write(self.file(target, "err"), stderr).expect("Failed writing fake stderr");
+        fs::write(self.file(target, "code"), code.to_string()).expect("Failed writing fake code");
+    }
+
+    fn file(&self, target: &str, extension: &str) -> PathBuf {
+        let key = target
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+            .collect::<String>();
+
+        Path::new(&self.responses).join(format!("{key}.{extension}"))
+    }
+}
+
+/// Request received by [`StubServer`].
+#[derive(Clone, Debug)]
+pub struct Request {
+    pub method: String,
+    pub path: String,
+    pub headers: HashMap<String, String>,
+    pub body: Vec<u8>,
+}
+
+impl Request {
+    pub fn json(&self) -> Value {
+        serde_json::from_slice(&self.body).expect("Expected request body to be JSON")
+    }
+}
+
+/// Canned response served by [`StubServer`].
+#[derive(Clone, Debug)]
+pub struct Response {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl Response {
+    pub fn new(status: u16) -> Self {
+        Self {
+            status,
+            headers: Vec::new(),
+            body: Vec::new(),
+        }
+    }
+
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    pub fn body(mut self, body: impl Into<Vec<u8>>) -> Self {
+        self.body = body.into();
+        self
+    }
+
+    pub fn json(self, body: &Value) -> Self {
+        self.header("content-type", "application/json")
+            .body(body.to_string())
+    }
+}
+
+type Route = Box<dyn Fn(&Request) -> Option<Response> + Send + Sync>;
+
+/// Local HTTP/1.1 server recording requests and answering them from routes, tried in the
+/// order they were added. Unrouted requests get a `404`.
+#[derive(Clone)]
+pub struct StubServer {
+    url: String,
+    routes: Arc<Mutex<Vec<Route>>>,
+    requests: Arc<Mutex<Vec<Request>>>,
+}
+
+impl StubServer {
+    pub async fn start() -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("Failed binding stub server");
+        let addr = listener.local_addr().expect("Expected stub server address");
+
+        let server = Self {
+            url: format!("http://{addr}"),
+            routes: Arc::default(),
+            requests: Arc::default(),
+        };
+
+        let accepting = server.clone();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let server = accepting.clone();
+                tokio::spawn(async move { server.serve(stream).await });
+            }
+        });
+
+        server
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    pub fn route(&self, route: impl Fn(&Request) -> Option<Response> + Send + Sync + 'static) {
+        self.routes.lock().unwrap().push(Box::new(route));
+    }
+
+    /// Answers `method` requests to `path` with `response`.
+    pub fn on(&self, method: &str, path: &str, response: Response) {
+        let (method, path) = (method.to_string(), path.to_string());
+        self.route(move |req| (req.method == method && req.path == path).then(|| response.clone()));
+    }
+
+    pub fn requests(&self) -> Vec<Request> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    async fn serve(&self, mut stream: TcpStream) {
+        let Some(request) = read_request(&mut stream).await else {
+            return;
+        };
+
+        let response = self
+            .routes
+            .lock()
+            .unwrap()
+            .iter()
+            .find_map(|route| route(&request))
+            .unwrap_or_else(|| Response::new(404));
+        self.requests.lock().unwrap().push(request);
+
+        let mut head = format!(
+            "HTTP/1.1 {} Stub\r\ncontent-length: {}\r\nconnection: close\r\n",
+            response.status,
+            response.body.len()
+        );
+        for (name, value) in &response.headers {
+            head.push_str(&format!("{name}: {value}\r\n"));
+        }
+        head.push_str("\r\n");
+
+        let _ = stream.write_all(head.as_bytes()).await;
+        let _ = stream.write_all(&response.body).await;
+        let _ = stream.shutdown().await;
+    }
+}
+
+async fn read_request(stream: &mut TcpStream) -> Option<Request> {
+    let mut buf = Vec::new();
+    let head_end = loop {
+        if let Some(pos) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+            break pos;
+        }
+
+        let mut chunk = [0; 4096];
+        let read = stream
+            .read(&mut chunk)
+            .await
+            .ok()
+            .filter(|read| *read > 0)?;
+        buf.extend_from_slice(&chunk[..read]);
+    };
+
+    let head = String::from_utf8_lossy(&buf[..head_end]).into_owned();
+    let mut lines = head.lines();
+    let mut request_line = lines.next()?.split_whitespace();
+    let method = request_line.next()?.to_string();
+    let path = request_line.next()?.to_string();
+
+    let headers = lines
+        .filter_map(|line| line.split_once(':'))
+        .map(|(name, value)| (name.trim().to_lowercase(), value.trim().to_string()))
+        .collect::<HashMap<_, _>>();
+
+    let len = headers
+        .get("content-length")
+        .and_then(|len| len.parse::<usize>().ok())
+        .unwrap_or_default();
+
+    let mut body = buf.split_off(head_end + 4);
+    while body.len() < len {
+        let mut chunk = [0; 4096];
+        let read = stream
+            .read(&mut chunk)
+            .await
+            .ok()
+            .filter(|read| *read > 0)?;
+        body.extend_from_slice(&chunk[..read]);
+    }
+
+    Some(Request {
+        method,
+        path,
+        headers,
+        body,
+    })
+}
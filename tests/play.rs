@@ -0,0 +1,568 @@
+mod common;
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use reqwest::Client as HttpClient;
+use rina::attachment::{self, Attachment};
+use rina::embed::{Embed, EmbedBuilder};
+use rina::metadata_cache::{self, MetadataCache};
+use rina::playlist;
+use rina::quota::{Limits, Quota};
+use rina::resolve::{self, PlayContext, PlayQueue, ResolveError, Resolved};
+use rina::stream;
+use rina::ytdlp;
+use serde_json::{json, Value};
+use tokio::sync::{Mutex, MutexGuard};
+
+use common::{FakeYtDlp, Response, StubServer};
+
+fn track(id: &str, title: &str, duration: f64) -> Value {
+    json!({
+        "url": format!("https://cdn.example.com/{id}.webm"),
+        "webpage_url": format!("https://www.youtube.com/watch?v={id}"),
+        "title": title,
+        "duration": duration,
+        "uploader": "Uploader",
+    })
+}
+
+fn limits(max_track_duration: Option<Duration>) -> Limits {
+    Limits {
+        max_queue_length: None,
+        max_user_tracks: None,
+        max_track_duration,
+        allow_streams: true,
+    }
+}
+
+async fn resolve_with(
+    ytdlp: &FakeYtDlp,
+    cache: &MetadataCache,
+    query: &str,
+) -> Result<Resolved, ResolveError> {
+    let config = Arc::new(ytdlp.config());
+    // stub servers listen on loopback
+    resolve::resolve(&HttpClient::new(), &config, cache, true, query).await
+}
+
+async fn resolve(ytdlp: &FakeYtDlp, query: &str) -> Result<Resolved, ResolveError> {
+    let cache = MetadataCache::new(metadata_cache::Config::default());
+    resolve_with(ytdlp, &cache, query).await
+}
+
+/// Queue standing for a voice call, recording what `!play` enqueues into it.
+struct RecordingQueue {
+    quota: Quota,
+    enqueued: Vec<Resolved>,
+    attachments: Vec<String>,
+}
+
+impl RecordingQueue {
+    fn titles(&self) -> Vec<&str> {
+        self.enqueued
+            .iter()
+            .flat_map(|resolved| match resolved {
+                Resolved::Playlist(tracks) => tracks.iter().map(|t| t.title.as_str()).collect(),
+                Resolved::Track(metadata) => vec![metadata.title.as_str()],
+                Resolved::Stream { title, .. } => vec![title.as_str()],
+            })
+            .collect()
+    }
+}
+
+struct LockedQueue<'a>(MutexGuard<'a, RecordingQueue>);
+
+impl PlayQueue for LockedQueue<'_> {
+    async fn quota(&mut self) -> Quota {
+        self.0.quota.clone()
+    }
+
+    async fn enqueue(&mut self, resolved: Resolved) {
+        self.0.enqueued.push(resolved);
+    }
+
+    async fn enqueue_attachment(&mut self, attachment: &Attachment) {
+        self.0.attachments.push(attachment.filename.clone());
+    }
+}
+
+/// Chat platform `!play` runs on, recording its replies and what it enqueues.
+struct FakeCommand {
+    ytdlp_config: Arc<ytdlp::Config>,
+    /// Reason joining the author voice channel fails with, if any.
+    join_error: Option<&'static str>,
+    joined: bool,
+    queue: Mutex<RecordingQueue>,
+    replies: std::sync::Mutex<Vec<Embed>>,
+}
+
+impl FakeCommand {
+    fn new(ytdlp: &FakeYtDlp, quota: Quota) -> Self {
+        Self {
+            ytdlp_config: Arc::new(ytdlp.config()),
+            join_error: None,
+            joined: false,
+            queue: Mutex::new(RecordingQueue {
+                quota,
+                enqueued: Vec::new(),
+                attachments: Vec::new(),
+            }),
+            replies: std::sync::Mutex::default(),
+        }
+    }
+
+    fn queue(&mut self) -> &mut RecordingQueue {
+        self.queue.get_mut()
+    }
+
+    fn replies(&self) -> Vec<Embed> {
+        self.replies.lock().unwrap().clone()
+    }
+}
+
+impl PlayContext for FakeCommand {
+    type JoinError = &'static str;
+
+    type Queue<'a> = LockedQueue<'a>;
+
+    async fn join(&mut self) -> Result<(), &'static str> {
+        self.joined = true;
+        self.join_error.map_or(Ok(()), Err)
+    }
+
+    async fn lock(&self) -> LockedQueue<'_> {
+        assert!(self.joined, "Expected queue to be locked once joined");
+        LockedQueue(self.queue.lock().await)
+    }
+
+    async fn resolve(&self, query: &str) -> Result<Resolved, ResolveError> {
+        let cache = MetadataCache::new(metadata_cache::Config::default());
+        resolve::resolve(&HttpClient::new(), &self.ytdlp_config, &cache, true, query).await
+    }
+
+    async fn reply(&self, reply: EmbedBuilder) {
+        self.replies.lock().unwrap().push(reply.finish());
+    }
+}
+
+/// Runs `!play <query>` with `attachments` uploaded along.
+async fn play(command: &mut FakeCommand, query: Option<&str>, attachments: &[Attachment]) {
+    let config = attachment::Config {
+        max_size: 1024 * 1024,
+    };
+    resolve::play_command(command, query, attachments, &config).await;
+}
+
+fn upload(filename: &str, content_type: &str, size: u32) -> Attachment {
+    Attachment {
+        filename: String::from(filename),
+        url: format!("https://cdn.discordapp.com/attachments/1/2/{filename}"),
+        size,
+        content_type: Some(String::from(content_type)),
+    }
+}
+
+#[tokio::test]
+async fn queries_flat_playlists() {
+    let ytdlp = FakeYtDlp::new();
+    let url = "https://www.youtube.com/playlist?list=PLflat";
+    ytdlp.respond(
+        url,
+        &[
+            track("one", "First", 61.5),
+            json!({ "url": "https://www.youtube.com/watch?v=live", "is_live": true, "channel": "Radio" }),
+        ],
+    );
+
+    let tracks = playlist::query(&ytdlp.config(), url)
+        .await
+        .expect("Expected playlist to be queried");
+
+    assert_eq!(tracks.len(), 2);
+    assert_eq!(tracks[0].title, "First");
+    assert_eq!(tracks[0].duration, Some(61.5));
+    assert_eq!(tracks[0].uploader.as_deref(), Some("Uploader"));
+    assert_eq!(tracks[1].title, "Unknown");
+    assert_eq!(tracks[1].uploader.as_deref(), Some("Radio"));
+    assert!(tracks[1].live);
+
+    let calls = ytdlp.calls(url);
+    assert_eq!(calls.len(), 1);
+    assert!(calls[0].starts_with(&format!("-j {url} -f ")));
+    assert!(calls[0].ends_with("--flat-playlist"));
+}
+
+#[tokio::test]
+async fn reports_failed_playlist_queries() {
+    let ytdlp = FakeYtDlp::new();
+    let url = "https://www.youtube.com/playlist?list=PLprivate";
+    ytdlp.fail(url, "ERROR: This playlist is private", 1);
+
+    let err = playlist::query(&ytdlp.config(), url)
+        .await
+        .expect_err("Expected private playlist to fail");
+
+    assert_eq!(
+        err.to_string(),
+        format!(
+            "Failed querying playlist: {} failed: ERROR: This playlist is private",
+            ytdlp.config().program
+        )
+    );
+}
+
+#[tokio::test]
+async fn resolves_and_caches_searches() {
+    let ytdlp = FakeYtDlp::new();
+    ytdlp.respond(
+        "ytsearch1:cached song",
+        &[track("cached", "Cached Song", 200.0)],
+    );
+
+    let cache = MetadataCache::new(metadata_cache::Config::default());
+    for _ in 0..2 {
+        let Ok(Resolved::Track(metadata)) = resolve_with(&ytdlp, &cache, "cached song").await
+        else {
+            panic!("Expected search to resolve a single track");
+        };
+
+        assert_eq!(metadata.title, "Cached Song");
+        assert_eq!(metadata.url, "https://www.youtube.com/watch?v=cached");
+        assert_eq!(metadata.duration, Some(200.0));
+    }
+
+    let calls = ytdlp.calls("ytsearch1:cached song");
+    assert_eq!(calls.len(), 1);
+    assert!(calls[0].ends_with("--no-playlist"));
+}
+
+#[tokio::test]
+async fn resolves_playlist_urls() {
+    let ytdlp = FakeYtDlp::new();
+    let url = "https://www.youtube.com/watch?v=a&list=PLresolve";
+    ytdlp.respond(url, &[track("a", "A", 10.0), track("b", "B", 20.0)]);
+
+    let Ok(Resolved::Playlist(tracks)) = resolve(&ytdlp, url).await else {
+        panic!("Expected playlist URL to resolve many tracks");
+    };
+
+    let titles = tracks.iter().map(|t| t.title.as_str()).collect::<Vec<_>>();
+    assert_eq!(titles, ["A", "B"]);
+}
+
+#[tokio::test]
+async fn reports_unresolved_queries() {
+    let ytdlp = FakeYtDlp::new();
+    ytdlp.respond("ytsearch1:nothing at all", &[]);
+    ytdlp.fail(
+        "https://www.youtube.com/watch?v=gone",
+        "ERROR: Video unavailable",
+        1,
+    );
+    ytdlp.fail(
+        "https://www.youtube.com/watch?v=x&list=PLgone",
+        "ERROR: Not found",
+        2,
+    );
+
+    let not_found = resolve(&ytdlp, "nothing at all").await;
+    assert_eq!(not_found.unwrap_err(), ResolveError::NotFound);
+
+    let unavailable = resolve(&ytdlp, "https://www.youtube.com/watch?v=gone").await;
+    assert_eq!(unavailable.unwrap_err(), ResolveError::NotFound);
+
+    let playlist = resolve(&ytdlp, "https://www.youtube.com/watch?v=x&list=PLgone").await;
+    assert_eq!(playlist.unwrap_err(), ResolveError::Playlist);
+}
+
+#[tokio::test]
+async fn resolves_direct_streams_without_ytdlp() {
+    let ytdlp = FakeYtDlp::new();
+    let server = StubServer::start().await;
+    let audio = Response::new(200)
+        .header("content-type", "audio/mpeg")
+        .body(vec![0; 64]);
+    server.on("GET", "/radio", audio.clone().header("icy-name", "Stub FM"));
+    server.on("GET", "/live/stream.mp3", audio);
+
+    let Ok(Resolved::Stream { title, icy, .. }) =
+        resolve(&ytdlp, &format!("{}/radio", server.url())).await
+    else {
+        panic!("Expected ICY server to resolve a stream");
+    };
+    assert_eq!(title, "Stub FM");
+    assert!(icy);
+
+    let url = format!("{}/live/stream.mp3", server.url());
+    let Ok(Resolved::Stream { title, icy, .. }) = resolve(&ytdlp, &url).await else {
+        panic!("Expected audio file to resolve a stream");
+    };
+    assert_eq!(title, "127.0.0.1/stream.mp3");
+    assert!(!icy);
+
+    let probes = server.requests();
+    assert_eq!(probes.len(), 2);
+    assert_eq!(
+        probes[0].headers.get("icy-metadata").map(String::as_str),
+        Some("1")
+    );
+}
+
+#[tokio::test]
+async fn rejects_private_hosts() {
+    let server = StubServer::start().await;
+    let ytdlp = FakeYtDlp::new();
+    let config = Arc::new(ytdlp.config());
+    let cache = MetadataCache::new(metadata_cache::Config::default());
+
+    let url = format!("{}/radio", server.url());
+    let err = resolve::resolve(&HttpClient::new(), &config, &cache, false, &url)
+        .await
+        .expect_err("Expected loopback URL to be rejected");
+
+    assert_eq!(err, ResolveError::PrivateHost);
+    assert!(server.requests().is_empty());
+}
+
+#[tokio::test]
+async fn refuses_redirects_to_private_hosts() {
+    let private = StubServer::start().await;
+    private.on(
+        "GET",
+        "/radio.mp3",
+        Response::new(200).header("content-type", "audio/mpeg"),
+    );
+    let port = private.url().rsplit(':').next().unwrap();
+
+    let public = StubServer::start().await;
+    public.on(
+        "GET",
+        "/ip",
+        Response::new(302).header("location", &format!("{}/radio.mp3", private.url())),
+    );
+    public.on(
+        "GET",
+        "/hostname",
+        Response::new(302).header("location", &format!("http://localhost:{port}/radio.mp3")),
+    );
+
+    // the stub standing for a public host listens on loopback too, reached by its address
+    let client = stream::public_hosts_only(HttpClient::builder(), None)
+        .build()
+        .unwrap();
+    for path in ["/ip", "/hostname"] {
+        let url = format!("{}{path}", public.url());
+        assert_eq!(stream::probe(&client, &url).await, None, "{path}");
+    }
+
+    let direct = client
+        .get(format!("http://localhost:{port}/radio.mp3"))
+        .send()
+        .await;
+    assert!(direct.is_err());
+
+    assert_eq!(public.requests().len(), 2);
+    assert!(private.requests().is_empty());
+}
+
+#[tokio::test]
+async fn resolves_web_pages_through_ytdlp() {
+    let ytdlp = FakeYtDlp::new();
+    let server = StubServer::start().await;
+    server.on(
+        "GET",
+        "/watch",
+        Response::new(200)
+            .header("content-type", "text/html")
+            .body("<html></html>"),
+    );
+
+    let url = format!("{}/watch", server.url());
+    ytdlp.respond(&url, &[track("page", "Page Track", 30.0)]);
+
+    let Ok(Resolved::Track(metadata)) = resolve(&ytdlp, &url).await else {
+        panic!("Expected web page to resolve a track");
+    };
+    assert_eq!(metadata.title, "Page Track");
+    assert_eq!(ytdlp.calls(&url).len(), 1);
+}
+
+#[tokio::test]
+async fn replies_with_enqueued_and_rejected_tracks() {
+    let ytdlp = FakeYtDlp::new();
+    let url = "https://www.youtube.com/watch?v=short&list=PLmixed";
+    ytdlp.respond(
+        url,
+        &[
+            track("short", "Short", 120.0),
+            track("long", "Long", 3600.0),
+            track("medium", "Medium", 300.0),
+        ],
+    );
+
+    let mut command = FakeCommand::new(
+        &ytdlp,
+        Quota::new(limits(Some(Duration::from_secs(600))), 0, 0),
+    );
+    play(&mut command, Some(url), &[]).await;
+
+    assert_eq!(command.queue().titles(), ["Short", "Medium"]);
+
+    let replies = command.replies();
+    assert_eq!(replies.len(), 1);
+    assert_eq!(replies[0].title.as_deref(), Some("!play"));
+    assert!(!replies[0].error);
+    assert_eq!(
+        replies[0].description.as_deref().unwrap(),
+        "2 tracks added to the queue\n\nRejected 1 tracks:\n- Long: Track is 1h long, over the limit of 10m\n"
+    );
+}
+
+#[tokio::test]
+async fn replies_with_rejected_track() {
+    let ytdlp = FakeYtDlp::new();
+    ytdlp.respond("ytsearch1:full queue", &[track("full", "Full", 60.0)]);
+
+    let limits = Limits {
+        max_user_tracks: Some(3),
+        ..limits(None)
+    };
+    let mut command = FakeCommand::new(&ytdlp, Quota::new(limits, 5, 3));
+    play(&mut command, Some("full queue"), &[]).await;
+
+    assert!(command.queue().enqueued.is_empty());
+
+    let replies = command.replies();
+    assert!(replies[0].error);
+    assert_eq!(
+        replies[0].description.as_deref().unwrap(),
+        "Could not add Full: Each member can have up to 3 tracks in the queue"
+    );
+}
+
+#[tokio::test]
+async fn replies_with_unresolved_query() {
+    let ytdlp = FakeYtDlp::new();
+    ytdlp.respond("ytsearch1:no such song", &[]);
+
+    let mut command = FakeCommand::new(&ytdlp, Quota::new(limits(None), 0, 0));
+    play(&mut command, Some("no such song"), &[]).await;
+
+    assert!(command.queue().enqueued.is_empty());
+
+    let replies = command.replies();
+    assert_eq!(replies[0].title.as_deref(), Some("!play"));
+    assert!(replies[0].error);
+    assert_eq!(
+        replies[0].description.as_deref().unwrap(),
+        ResolveError::NotFound.description("no such song")
+    );
+}
+
+#[tokio::test]
+async fn requires_query_or_attachments() {
+    let ytdlp = FakeYtDlp::new();
+    let mut command = FakeCommand::new(&ytdlp, Quota::new(limits(None), 0, 0));
+    play(&mut command, None, &[]).await;
+
+    assert!(!command.joined);
+
+    let replies = command.replies();
+    assert_eq!(replies.len(), 1);
+    assert!(replies[0].error);
+    assert_eq!(
+        replies[0].description.as_deref(),
+        Some("Missing music or URL argument")
+    );
+}
+
+#[tokio::test]
+async fn replies_when_author_voice_channel_cannot_be_joined() {
+    let ytdlp = FakeYtDlp::new();
+    ytdlp.respond("ytsearch1:not joined", &[track("joined", "Joined", 60.0)]);
+
+    let mut command = FakeCommand::new(&ytdlp, Quota::new(limits(None), 0, 0));
+    command.join_error = Some("User not in a voice channel");
+    play(&mut command, Some("not joined"), &[]).await;
+
+    assert!(command.queue().enqueued.is_empty());
+    assert!(ytdlp.calls("ytsearch1:not joined").is_empty());
+
+    let replies = command.replies();
+    assert_eq!(replies.len(), 1);
+    assert!(replies[0].error);
+    assert_eq!(
+        replies[0].description.as_deref(),
+        Some("User not in a voice channel")
+    );
+}
+
+#[tokio::test]
+async fn enqueues_valid_attachments_before_query() {
+    let ytdlp = FakeYtDlp::new();
+    ytdlp.respond("ytsearch1:after uploads", &[track("after", "After", 60.0)]);
+
+    let attachments = [
+        upload("song.mp3", "audio/mpeg", 512),
+        upload("notes.txt", "text/plain", 16),
+        upload("huge.flac", "audio/flac", 3 * 1024 * 1024),
+    ];
+    let mut command = FakeCommand::new(&ytdlp, Quota::new(limits(None), 0, 0));
+    play(&mut command, Some("after uploads"), &attachments).await;
+
+    assert_eq!(command.queue().attachments, ["song.mp3"]);
+    assert_eq!(command.queue().titles(), ["After"]);
+
+    let replies = command
+        .replies()
+        .into_iter()
+        .map(|reply| (reply.error, reply.description.unwrap()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        replies,
+        [
+            (
+                true,
+                String::from(
+                    "Could not play attachments:\n\
+                     notes.txt: unsupported file type `text/plain`\n\
+                     huge.flac: file has 3.0MB, but max allowed is 1.0MB\n"
+                )
+            ),
+            (false, String::from("Track song.mp3 added to queue\n")),
+            (false, String::from("Track After added to queue")),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn stops_when_every_attachment_is_rejected() {
+    let ytdlp = FakeYtDlp::new();
+    let mut command = FakeCommand::new(&ytdlp, Quota::new(limits(None), 0, 0));
+    play(&mut command, None, &[upload("notes.txt", "text/plain", 16)]).await;
+
+    assert!(!command.joined);
+    assert_eq!(command.replies().len(), 1);
+    assert!(command.queue().attachments.is_empty());
+}
+
+#[tokio::test]
+async fn reports_missing_ytdlp() {
+    let config = ytdlp::Config {
+        program: String::from("/nonexistent/yt-dlp"),
+        ..ytdlp::Config::default()
+    };
+    let expected = "Could not find executable /nonexistent/yt-dlp";
+
+    let err = ytdlp::run(&config, "ytsearch1:song", &[])
+        .await
+        .unwrap_err();
+    assert_eq!(err.to_string(), expected);
+
+    let url = "https://www.youtube.com/watch?v=missing";
+    let err = ytdlp::download(&config, url, "missing.%(ext)s")
+        .await
+        .unwrap_err();
+    assert_eq!(err.to_string(), expected);
+}